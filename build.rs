@@ -1,73 +1,274 @@
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
     let out_path = PathBuf::from(&out_dir);
-    let orig_dir = env::current_dir().unwrap();
 
-    // Check for clang dependency
-    if Command::new("clang").arg("-v").output().is_err() {
-        eprintln!("Clang is required for bindgen, please check installation instructions: https://rust-lang.github.io/rust-bindgen/requirements.html");
-        std::process::exit(1);
+    // Link against a prebuilt libswitchtec, an installed libswitchtec or the vendored
+    // switchtec-user submodule, getting back the include paths for the library's headers
+    println!("cargo:rerun-if-env-changed=SWITCHTEC_LIB_DIR");
+    let include_paths = if let Some(lib_dir) = env::var_os("SWITCHTEC_LIB_DIR") {
+        link_prebuilt_lib(Path::new(&lib_dir))
+    } else if env::var_os("CARGO_FEATURE_SYSTEM_LIB").is_some() {
+        link_system_lib()
+    } else {
+        build_vendored_lib(&out_path)
+    };
+
+    // Generate Rust Bindings for C Library, reusing cached bindings if enabled
+    if env::var_os("CARGO_FEATURE_CACHE_BINDINGS").is_some() {
+        cached_bindings(&out_path, &include_paths);
+    } else {
+        generate_bindings(&out_path.join("bindings.rs"), &include_paths);
     }
+}
 
-    // Make sure that switchtec-user submodule is available locally
-    Command::new("git")
-        .arg("submodule")
-        .arg("update")
-        .arg("--init")
-        .output()
-        .expect("couldn't download switchtec-user submodule");
-
-    // Generate Rust Bindings for C Library
-    let bindings = bindgen::Builder::default()
-        .header("switchtec-user/inc/switchtec/switchtec.h")
-        .clang_arg("-Iswitchtec-user/inc")
-        .formatter(bindgen::Formatter::Rustfmt)
-        .parse_callbacks(Box::new(bindgen::CargoCallbacks))
-        .generate()
-        .expect("Unable to generate bindings");
+/// Link the prebuilt `libswitchtec.a` in `lib_dir`, skipping the vendored build entirely
+///
+/// Bindings are generated from the headers in `lib_dir/include` when they're there, and from
+/// the switchtec-user submodule otherwise
+fn link_prebuilt_lib(lib_dir: &Path) -> Vec<PathBuf> {
+    let archive = lib_dir.join("libswitchtec.a");
+    if !archive.is_file() {
+        panic!(
+            "SWITCHTEC_LIB_DIR is set to {}, but it doesn't contain libswitchtec.a",
+            lib_dir.display()
+        );
+    }
+    println!("cargo:rerun-if-changed={}", archive.display());
+    println!("cargo:rustc-link-search=native={}", lib_dir.display());
+    println!("cargo:rustc-link-lib=static=switchtec");
 
-    bindings
-        .write_to_file(out_path.join("bindings.rs"))
-        .expect("Unable to save bindings");
+    let include_dir = lib_dir.join("include");
+    if include_dir.join("switchtec/switchtec.h").is_file() {
+        return vec![include_dir];
+    }
+    let submodule_inc = PathBuf::from("switchtec-user/inc");
+    if !submodule_inc.join("switchtec/switchtec.h").is_file() {
+        panic!(
+            "no switchtec headers found for the prebuilt libswitchtec: put them in {} or \
+             initialize the switchtec-user submodule",
+            include_dir.display()
+        );
+    }
+    vec![submodule_inc]
+}
+
+/// Locate an installed libswitchtec with `pkg-config`, which also emits the
+/// `cargo:rustc-link-*` lines needed to link it
+#[cfg(feature = "system-lib")]
+fn link_system_lib() -> Vec<PathBuf> {
+    let library = pkg_config::Config::new()
+        .probe("switchtec")
+        .unwrap_or_else(|e| panic!("couldn't find an installed libswitchtec: {e}"));
+    library.include_paths
+}
+
+#[cfg(not(feature = "system-lib"))]
+fn link_system_lib() -> Vec<PathBuf> {
+    unreachable!("system-lib feature is not enabled")
+}
+
+/// C sources of the vendored switchtec-user library, relative to the crate root
+const VENDORED_SOURCES: &[&str] = &[
+    "switchtec-user/lib/crc.c",
+    "switchtec-user/lib/diag.c",
+    "switchtec-user/lib/events.c",
+    "switchtec-user/lib/fabric.c",
+    "switchtec-user/lib/fw.c",
+    "switchtec-user/lib/gas_mrpc.c",
+    "switchtec-user/lib/mfg.c",
+    "switchtec-user/lib/mrpc.c",
+    "switchtec-user/lib/pmon.c",
+    "switchtec-user/lib/switchtec.c",
+    "switchtec-user/lib/platform/platform.c",
+    "switchtec-user/lib/platform/linux.c",
+    "switchtec-user/lib/platform/linux-eth.c",
+    "switchtec-user/lib/platform/linux-i2c.c",
+    "switchtec-user/lib/platform/linux-uart.c",
+    "switchtec-user/lib/platform/gasops.c",
+];
 
-    // Compile switchtec-user library
-    env::set_current_dir(&out_path).unwrap();
+/// Compile and statically link the vendored switchtec-user submodule
+fn build_vendored_lib(out_path: &Path) -> Vec<PathBuf> {
+    // Only rebuild when the library sources (or this script) change, instead of on any change
+    // to the package
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=wrapper.h");
+    println!("cargo:rerun-if-changed=switchtec-user/inc");
+    println!("cargo:rerun-if-changed=switchtec-user/lib");
 
-    let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
-    let root_path: PathBuf = [&root_dir, "switchtec-user", "configure"].iter().collect();
-    Command::new(root_path)
-        .output()
-        .expect("couldn't run ./configure");
+    // Make sure that switchtec-user submodule is available locally
+    if !Path::new("switchtec-user/configure").is_file() {
+        run_command(
+            Command::new("git").args(["submodule", "update", "--init"]),
+            "download the switchtec-user submodule (it can also be checked out with \
+             `git submodule update --init`)",
+        );
+    }
 
-    env::set_current_dir(orig_dir).unwrap();
+    // Configure switchtec-user, which generates `config.h` in OUT_DIR. It only depends on the
+    // build host and target (which each get their own OUT_DIR), so an existing `config.h`
+    // from a previous build is reused
+    let mut build = cc::Build::new();
+    if !out_path.join("config.h").is_file() {
+        let root_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let configure: PathBuf = [&root_dir, "switchtec-user", "configure"].iter().collect();
+        let mut configure = Command::new(configure);
+        configure.current_dir(out_path);
+        // When cross-compiling, configure for the target with the same C compiler that `cc`
+        // uses (E.g. from `CC_aarch64_unknown_linux_gnu`)
+        if let Some(target) = cross_target() {
+            configure
+                .arg(format!("--host={target}"))
+                .env("CC", build.get_compiler().path());
+        }
+        run_command(&mut configure, "configure switchtec-user");
+    }
 
-    cc::Build::new()
+    // The `parallel` feature of `cc` compiles the sources concurrently
+    build
         .include("switchtec-user/inc")
-        .include(&out_dir)
+        .include(out_path)
         .include("switchtec-user")
         .include("switchtec-user/lib")
         .include("switchtec-user/lib/platform")
-        .file("switchtec-user/lib/crc.c")
-        .file("switchtec-user/lib/diag.c")
-        .file("switchtec-user/lib/events.c")
-        .file("switchtec-user/lib/fabric.c")
-        .file("switchtec-user/lib/fw.c")
-        .file("switchtec-user/lib/gas_mrpc.c")
-        .file("switchtec-user/lib/mfg.c")
-        .file("switchtec-user/lib/mrpc.c")
-        .file("switchtec-user/lib/pmon.c")
-        .file("switchtec-user/lib/switchtec.c")
-        .file("switchtec-user/lib/platform/platform.c")
-        .file("switchtec-user/lib/platform/linux.c")
-        .file("switchtec-user/lib/platform/linux-eth.c")
-        .file("switchtec-user/lib/platform/linux-i2c.c")
-        .file("switchtec-user/lib/platform/linux-uart.c")
-        .file("switchtec-user/lib/platform/gasops.c")
+        .files(VENDORED_SOURCES)
         .warnings(false)
         .extra_warnings(false)
         .compile("libswitchtec.a");
+
+    vec![PathBuf::from("switchtec-user/inc")]
+}
+
+/// Run an external `command`, panicking with the reason (including the command's stderr) if
+/// it can't be run or fails
+///
+/// `action` describes what the command does, E.g. "configure switchtec-user"
+fn run_command(command: &mut Command, action: &str) {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            panic!("couldn't {action}: `{program}` was not found, please install it")
+        }
+        Err(e) => panic!("couldn't {action}: failed to run `{program}`: {e}"),
+    };
+    if !output.status.success() {
+        panic!(
+            "couldn't {action}: `{program}` failed ({}):\n{}{}",
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+/// Generate the Rust bindings for the `switchtec-user` headers and save them to `dest`
+fn generate_bindings(dest: &Path, include_paths: &[PathBuf]) {
+    // Check for clang dependency. bindgen uses the host's libclang, which a cross-compiling
+    // setup may provide without a `clang` binary, so this is only checked for native builds
+    let target = cross_target();
+    if target.is_none() && Command::new("clang").arg("-v").output().is_err() {
+        panic!("Clang is required for bindgen, please check installation instructions: https://rust-lang.github.io/rust-bindgen/requirements.html");
+    }
+
+    let bindings = bindgen::Builder::default()
+        .header("wrapper.h")
+        .clang_args(
+            include_paths
+                .iter()
+                .map(|path| format!("-I{}", path.display())),
+        )
+        // Lay out types for the target instead of the host when cross-compiling
+        .clang_args(target.map(|target| format!("--target={target}")))
+        // Formatting needs rustfmt at build time, so it can be disabled for minimal environments
+        .formatter(if env::var_os("CARGO_FEATURE_RUSTFMT_BINDINGS").is_some() {
+            bindgen::Formatter::Rustfmt
+        } else {
+            bindgen::Formatter::None
+        })
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks))
+        .generate()
+        .expect("Unable to generate bindings");
+
+    bindings
+        .write_to_file(dest)
+        .expect("Unable to save bindings");
+}
+
+/// Get the target triple if cross-compiling (the target differs from the host)
+fn cross_target() -> Option<String> {
+    let target = env::var("TARGET").unwrap();
+    (target != env::var("HOST").unwrap()).then_some(target)
+}
+
+/// Reuse previously generated bindings when the `switchtec-user` headers haven't changed
+///
+/// Bindings are cached in `SWITCHTEC_BINDINGS_CACHE_DIR` (defaulting to a directory in the
+/// target profile dir, so it survives rebuilds of this crate) under a name derived from
+/// a hash of the header contents. Any header change produces a new hash, and a cache miss
+/// falls back to running bindgen.
+fn cached_bindings(out_path: &Path, include_paths: &[PathBuf]) {
+    println!("cargo:rerun-if-env-changed=SWITCHTEC_BINDINGS_CACHE_DIR");
+    let cache_dir = env::var_os("SWITCHTEC_BINDINGS_CACHE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            // OUT_DIR is `<target>/<profile>/build/<pkg>-<hash>/out`
+            out_path
+                .ancestors()
+                .nth(3)
+                .expect("unexpected OUT_DIR layout")
+                .join("switchtec-bindings")
+        });
+    let header_hash = hash_headers(include_paths);
+    // Type layouts depend on the target, so bindings for different targets are cached apart
+    let target = env::var("TARGET").unwrap();
+    let cached = cache_dir.join(format!("bindings-{target}-{header_hash:016x}.rs"));
+    let dest = out_path.join("bindings.rs");
+
+    if cached.is_file() {
+        fs::copy(&cached, &dest).expect("Unable to copy cached bindings");
+        return;
+    }
+
+    generate_bindings(&dest, include_paths);
+    fs::create_dir_all(&cache_dir).expect("Unable to create bindings cache dir");
+    fs::copy(&dest, &cached).expect("Unable to save bindings to cache");
+}
+
+/// FNV-1a hash over `wrapper.h` and the paths and contents of the `switchtec/*.h` headers in
+/// `include_paths`
+///
+/// `wrapper.h` is part of the hash since it picks which headers bindings are generated for,
+/// so including another header has to miss the cache.
+///
+/// This is only used to detect header changes for the bindings cache, and is stable across
+/// Rust versions (unlike `std::hash::DefaultHasher`)
+fn hash_headers(include_paths: &[PathBuf]) -> u64 {
+    let mut headers: Vec<PathBuf> = include_paths
+        .iter()
+        .filter_map(|path| fs::read_dir(path.join("switchtec")).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "h"))
+        .collect();
+    headers.sort();
+    headers.insert(0, PathBuf::from("wrapper.h"));
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for header in headers {
+        println!("cargo:rerun-if-changed={}", header.display());
+        let name = header.to_string_lossy().into_owned();
+        let contents = fs::read(&header).expect("couldn't read header");
+        for byte in name.bytes().chain(contents) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
 }
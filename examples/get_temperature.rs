@@ -37,8 +37,8 @@ fn main() -> anyhow::Result<()> {
 
     // Don't bubble up Errors with '?', since we still have to manually close the device
     let temp = get_temperature(dev);
-    // Switchtec returns 100ths of a degree C
-    println!("Temp: {}C", temp / 100.0);
+    // `switchtec_die_temp` returns degrees C, or a negative value on error
+    println!("Temp: {temp:.1}C");
 
     unsafe {
         switchtec_close(dev);
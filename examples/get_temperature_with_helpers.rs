@@ -3,7 +3,7 @@
 
 use std::env;
 
-use switchtec_user_sys::{switchtec_die_temp, SwitchtecDevice};
+use switchtec_user_sys::SwitchtecDevice;
 
 fn main() -> anyhow::Result<()> {
     let path = env::args()
@@ -11,10 +11,8 @@ fn main() -> anyhow::Result<()> {
         .next()
         .unwrap_or_else(|| "/dev/pciswitch0".to_owned());
     let dev = SwitchtecDevice::open(path)?;
-    unsafe {
-        let temp = switchtec_die_temp(*dev);
-        println!("Temperature: {}", temp);
-    }
+    let temp = dev.die_temp()?;
+    println!("Temperature: {temp}");
 
     Ok(())
 }
@@ -0,0 +1,22 @@
+//! This is an example of discovering the Switchtec devices on a system with
+//! `SwitchtecDevice::list()`, and opening each of them
+
+use switchtec_user_sys::SwitchtecDevice;
+
+fn main() -> anyhow::Result<()> {
+    let devices = SwitchtecDevice::list()?;
+    if devices.is_empty() {
+        println!("No Switchtec devices found");
+    }
+
+    for info in devices {
+        println!(
+            "{}\t{}\t{} {}\t{}",
+            info.name, info.pci_dev, info.product_id, info.product_rev, info.fw_version
+        );
+        let device = SwitchtecDevice::open(&info.path)?;
+        println!("  partition: {}", device.partition());
+    }
+
+    Ok(())
+}
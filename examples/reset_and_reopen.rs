@@ -0,0 +1,42 @@
+//! This is an example of resetting a Switchtec device with `SwitchtecDevice::hard_reset()`,
+//! then waiting for it to come back so it can be opened again
+
+use std::env;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use switchtec_user_sys::SwitchtecDevice;
+
+fn main() -> anyhow::Result<()> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "/dev/pciswitch0".to_owned());
+    let device = SwitchtecDevice::open(&path)?;
+    println!("Resetting {}", device.name()?);
+
+    // The management link drops as the switch resets, so an error here doesn't necessarily
+    // mean that the reset failed
+    if let Err(e) = device.hard_reset() {
+        println!("Reset returned: {e}");
+    }
+
+    let start = Instant::now();
+    let device = loop {
+        thread::sleep(Duration::from_secs(1));
+        match SwitchtecDevice::open(&path) {
+            Ok(device) => break device,
+            Err(e) if start.elapsed() > Duration::from_secs(30) => {
+                anyhow::bail!("device didn't come back after reset: {e}")
+            }
+            Err(_) => continue,
+        }
+    };
+    println!(
+        "{} is back after {:?}, running firmware {}",
+        device.name()?,
+        start.elapsed(),
+        device.firmware_version()?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+
+use crate::{
+    switchtec_boot_phase, switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL1,
+    switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL2, switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_FW,
+    switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_UNKNOWN,
+};
+
+/// Boot phase a Switchtec device is running in
+///
+/// ```
+/// use switchtec_user_sys::{
+///     switchtec_boot_phase, switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL2, BootPhase,
+/// };
+///
+/// let phase = BootPhase::from(switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL2);
+/// assert_eq!(phase, BootPhase::Bl2);
+/// assert_eq!(phase.to_string(), "BL2");
+/// assert_eq!(
+///     switchtec_boot_phase::from(phase),
+///     switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL2
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BootPhase {
+    /// Running the first stage boot loader (BL1)
+    Bl1,
+    /// Running the second stage boot loader (BL2)
+    Bl2,
+    /// Running the main firmware
+    Firmware,
+    /// The device reported a boot phase this crate doesn't know about
+    Unknown,
+}
+
+impl BootPhase {
+    /// Get the name of the boot phase (E.g. "BL2")
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Bl1 => "BL1",
+            Self::Bl2 => "BL2",
+            Self::Firmware => "Main Firmware",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
+impl From<switchtec_boot_phase> for BootPhase {
+    fn from(phase: switchtec_boot_phase) -> Self {
+        match phase {
+            switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL1 => Self::Bl1,
+            switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL2 => Self::Bl2,
+            switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_FW => Self::Firmware,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl From<BootPhase> for switchtec_boot_phase {
+    fn from(phase: BootPhase) -> Self {
+        match phase {
+            BootPhase::Bl1 => switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL1,
+            BootPhase::Bl2 => switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL2,
+            BootPhase::Firmware => switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_FW,
+            BootPhase::Unknown => switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_UNKNOWN,
+        }
+    }
+}
+
+impl fmt::Display for BootPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[test]
+fn test_boot_phase_names() {
+    assert_eq!(
+        BootPhase::from(switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL1).to_string(),
+        "BL1"
+    );
+    assert_eq!(
+        BootPhase::from(switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL2).to_string(),
+        "BL2"
+    );
+    assert_eq!(
+        BootPhase::from(switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_FW).to_string(),
+        "Main Firmware"
+    );
+    assert_eq!(
+        BootPhase::from(switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_UNKNOWN).to_string(),
+        "Unknown"
+    );
+}
+
+#[test]
+fn test_boot_phase_round_trip() {
+    for raw in [
+        switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL1,
+        switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_BL2,
+        switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_FW,
+        switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_UNKNOWN,
+    ] {
+        assert_eq!(switchtec_boot_phase::from(BootPhase::from(raw)), raw);
+    }
+    for phase in [
+        BootPhase::Bl1,
+        BootPhase::Bl2,
+        BootPhase::Firmware,
+        BootPhase::Unknown,
+    ] {
+        assert_eq!(BootPhase::from(switchtec_boot_phase::from(phase)), phase);
+    }
+    // Values this crate doesn't know about can't be round-tripped
+    assert_eq!(BootPhase::from(0x42), BootPhase::Unknown);
+    assert_eq!(
+        switchtec_boot_phase::from(BootPhase::from(0x42)),
+        switchtec_boot_phase_SWITCHTEC_BOOT_PHASE_UNKNOWN
+    );
+}
@@ -0,0 +1,705 @@
+use std::io;
+use std::thread;
+use std::time::Duration;
+
+use crate::ffi::MRPC_MAX_DATA_LEN;
+use crate::mrpc::{
+    mrpc_cmd_MRPC_ACT_IMG_IDX_GET, mrpc_cmd_MRPC_ACT_IMG_IDX_SET, mrpc_cmd_MRPC_AER_GEN,
+    mrpc_cmd_MRPC_ARB, mrpc_cmd_MRPC_BOOTUP_RESUME, mrpc_cmd_MRPC_CROSS_HAIR,
+    mrpc_cmd_MRPC_DBG_UNLOCK, mrpc_cmd_MRPC_DEVICE_MANAGE_CMD, mrpc_cmd_MRPC_DEV_INFO_GET,
+    mrpc_cmd_MRPC_DIAG_PMC_START, mrpc_cmd_MRPC_DIAG_PORT_EYE, mrpc_cmd_MRPC_DIAG_PORT_LN_ADPT,
+    mrpc_cmd_MRPC_DIAG_PORT_LTSSM_LOG, mrpc_cmd_MRPC_DIAG_PORT_TLP_ANL,
+    mrpc_cmd_MRPC_DIAG_POT_VHIST, mrpc_cmd_MRPC_DIAG_TLP_INJECT, mrpc_cmd_MRPC_DIETEMP,
+    mrpc_cmd_MRPC_ECHO, mrpc_cmd_MRPC_EP_RESOURCE_ACCESS, mrpc_cmd_MRPC_EP_TUNNEL_CFG,
+    mrpc_cmd_MRPC_EVENTS_QUERY, mrpc_cmd_MRPC_EXT_RCVR_OBJ_DUMP, mrpc_cmd_MRPC_EYE_OBSERVE,
+    mrpc_cmd_MRPC_FWDNLD, mrpc_cmd_MRPC_FWLOGRD, mrpc_cmd_MRPC_FW_TX, mrpc_cmd_MRPC_GAS_READ,
+    mrpc_cmd_MRPC_GAS_WRITE, mrpc_cmd_MRPC_GET_PAX_ID, mrpc_cmd_MRPC_GFMS_BIND_UNBIND,
+    mrpc_cmd_MRPC_GFMS_DB_DUMP, mrpc_cmd_MRPC_GFMS_EVENT, mrpc_cmd_MRPC_I2C_TWI_PING,
+    mrpc_cmd_MRPC_INT_LOOPBACK, mrpc_cmd_MRPC_KMSK_ENTRY_SET, mrpc_cmd_MRPC_LNKSTAT,
+    mrpc_cmd_MRPC_LOG_DEF_GET, mrpc_cmd_MRPC_MAILBOX_GET, mrpc_cmd_MRPC_MCOVRLY,
+    mrpc_cmd_MRPC_MRPC_ERR_INJ, mrpc_cmd_MRPC_MRPC_PERM_TABLE_GET, mrpc_cmd_MRPC_MULTI_CFG,
+    mrpc_cmd_MRPC_NT_MCG_CAPABLE_CONFIG, mrpc_cmd_MRPC_NVME_ADMIN_PASSTHRU,
+    mrpc_cmd_MRPC_PART_INFO, mrpc_cmd_MRPC_PAT_GEN, mrpc_cmd_MRPC_PCIE_GEN_1_2_DUMP,
+    mrpc_cmd_MRPC_PCIE_GEN_1_2_TUNE, mrpc_cmd_MRPC_PMON, mrpc_cmd_MRPC_PORTARB,
+    mrpc_cmd_MRPC_PORTLN, mrpc_cmd_MRPC_PORTPARTP2P, mrpc_cmd_MRPC_PORT_CONFIG,
+    mrpc_cmd_MRPC_PORT_CONTROL, mrpc_cmd_MRPC_PORT_EQ_CTRL, mrpc_cmd_MRPC_PORT_EQ_STATUS,
+    mrpc_cmd_MRPC_PWM, mrpc_cmd_MRPC_RCVR_OBJ_DUMP, mrpc_cmd_MRPC_RD_FLASH,
+    mrpc_cmd_MRPC_RECV_DETECT_STATUS, mrpc_cmd_MRPC_REFCLK_S, mrpc_cmd_MRPC_RESET,
+    mrpc_cmd_MRPC_ROUTE_TO_SELF, mrpc_cmd_MRPC_SECURE_STATE_SET, mrpc_cmd_MRPC_SECURITY_CONFIG_GET,
+    mrpc_cmd_MRPC_SECURITY_CONFIG_GET_EXT, mrpc_cmd_MRPC_SECURITY_CONFIG_GET_GEN5,
+    mrpc_cmd_MRPC_SECURITY_CONFIG_SET, mrpc_cmd_MRPC_SECURITY_CONFIG_SET_GEN5, mrpc_cmd_MRPC_SES,
+    mrpc_cmd_MRPC_SMBUS, mrpc_cmd_MRPC_SN_VER_GET, mrpc_cmd_MRPC_SPI_ECC,
+    mrpc_cmd_MRPC_SRDS_EQ_CTRL, mrpc_cmd_MRPC_SRDS_LN_TUNING_MODE, mrpc_cmd_MRPC_SRDS_PCIE_PEAK,
+    mrpc_cmd_MRPC_STACKBIF, mrpc_cmd_MRPC_SYNTH_EP, mrpc_cmd_MRPC_TCH,
+    mrpc_cmd_MRPC_TOPO_INFO_DUMP, mrpc_cmd_MRPC_TWI, mrpc_cmd_MRPC_VGPIO,
+};
+use crate::{get_switchtec_error, switchtec_cmd, SwitchtecDevice, SwitchtecError};
+
+/// Maximum length of an MRPC command's input or output payload, in bytes
+pub const MRPC_MAX_PAYLOAD_LEN: usize = MRPC_MAX_DATA_LEN as usize;
+
+/// A known MRPC command opcode, for [`SwitchtecDevice::mrpc`]
+///
+/// Variants are named after the `MRPC_*` opcodes in `switchtec/mrpc.h`, with
+/// [`MrpcCommand::Other`] for any opcode that isn't listed
+///
+/// ```
+/// use switchtec_user_sys::{mrpc::mrpc_cmd_MRPC_ECHO, MrpcCommand};
+///
+/// assert_eq!(MrpcCommand::Echo.as_u32(), mrpc_cmd_MRPC_ECHO);
+/// assert_eq!(MrpcCommand::from_u32(mrpc_cmd_MRPC_ECHO), Some(MrpcCommand::Echo));
+/// assert_eq!(MrpcCommand::from(0xffff), MrpcCommand::Other(0xffff));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MrpcCommand {
+    /// `MRPC_ACT_IMG_IDX_GET`
+    ActImgIdxGet,
+    /// `MRPC_ACT_IMG_IDX_SET`
+    ActImgIdxSet,
+    /// `MRPC_AER_GEN`
+    AerGen,
+    /// `MRPC_ARB`
+    Arb,
+    /// `MRPC_BOOTUP_RESUME`
+    BootupResume,
+    /// `MRPC_CROSS_HAIR`
+    CrossHair,
+    /// `MRPC_DBG_UNLOCK`
+    DbgUnlock,
+    /// `MRPC_DEVICE_MANAGE_CMD`
+    DeviceManageCmd,
+    /// `MRPC_DEV_INFO_GET`
+    DevInfoGet,
+    /// `MRPC_DIAG_PMC_START`
+    DiagPmcStart,
+    /// `MRPC_DIAG_PORT_EYE`
+    DiagPortEye,
+    /// `MRPC_DIAG_PORT_LN_ADPT`
+    DiagPortLnAdpt,
+    /// `MRPC_DIAG_PORT_LTSSM_LOG`
+    DiagPortLtssmLog,
+    /// `MRPC_DIAG_PORT_TLP_ANL`
+    DiagPortTlpAnl,
+    /// `MRPC_DIAG_POT_VHIST`
+    DiagPotVhist,
+    /// `MRPC_DIAG_TLP_INJECT`
+    DiagTlpInject,
+    /// `MRPC_DIETEMP`
+    DieTemp,
+    /// `MRPC_ECHO`
+    Echo,
+    /// `MRPC_EP_RESOURCE_ACCESS`
+    EpResourceAccess,
+    /// `MRPC_EP_TUNNEL_CFG`
+    EpTunnelCfg,
+    /// `MRPC_EVENTS_QUERY`
+    EventsQuery,
+    /// `MRPC_EXT_RCVR_OBJ_DUMP`
+    ExtRcvrObjDump,
+    /// `MRPC_EYE_OBSERVE`
+    EyeObserve,
+    /// `MRPC_FWDNLD`
+    FwDownload,
+    /// `MRPC_FWLOGRD`
+    FwLogRead,
+    /// `MRPC_FW_TX`
+    FwTx,
+    /// `MRPC_GAS_READ`
+    GasRead,
+    /// `MRPC_GAS_WRITE`
+    GasWrite,
+    /// `MRPC_GET_PAX_ID`
+    GetPaxId,
+    /// `MRPC_GFMS_BIND_UNBIND`
+    GfmsBindUnbind,
+    /// `MRPC_GFMS_DB_DUMP`
+    GfmsDbDump,
+    /// `MRPC_GFMS_EVENT`
+    GfmsEvent,
+    /// `MRPC_I2C_TWI_PING`
+    I2cTwiPing,
+    /// `MRPC_INT_LOOPBACK`
+    IntLoopback,
+    /// `MRPC_KMSK_ENTRY_SET`
+    KmskEntrySet,
+    /// `MRPC_LNKSTAT`
+    LinkStat,
+    /// `MRPC_LOG_DEF_GET`
+    LogDefGet,
+    /// `MRPC_MAILBOX_GET`
+    MailboxGet,
+    /// `MRPC_MCOVRLY`
+    McOverlay,
+    /// `MRPC_MRPC_ERR_INJ`
+    MrpcErrInj,
+    /// `MRPC_MRPC_PERM_TABLE_GET`
+    MrpcPermTableGet,
+    /// `MRPC_MULTI_CFG`
+    MultiCfg,
+    /// `MRPC_NT_MCG_CAPABLE_CONFIG`
+    NtMcgCapableConfig,
+    /// `MRPC_NVME_ADMIN_PASSTHRU`
+    NvmeAdminPassthru,
+    /// `MRPC_PART_INFO`
+    PartInfo,
+    /// `MRPC_PAT_GEN`
+    PatGen,
+    /// `MRPC_PCIE_GEN_1_2_DUMP`
+    PcieGen12Dump,
+    /// `MRPC_PCIE_GEN_1_2_TUNE`
+    PcieGen12Tune,
+    /// `MRPC_PMON`
+    Pmon,
+    /// `MRPC_PORTARB`
+    PortArb,
+    /// `MRPC_PORTLN`
+    PortLn,
+    /// `MRPC_PORTPARTP2P`
+    PortPartP2p,
+    /// `MRPC_PORT_CONFIG`
+    PortConfig,
+    /// `MRPC_PORT_CONTROL`
+    PortControl,
+    /// `MRPC_PORT_EQ_CTRL`
+    PortEqCtrl,
+    /// `MRPC_PORT_EQ_STATUS`
+    ///
+    /// `libswitchtec` doesn't issue this command or define its payloads, so the link
+    /// equalization status it reports is only reachable through [`SwitchtecDevice::mrpc`]
+    PortEqStatus,
+    /// `MRPC_PWM`
+    Pwm,
+    /// `MRPC_RCVR_OBJ_DUMP`
+    RcvrObjDump,
+    /// `MRPC_RD_FLASH`
+    RdFlash,
+    /// `MRPC_RECV_DETECT_STATUS`
+    RecvDetectStatus,
+    /// `MRPC_REFCLK_S`
+    RefClkS,
+    /// `MRPC_RESET`
+    Reset,
+    /// `MRPC_ROUTE_TO_SELF`
+    RouteToSelf,
+    /// `MRPC_SECURE_STATE_SET`
+    SecureStateSet,
+    /// `MRPC_SECURITY_CONFIG_GET`
+    SecurityConfigGet,
+    /// `MRPC_SECURITY_CONFIG_GET_EXT`
+    SecurityConfigGetExt,
+    /// `MRPC_SECURITY_CONFIG_GET_GEN5`
+    SecurityConfigGetGen5,
+    /// `MRPC_SECURITY_CONFIG_SET`
+    SecurityConfigSet,
+    /// `MRPC_SECURITY_CONFIG_SET_GEN5`
+    SecurityConfigSetGen5,
+    /// `MRPC_SES`
+    Ses,
+    /// `MRPC_SMBUS`
+    Smbus,
+    /// `MRPC_SN_VER_GET`
+    SnVerGet,
+    /// `MRPC_SPI_ECC`
+    SpiEcc,
+    /// `MRPC_SRDS_EQ_CTRL`
+    SrdsEqCtrl,
+    /// `MRPC_SRDS_LN_TUNING_MODE`
+    SrdsLnTuningMode,
+    /// `MRPC_SRDS_PCIE_PEAK`
+    SrdsPciePeak,
+    /// `MRPC_STACKBIF`
+    StackBif,
+    /// `MRPC_SYNTH_EP`
+    SynthEp,
+    /// `MRPC_TCH`
+    Tch,
+    /// `MRPC_TOPO_INFO_DUMP`
+    TopoInfoDump,
+    /// `MRPC_TWI`
+    Twi,
+    /// `MRPC_VGPIO`
+    Vgpio,
+    /// An opcode this crate doesn't list
+    Other(u32),
+}
+
+impl MrpcCommand {
+    /// Get the raw opcode to pass to the library
+    pub fn as_u32(&self) -> u32 {
+        match *self {
+            Self::ActImgIdxGet => mrpc_cmd_MRPC_ACT_IMG_IDX_GET,
+            Self::ActImgIdxSet => mrpc_cmd_MRPC_ACT_IMG_IDX_SET,
+            Self::AerGen => mrpc_cmd_MRPC_AER_GEN,
+            Self::Arb => mrpc_cmd_MRPC_ARB,
+            Self::BootupResume => mrpc_cmd_MRPC_BOOTUP_RESUME,
+            Self::CrossHair => mrpc_cmd_MRPC_CROSS_HAIR,
+            Self::DbgUnlock => mrpc_cmd_MRPC_DBG_UNLOCK,
+            Self::DeviceManageCmd => mrpc_cmd_MRPC_DEVICE_MANAGE_CMD,
+            Self::DevInfoGet => mrpc_cmd_MRPC_DEV_INFO_GET,
+            Self::DiagPmcStart => mrpc_cmd_MRPC_DIAG_PMC_START,
+            Self::DiagPortEye => mrpc_cmd_MRPC_DIAG_PORT_EYE,
+            Self::DiagPortLnAdpt => mrpc_cmd_MRPC_DIAG_PORT_LN_ADPT,
+            Self::DiagPortLtssmLog => mrpc_cmd_MRPC_DIAG_PORT_LTSSM_LOG,
+            Self::DiagPortTlpAnl => mrpc_cmd_MRPC_DIAG_PORT_TLP_ANL,
+            Self::DiagPotVhist => mrpc_cmd_MRPC_DIAG_POT_VHIST,
+            Self::DiagTlpInject => mrpc_cmd_MRPC_DIAG_TLP_INJECT,
+            Self::DieTemp => mrpc_cmd_MRPC_DIETEMP,
+            Self::Echo => mrpc_cmd_MRPC_ECHO,
+            Self::EpResourceAccess => mrpc_cmd_MRPC_EP_RESOURCE_ACCESS,
+            Self::EpTunnelCfg => mrpc_cmd_MRPC_EP_TUNNEL_CFG,
+            Self::EventsQuery => mrpc_cmd_MRPC_EVENTS_QUERY,
+            Self::ExtRcvrObjDump => mrpc_cmd_MRPC_EXT_RCVR_OBJ_DUMP,
+            Self::EyeObserve => mrpc_cmd_MRPC_EYE_OBSERVE,
+            Self::FwDownload => mrpc_cmd_MRPC_FWDNLD,
+            Self::FwLogRead => mrpc_cmd_MRPC_FWLOGRD,
+            Self::FwTx => mrpc_cmd_MRPC_FW_TX,
+            Self::GasRead => mrpc_cmd_MRPC_GAS_READ,
+            Self::GasWrite => mrpc_cmd_MRPC_GAS_WRITE,
+            Self::GetPaxId => mrpc_cmd_MRPC_GET_PAX_ID,
+            Self::GfmsBindUnbind => mrpc_cmd_MRPC_GFMS_BIND_UNBIND,
+            Self::GfmsDbDump => mrpc_cmd_MRPC_GFMS_DB_DUMP,
+            Self::GfmsEvent => mrpc_cmd_MRPC_GFMS_EVENT,
+            Self::I2cTwiPing => mrpc_cmd_MRPC_I2C_TWI_PING,
+            Self::IntLoopback => mrpc_cmd_MRPC_INT_LOOPBACK,
+            Self::KmskEntrySet => mrpc_cmd_MRPC_KMSK_ENTRY_SET,
+            Self::LinkStat => mrpc_cmd_MRPC_LNKSTAT,
+            Self::LogDefGet => mrpc_cmd_MRPC_LOG_DEF_GET,
+            Self::MailboxGet => mrpc_cmd_MRPC_MAILBOX_GET,
+            Self::McOverlay => mrpc_cmd_MRPC_MCOVRLY,
+            Self::MrpcErrInj => mrpc_cmd_MRPC_MRPC_ERR_INJ,
+            Self::MrpcPermTableGet => mrpc_cmd_MRPC_MRPC_PERM_TABLE_GET,
+            Self::MultiCfg => mrpc_cmd_MRPC_MULTI_CFG,
+            Self::NtMcgCapableConfig => mrpc_cmd_MRPC_NT_MCG_CAPABLE_CONFIG,
+            Self::NvmeAdminPassthru => mrpc_cmd_MRPC_NVME_ADMIN_PASSTHRU,
+            Self::PartInfo => mrpc_cmd_MRPC_PART_INFO,
+            Self::PatGen => mrpc_cmd_MRPC_PAT_GEN,
+            Self::PcieGen12Dump => mrpc_cmd_MRPC_PCIE_GEN_1_2_DUMP,
+            Self::PcieGen12Tune => mrpc_cmd_MRPC_PCIE_GEN_1_2_TUNE,
+            Self::Pmon => mrpc_cmd_MRPC_PMON,
+            Self::PortArb => mrpc_cmd_MRPC_PORTARB,
+            Self::PortLn => mrpc_cmd_MRPC_PORTLN,
+            Self::PortPartP2p => mrpc_cmd_MRPC_PORTPARTP2P,
+            Self::PortConfig => mrpc_cmd_MRPC_PORT_CONFIG,
+            Self::PortControl => mrpc_cmd_MRPC_PORT_CONTROL,
+            Self::PortEqCtrl => mrpc_cmd_MRPC_PORT_EQ_CTRL,
+            Self::PortEqStatus => mrpc_cmd_MRPC_PORT_EQ_STATUS,
+            Self::Pwm => mrpc_cmd_MRPC_PWM,
+            Self::RcvrObjDump => mrpc_cmd_MRPC_RCVR_OBJ_DUMP,
+            Self::RdFlash => mrpc_cmd_MRPC_RD_FLASH,
+            Self::RecvDetectStatus => mrpc_cmd_MRPC_RECV_DETECT_STATUS,
+            Self::RefClkS => mrpc_cmd_MRPC_REFCLK_S,
+            Self::Reset => mrpc_cmd_MRPC_RESET,
+            Self::RouteToSelf => mrpc_cmd_MRPC_ROUTE_TO_SELF,
+            Self::SecureStateSet => mrpc_cmd_MRPC_SECURE_STATE_SET,
+            Self::SecurityConfigGet => mrpc_cmd_MRPC_SECURITY_CONFIG_GET,
+            Self::SecurityConfigGetExt => mrpc_cmd_MRPC_SECURITY_CONFIG_GET_EXT,
+            Self::SecurityConfigGetGen5 => mrpc_cmd_MRPC_SECURITY_CONFIG_GET_GEN5,
+            Self::SecurityConfigSet => mrpc_cmd_MRPC_SECURITY_CONFIG_SET,
+            Self::SecurityConfigSetGen5 => mrpc_cmd_MRPC_SECURITY_CONFIG_SET_GEN5,
+            Self::Ses => mrpc_cmd_MRPC_SES,
+            Self::Smbus => mrpc_cmd_MRPC_SMBUS,
+            Self::SnVerGet => mrpc_cmd_MRPC_SN_VER_GET,
+            Self::SpiEcc => mrpc_cmd_MRPC_SPI_ECC,
+            Self::SrdsEqCtrl => mrpc_cmd_MRPC_SRDS_EQ_CTRL,
+            Self::SrdsLnTuningMode => mrpc_cmd_MRPC_SRDS_LN_TUNING_MODE,
+            Self::SrdsPciePeak => mrpc_cmd_MRPC_SRDS_PCIE_PEAK,
+            Self::StackBif => mrpc_cmd_MRPC_STACKBIF,
+            Self::SynthEp => mrpc_cmd_MRPC_SYNTH_EP,
+            Self::Tch => mrpc_cmd_MRPC_TCH,
+            Self::TopoInfoDump => mrpc_cmd_MRPC_TOPO_INFO_DUMP,
+            Self::Twi => mrpc_cmd_MRPC_TWI,
+            Self::Vgpio => mrpc_cmd_MRPC_VGPIO,
+            Self::Other(cmd) => cmd,
+        }
+    }
+
+    /// Get the known command for a raw opcode, or `None` if it isn't listed
+    pub fn from_u32(cmd: u32) -> Option<Self> {
+        match Self::from(cmd) {
+            Self::Other(_) => None,
+            cmd => Some(cmd),
+        }
+    }
+}
+
+impl From<u32> for MrpcCommand {
+    fn from(cmd: u32) -> Self {
+        match cmd {
+            mrpc_cmd_MRPC_ACT_IMG_IDX_GET => Self::ActImgIdxGet,
+            mrpc_cmd_MRPC_ACT_IMG_IDX_SET => Self::ActImgIdxSet,
+            mrpc_cmd_MRPC_AER_GEN => Self::AerGen,
+            mrpc_cmd_MRPC_ARB => Self::Arb,
+            mrpc_cmd_MRPC_BOOTUP_RESUME => Self::BootupResume,
+            mrpc_cmd_MRPC_CROSS_HAIR => Self::CrossHair,
+            mrpc_cmd_MRPC_DBG_UNLOCK => Self::DbgUnlock,
+            mrpc_cmd_MRPC_DEVICE_MANAGE_CMD => Self::DeviceManageCmd,
+            mrpc_cmd_MRPC_DEV_INFO_GET => Self::DevInfoGet,
+            mrpc_cmd_MRPC_DIAG_PMC_START => Self::DiagPmcStart,
+            mrpc_cmd_MRPC_DIAG_PORT_EYE => Self::DiagPortEye,
+            mrpc_cmd_MRPC_DIAG_PORT_LN_ADPT => Self::DiagPortLnAdpt,
+            mrpc_cmd_MRPC_DIAG_PORT_LTSSM_LOG => Self::DiagPortLtssmLog,
+            mrpc_cmd_MRPC_DIAG_PORT_TLP_ANL => Self::DiagPortTlpAnl,
+            mrpc_cmd_MRPC_DIAG_POT_VHIST => Self::DiagPotVhist,
+            mrpc_cmd_MRPC_DIAG_TLP_INJECT => Self::DiagTlpInject,
+            mrpc_cmd_MRPC_DIETEMP => Self::DieTemp,
+            mrpc_cmd_MRPC_ECHO => Self::Echo,
+            mrpc_cmd_MRPC_EP_RESOURCE_ACCESS => Self::EpResourceAccess,
+            mrpc_cmd_MRPC_EP_TUNNEL_CFG => Self::EpTunnelCfg,
+            mrpc_cmd_MRPC_EVENTS_QUERY => Self::EventsQuery,
+            mrpc_cmd_MRPC_EXT_RCVR_OBJ_DUMP => Self::ExtRcvrObjDump,
+            mrpc_cmd_MRPC_EYE_OBSERVE => Self::EyeObserve,
+            mrpc_cmd_MRPC_FWDNLD => Self::FwDownload,
+            mrpc_cmd_MRPC_FWLOGRD => Self::FwLogRead,
+            mrpc_cmd_MRPC_FW_TX => Self::FwTx,
+            mrpc_cmd_MRPC_GAS_READ => Self::GasRead,
+            mrpc_cmd_MRPC_GAS_WRITE => Self::GasWrite,
+            mrpc_cmd_MRPC_GET_PAX_ID => Self::GetPaxId,
+            mrpc_cmd_MRPC_GFMS_BIND_UNBIND => Self::GfmsBindUnbind,
+            mrpc_cmd_MRPC_GFMS_DB_DUMP => Self::GfmsDbDump,
+            mrpc_cmd_MRPC_GFMS_EVENT => Self::GfmsEvent,
+            mrpc_cmd_MRPC_I2C_TWI_PING => Self::I2cTwiPing,
+            mrpc_cmd_MRPC_INT_LOOPBACK => Self::IntLoopback,
+            mrpc_cmd_MRPC_KMSK_ENTRY_SET => Self::KmskEntrySet,
+            mrpc_cmd_MRPC_LNKSTAT => Self::LinkStat,
+            mrpc_cmd_MRPC_LOG_DEF_GET => Self::LogDefGet,
+            mrpc_cmd_MRPC_MAILBOX_GET => Self::MailboxGet,
+            mrpc_cmd_MRPC_MCOVRLY => Self::McOverlay,
+            mrpc_cmd_MRPC_MRPC_ERR_INJ => Self::MrpcErrInj,
+            mrpc_cmd_MRPC_MRPC_PERM_TABLE_GET => Self::MrpcPermTableGet,
+            mrpc_cmd_MRPC_MULTI_CFG => Self::MultiCfg,
+            mrpc_cmd_MRPC_NT_MCG_CAPABLE_CONFIG => Self::NtMcgCapableConfig,
+            mrpc_cmd_MRPC_NVME_ADMIN_PASSTHRU => Self::NvmeAdminPassthru,
+            mrpc_cmd_MRPC_PART_INFO => Self::PartInfo,
+            mrpc_cmd_MRPC_PAT_GEN => Self::PatGen,
+            mrpc_cmd_MRPC_PCIE_GEN_1_2_DUMP => Self::PcieGen12Dump,
+            mrpc_cmd_MRPC_PCIE_GEN_1_2_TUNE => Self::PcieGen12Tune,
+            mrpc_cmd_MRPC_PMON => Self::Pmon,
+            mrpc_cmd_MRPC_PORTARB => Self::PortArb,
+            mrpc_cmd_MRPC_PORTLN => Self::PortLn,
+            mrpc_cmd_MRPC_PORTPARTP2P => Self::PortPartP2p,
+            mrpc_cmd_MRPC_PORT_CONFIG => Self::PortConfig,
+            mrpc_cmd_MRPC_PORT_CONTROL => Self::PortControl,
+            mrpc_cmd_MRPC_PORT_EQ_CTRL => Self::PortEqCtrl,
+            mrpc_cmd_MRPC_PORT_EQ_STATUS => Self::PortEqStatus,
+            mrpc_cmd_MRPC_PWM => Self::Pwm,
+            mrpc_cmd_MRPC_RCVR_OBJ_DUMP => Self::RcvrObjDump,
+            mrpc_cmd_MRPC_RD_FLASH => Self::RdFlash,
+            mrpc_cmd_MRPC_RECV_DETECT_STATUS => Self::RecvDetectStatus,
+            mrpc_cmd_MRPC_REFCLK_S => Self::RefClkS,
+            mrpc_cmd_MRPC_RESET => Self::Reset,
+            mrpc_cmd_MRPC_ROUTE_TO_SELF => Self::RouteToSelf,
+            mrpc_cmd_MRPC_SECURE_STATE_SET => Self::SecureStateSet,
+            mrpc_cmd_MRPC_SECURITY_CONFIG_GET => Self::SecurityConfigGet,
+            mrpc_cmd_MRPC_SECURITY_CONFIG_GET_EXT => Self::SecurityConfigGetExt,
+            mrpc_cmd_MRPC_SECURITY_CONFIG_GET_GEN5 => Self::SecurityConfigGetGen5,
+            mrpc_cmd_MRPC_SECURITY_CONFIG_SET => Self::SecurityConfigSet,
+            mrpc_cmd_MRPC_SECURITY_CONFIG_SET_GEN5 => Self::SecurityConfigSetGen5,
+            mrpc_cmd_MRPC_SES => Self::Ses,
+            mrpc_cmd_MRPC_SMBUS => Self::Smbus,
+            mrpc_cmd_MRPC_SN_VER_GET => Self::SnVerGet,
+            mrpc_cmd_MRPC_SPI_ECC => Self::SpiEcc,
+            mrpc_cmd_MRPC_SRDS_EQ_CTRL => Self::SrdsEqCtrl,
+            mrpc_cmd_MRPC_SRDS_LN_TUNING_MODE => Self::SrdsLnTuningMode,
+            mrpc_cmd_MRPC_SRDS_PCIE_PEAK => Self::SrdsPciePeak,
+            mrpc_cmd_MRPC_STACKBIF => Self::StackBif,
+            mrpc_cmd_MRPC_SYNTH_EP => Self::SynthEp,
+            mrpc_cmd_MRPC_TCH => Self::Tch,
+            mrpc_cmd_MRPC_TOPO_INFO_DUMP => Self::TopoInfoDump,
+            mrpc_cmd_MRPC_TWI => Self::Twi,
+            mrpc_cmd_MRPC_VGPIO => Self::Vgpio,
+            cmd => Self::Other(cmd),
+        }
+    }
+}
+
+impl From<MrpcCommand> for u32 {
+    fn from(cmd: MrpcCommand) -> Self {
+        cmd.as_u32()
+    }
+}
+
+/// How [`SwitchtecDevice::mrpc_with_retry`] retries MRPC commands that time out, and
+/// [`SwitchtecDevice::firmware_update`] retries downloads that do (see
+/// [`FwUpdateOpts::retry`](crate::fw::FwUpdateOpts::retry))
+///
+/// The default makes up to 5 attempts, waiting 100ms before the first retry and doubling the
+/// delay for each retry after it, up to 2s
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MrpcRetry {
+    /// Maximum number of times to submit the command, including the first attempt. `0` is
+    /// treated as `1`
+    pub attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Longest delay between retries
+    pub max_backoff: Duration,
+}
+
+impl Default for MrpcRetry {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl MrpcRetry {
+    /// The delay before each retry, doubling from `initial_backoff` up to `max_backoff`
+    pub(crate) fn backoffs(&self) -> impl Iterator<Item = Duration> {
+        let max_backoff = self.max_backoff;
+        std::iter::successors(
+            Some(self.initial_backoff.min(max_backoff)),
+            move |backoff| Some((*backoff * 2).min(max_backoff)),
+        )
+        .take(self.attempts.saturating_sub(1) as usize)
+    }
+}
+
+impl SwitchtecDevice {
+    /// Submit a raw MRPC command with the given `input` payload, returning the output payload
+    ///
+    /// This is an escape hatch for commands that don't have a safe wrapper yet. The output is
+    /// always [`MRPC_MAX_PAYLOAD_LEN`] bytes, since the library doesn't report how much of it
+    /// the firmware filled in. Use [`SwitchtecDevice::mrpc_into`] to read a known response
+    /// length.
+    ///
+    /// `cmd` is an [`MrpcCommand`] or a raw opcode (E.g. [`mrpc_cmd_MRPC_ECHO`]). Returns an
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput) error if `input` is longer than
+    /// [`MRPC_MAX_PAYLOAD_LEN`]
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{MrpcCommand, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let output = device.mrpc(MrpcCommand::Echo, &0x1234_5678u32.to_le_bytes())?;
+    /// println!("Echo reply: {:02x?}", &output[..4]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mrpc(&self, cmd: impl Into<u32>, input: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = vec![0u8; MRPC_MAX_PAYLOAD_LEN];
+        self.mrpc_into(cmd, input, &mut output)?;
+        Ok(output)
+    }
+
+    /// Submit a raw MRPC command with the given `input` payload, writing the output payload
+    /// into `output` and returning the number of bytes written
+    ///
+    /// MRPC replies aren't length-prefixed: libswitchtec always reads back exactly as many
+    /// bytes as were requested, so on success this is `output.len()`. Returns an
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput) error if `input` or `output` is longer
+    /// than [`MRPC_MAX_PAYLOAD_LEN`]
+    ///
+    /// With the `log` feature enabled, each command is logged at the `debug` level along with
+    /// how long it took. Every other raw MRPC method is built on this one, so they're logged too.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{mrpc::mrpc_cmd_MRPC_ECHO, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let mut reply = [0u8; 4];
+    /// device.mrpc_into(mrpc_cmd_MRPC_ECHO, &0x1234_5678u32.to_le_bytes(), &mut reply)?;
+    /// println!("Echo reply: {:#010x}", u32::from_le_bytes(reply));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mrpc_into(
+        &self,
+        cmd: impl Into<u32>,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> io::Result<usize> {
+        check_payload_len("input", input.len())?;
+        check_payload_len("output", output.len())?;
+
+        let cmd = cmd.into();
+        #[cfg(feature = "log")]
+        let start = std::time::Instant::now();
+        // SAFETY: We know that device holds a valid/open switchtec device, and the payload
+        // pointers are valid for the lengths passed with them
+        let ret = unsafe {
+            switchtec_cmd(
+                self.inner,
+                cmd,
+                input.as_ptr() as *const _,
+                input.len(),
+                output.as_mut_ptr() as *mut _,
+                output.len(),
+            )
+        };
+        // Read the error before logging, which could overwrite `errno`
+        let result = cmd_output_len(ret, output);
+        #[cfg(feature = "log")]
+        log::debug!(
+            "MRPC {:?} ({cmd:#x}) {} in {:?}, {} bytes in, {} bytes out",
+            MrpcCommand::from(cmd),
+            if result.is_ok() {
+                "completed"
+            } else {
+                "failed"
+            },
+            start.elapsed(),
+            input.len(),
+            output.len(),
+        );
+        result
+    }
+
+    /// Submit a raw MRPC command like [`SwitchtecDevice::mrpc`], retrying with backoff if it
+    /// times out
+    ///
+    /// `libswitchtec` doesn't expose a way to change how long it (and the kernel driver) waits
+    /// for an MRPC command to complete, so slow commands can time out when the switch is busy.
+    /// A [`TimedOut`](io::ErrorKind::TimedOut) error is retried as described by `retry`, and
+    /// any other error is returned immediately. Only use this for commands that are safe to
+    /// submit more than once.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{mrpc::mrpc_cmd_MRPC_ECHO, MrpcRetry, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let retry = MrpcRetry {
+    ///     attempts: 10,
+    ///     ..Default::default()
+    /// };
+    /// let output = device.mrpc_with_retry(mrpc_cmd_MRPC_ECHO, &[0xab; 4], &retry)?;
+    /// println!("Echo reply: {:02x?}", &output[..4]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mrpc_with_retry(
+        &self,
+        cmd: impl Into<u32>,
+        input: &[u8],
+        retry: &MrpcRetry,
+    ) -> io::Result<Vec<u8>> {
+        let cmd = cmd.into();
+        let mut backoffs = retry.backoffs();
+        loop {
+            match self.mrpc(cmd, input) {
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => match backoffs.next() {
+                    Some(backoff) => thread::sleep(backoff),
+                    None => return Err(e),
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Run `f` on the device, retrying up to `attempts` times in total while it fails with a
+    /// [`SwitchtecError::Busy`] error, waiting `delay` between attempts
+    ///
+    /// The device reports busy when another process (E.g. `switchtec` CLI tools) holds the MRPC
+    /// lock. Any other error is returned immediately, as is the busy error after the last
+    /// attempt. `0` attempts is treated as `1`.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let temp = device.with_retry_on_busy(5, Duration::from_millis(200), |dev| dev.die_temp())?;
+    /// println!("Temperature: {temp}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_retry_on_busy<T>(
+        &self,
+        attempts: u32,
+        delay: Duration,
+        mut f: impl FnMut(&Self) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut retries = attempts.saturating_sub(1);
+        loop {
+            match f(self) {
+                Err(e) if retries > 0 && SwitchtecError::is_busy(&e) => {
+                    retries -= 1;
+                    thread::sleep(delay);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn check_payload_len(name: &str, len: usize) -> io::Result<()> {
+    if len > MRPC_MAX_PAYLOAD_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("MRPC {name} is {len} bytes, more than the maximum of {MRPC_MAX_PAYLOAD_LEN}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Get the number of output bytes written by a `switchtec_cmd` call that returned `ret`
+///
+/// `switchtec_cmd` fills the whole output buffer on success, and returns a negative value
+/// with `errno` set on failure
+fn cmd_output_len(ret: i32, output: &[u8]) -> io::Result<usize> {
+    if ret < 0 {
+        return Err(get_switchtec_error());
+    }
+    Ok(output.len())
+}
+
+#[test]
+fn test_cmd_output_len() {
+    assert_eq!(cmd_output_len(0, &[]).unwrap(), 0);
+    assert_eq!(cmd_output_len(0, &[0u8; 4]).unwrap(), 4);
+    assert_eq!(
+        cmd_output_len(0, &[0u8; MRPC_MAX_PAYLOAD_LEN]).unwrap(),
+        MRPC_MAX_PAYLOAD_LEN
+    );
+}
+
+#[test]
+fn test_check_payload_len() {
+    assert!(check_payload_len("input", 0).is_ok());
+    assert!(check_payload_len("input", MRPC_MAX_PAYLOAD_LEN).is_ok());
+    let err = check_payload_len("input", MRPC_MAX_PAYLOAD_LEN + 1).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_mrpc_retry_backoffs() {
+    let backoffs: Vec<_> = MrpcRetry::default().backoffs().collect();
+    assert_eq!(backoffs.len(), 4);
+    assert_eq!(backoffs[0], Duration::from_millis(100));
+    assert_eq!(backoffs[3], Duration::from_millis(800));
+
+    let retry = MrpcRetry {
+        attempts: 8,
+        ..Default::default()
+    };
+    assert_eq!(retry.backoffs().last(), Some(Duration::from_secs(2)));
+    for attempts in [0, 1] {
+        let retry = MrpcRetry {
+            attempts,
+            ..Default::default()
+        };
+        assert_eq!(retry.backoffs().count(), 0);
+    }
+}
+
+#[test]
+fn test_mrpc_command_round_trip() {
+    for cmd in [
+        MrpcCommand::Echo,
+        MrpcCommand::DieTemp,
+        MrpcCommand::FwDownload,
+        MrpcCommand::Vgpio,
+    ] {
+        assert_eq!(MrpcCommand::from_u32(cmd.as_u32()), Some(cmd));
+        assert_eq!(MrpcCommand::from(u32::from(cmd)), cmd);
+    }
+    assert_eq!(MrpcCommand::from_u32(0xffff), None);
+    assert_eq!(MrpcCommand::Other(0xffff).as_u32(), 0xffff);
+}
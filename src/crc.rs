@@ -0,0 +1,56 @@
+//! The CRC-32 used by Switchtec firmware images, matching `crc32()` in `switchtec-user/lib/crc.c`
+
+/// Generator polynomial, processed MSB-first
+const POLYNOMIAL: u32 = 0x04c1_1db7;
+
+/// Compute the CRC-32 of `data` the same way the firmware and `libswitchtec` do, E.g. for the
+/// CRC stored in the header of a firmware image
+///
+/// This is CRC-32/BZIP2: the MSB-first CRC with an initial value and final XOR of `0xffffffff`.
+/// It's bit-identical to `crc32()` in the vendored `switchtec-user/lib/crc.c`, so tooling that
+/// builds or checks images can use it instead of reimplementing the algorithm.
+///
+/// ```
+/// use switchtec_user_sys::switchtec_crc;
+///
+/// assert_eq!(switchtec_crc(b"123456789"), 0xfc89_1918);
+/// ```
+pub fn switchtec_crc(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ (u32::from(byte) << 24), |crc, _| {
+            if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ POLYNOMIAL
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[test]
+fn test_switchtec_crc() {
+    assert_eq!(switchtec_crc(b""), 0);
+    // The standard check value for CRC-32/BZIP2
+    assert_eq!(switchtec_crc(b"123456789"), 0xfc89_1918);
+    // Checked against zlib's CRC-32, which is the same CRC with the bits of each byte and of
+    // the result reversed
+    assert_eq!(switchtec_crc(&[0; 4]), 0x38fb_2284);
+    assert_eq!(switchtec_crc(&(0..=255).collect::<Vec<u8>>()), 0xb6b5_ee95);
+    // Erased flash, as found in the padding of an image
+    assert_eq!(switchtec_crc(&[0xff; 1024]), 0x2fff_5c1d);
+}
+
+#[test]
+fn test_switchtec_crc_image_header() {
+    // A 56 byte image header: "PMC\0" magic, then little-endian length (128KiB), type, load
+    // address and version words, and zeroed reserved words. The expected value isn't from
+    // this implementation, it's the block CRC that libbz2 (an independent CRC-32/BZIP2
+    // implementation) stores when compressing these bytes. libbz2 agrees on the vectors in
+    // `test_switchtec_crc` too.
+    let mut header = b"PMC\0".to_vec();
+    for word in [0x0002_0000u32, 1, 0x0010_0000, 0x0407_0104] {
+        header.extend_from_slice(&word.to_le_bytes());
+    }
+    header.resize(56, 0);
+    assert_eq!(switchtec_crc(&header), 0x94a9_0c0a);
+}
@@ -0,0 +1,793 @@
+//! SerDes diagnostics for signal-integrity debugging
+
+use std::fmt::Write;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::ffi::{
+    range, switchtec_diag_eye_cancel, switchtec_diag_eye_data_mode,
+    switchtec_diag_eye_data_mode_SWITCHTEC_DIAG_EYE_RATIO,
+    switchtec_diag_eye_data_mode_SWITCHTEC_DIAG_EYE_RAW, switchtec_diag_eye_fetch,
+    switchtec_diag_eye_set_mode, switchtec_diag_eye_start, switchtec_diag_loopback_enable,
+    switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_LTSSM,
+    switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_RX_TO_TX,
+    switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_TX_TO_RX, switchtec_diag_loopback_get,
+    switchtec_diag_loopback_set, switchtec_diag_ltssm_log, switchtec_diag_ltssm_speed,
+    switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN1,
+    switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN2,
+    switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN3,
+    switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN4, switchtec_diag_pattern,
+    switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_11,
+    switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_15,
+    switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_23,
+    switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_31,
+    switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_7,
+    switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_9,
+    switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_DISABLED, switchtec_diag_pattern_gen_get,
+    switchtec_diag_pattern_gen_set, switchtec_diag_pattern_mon_get, switchtec_diag_pattern_mon_set,
+    SWITCHTEC_LTSSM_MAX_LOGS,
+};
+use crate::{get_switchtec_error, LtssmState, PortId, SwitchtecDevice};
+
+/// An inclusive range of eye capture steps, E.g. the phase offsets of the X axis
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EyeRange {
+    /// First step of the range
+    pub start: i32,
+    /// Last step of the range
+    pub end: i32,
+    /// Distance between steps
+    pub step: i32,
+}
+
+impl EyeRange {
+    /// Number of steps in the range
+    pub fn len(&self) -> usize {
+        if self.step <= 0 || self.end < self.start {
+            return 0;
+        }
+        ((self.end - self.start) / self.step + 1) as usize
+    }
+
+    /// Returns true if the range has no steps
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the steps of the range
+    pub fn steps(&self) -> impl Iterator<Item = i32> {
+        let Self { start, step, .. } = *self;
+        (0..self.len()).map(move |idx| start + idx as i32 * step)
+    }
+}
+
+impl From<EyeRange> for range {
+    fn from(r: EyeRange) -> Self {
+        Self {
+            start: r.start,
+            end: r.end,
+            step: r.step,
+        }
+    }
+}
+
+/// What each pixel of an eye capture holds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum EyeDataMode {
+    /// Raw error counts
+    Raw,
+    /// Ratio of errors to samples
+    #[default]
+    Ratio,
+}
+
+impl EyeDataMode {
+    fn raw(self) -> switchtec_diag_eye_data_mode {
+        match self {
+            Self::Raw => switchtec_diag_eye_data_mode_SWITCHTEC_DIAG_EYE_RAW,
+            Self::Ratio => switchtec_diag_eye_data_mode_SWITCHTEC_DIAG_EYE_RATIO,
+        }
+    }
+}
+
+/// Options for [`SwitchtecDevice::eye_capture`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EyeOpts {
+    /// Phase offsets to sample (the X axis of the eye)
+    pub x_range: EyeRange,
+    /// Voltage offsets to sample (the Y axis of the eye)
+    pub y_range: EyeRange,
+    /// Time to sample each pixel for, in milliseconds
+    pub step_interval: i32,
+    /// What each pixel holds
+    pub mode: EyeDataMode,
+    /// Cancel the capture if it hasn't finished after this long
+    pub timeout: Option<Duration>,
+}
+
+impl Default for EyeOpts {
+    /// The same defaults as the `switchtec diag eye` CLI command
+    fn default() -> Self {
+        Self {
+            x_range: EyeRange {
+                start: -64,
+                end: 64,
+                step: 1,
+            },
+            y_range: EyeRange {
+                start: -255,
+                end: 255,
+                step: 5,
+            },
+            step_interval: 1,
+            mode: EyeDataMode::default(),
+            timeout: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+/// The result of an eye capture of a single lane, returned by [`SwitchtecDevice::eye_capture`]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EyeData {
+    /// Lane the eye was captured on
+    pub lane: u32,
+    /// Phase offsets of each column
+    pub x_range: EyeRange,
+    /// Voltage offsets of each row
+    pub y_range: EyeRange,
+    /// Sampled pixels, indexed by row (Y) and then column (X)
+    pub pixels: Vec<Vec<f64>>,
+}
+
+impl EyeData {
+    /// Format the eye as CSV for plotting
+    ///
+    /// The first row holds the phase offset of each column, and the first column holds the
+    /// voltage offset of each row:
+    ///
+    /// ```
+    /// use switchtec_user_sys::diag::{EyeData, EyeRange};
+    ///
+    /// let eye = EyeData {
+    ///     lane: 0,
+    ///     x_range: EyeRange { start: -1, end: 1, step: 1 },
+    ///     y_range: EyeRange { start: 0, end: 5, step: 5 },
+    ///     pixels: vec![vec![0.5, 0.0, 0.5], vec![1.0, 0.25, 1.0]],
+    /// };
+    /// assert_eq!(eye.to_csv(), ",-1,0,1\n0,0.5,0,0.5\n5,1,0.25,1\n");
+    /// ```
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for x in self.x_range.steps() {
+            let _ = write!(csv, ",{x}");
+        }
+        csv.push('\n');
+        for (y, row) in self.y_range.steps().zip(&self.pixels) {
+            let _ = write!(csv, "{y}");
+            for pixel in row {
+                let _ = write!(csv, ",{pixel}");
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Link rate of an LTSSM loopback, set with [`SwitchtecDevice::set_loopback`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum LtssmSpeed {
+    /// PCIe Gen1, 2.5 GT/s
+    Gen1,
+    /// PCIe Gen2, 5 GT/s
+    Gen2,
+    /// PCIe Gen3, 8 GT/s
+    Gen3,
+    /// PCIe Gen4, 16 GT/s
+    Gen4,
+}
+
+impl LtssmSpeed {
+    fn raw(self) -> switchtec_diag_ltssm_speed {
+        match self {
+            Self::Gen1 => switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN1,
+            Self::Gen2 => switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN2,
+            Self::Gen3 => switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN3,
+            Self::Gen4 => switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN4,
+        }
+    }
+
+    fn from_raw(speed: switchtec_diag_ltssm_speed) -> Option<Self> {
+        match speed {
+            switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN1 => Some(Self::Gen1),
+            switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN2 => Some(Self::Gen2),
+            switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN3 => Some(Self::Gen3),
+            switchtec_diag_ltssm_speed_SWITCHTEC_DIAG_LTSSM_GEN4 => Some(Self::Gen4),
+            _ => None,
+        }
+    }
+}
+
+/// Loopback mode of a port, set with [`SwitchtecDevice::set_loopback`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum LoopbackMode {
+    /// Loopback is disabled and the port carries normal traffic
+    Disabled,
+    /// Data received on the port is sent back out of it
+    RxToTx,
+    /// Data sent by the port is looped back into its receiver
+    TxToRx,
+    /// The link is trained into LTSSM loopback at the given speed
+    Ltssm(LtssmSpeed),
+}
+
+impl LoopbackMode {
+    /// Get the raw `switchtec_diag_loopback_enable` bits and LTSSM speed for the mode
+    fn raw(self) -> (c_int, switchtec_diag_ltssm_speed) {
+        let no_speed = LtssmSpeed::Gen1.raw();
+        match self {
+            Self::Disabled => (0, no_speed),
+            Self::RxToTx => (
+                switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_RX_TO_TX as c_int,
+                no_speed,
+            ),
+            Self::TxToRx => (
+                switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_TX_TO_RX as c_int,
+                no_speed,
+            ),
+            Self::Ltssm(speed) => (
+                switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_LTSSM as c_int,
+                speed.raw(),
+            ),
+        }
+    }
+
+    /// Create the mode from the raw values reported by the firmware, if they're a single
+    /// known mode
+    fn from_raw(enabled: c_int, speed: switchtec_diag_ltssm_speed) -> Option<Self> {
+        match enabled as switchtec_diag_loopback_enable {
+            0 => Some(Self::Disabled),
+            switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_RX_TO_TX => Some(Self::RxToTx),
+            switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_TX_TO_RX => Some(Self::TxToRx),
+            switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_LTSSM => {
+                LtssmSpeed::from_raw(speed).map(Self::Ltssm)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A PRBS (pseudo-random bit sequence) test pattern for the pattern generator and monitor,
+/// using the ITU-T O.150 polynomials
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Pattern {
+    /// PRBS7, polynomial x^7 + x^6 + 1
+    Prbs7,
+    /// PRBS9, polynomial x^9 + x^5 + 1
+    Prbs9,
+    /// PRBS11, polynomial x^11 + x^9 + 1
+    Prbs11,
+    /// PRBS15, polynomial x^15 + x^14 + 1
+    Prbs15,
+    /// PRBS23, polynomial x^23 + x^18 + 1
+    Prbs23,
+    /// PRBS31, polynomial x^31 + x^28 + 1
+    Prbs31,
+}
+
+impl Pattern {
+    /// Get the raw `switchtec_diag_pattern` for an optional pattern, where `None` disables
+    /// the generator or monitor
+    fn raw(pattern: Option<Self>) -> switchtec_diag_pattern {
+        match pattern {
+            Some(Self::Prbs7) => switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_7,
+            Some(Self::Prbs9) => switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_9,
+            Some(Self::Prbs11) => switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_11,
+            Some(Self::Prbs15) => switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_15,
+            Some(Self::Prbs23) => switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_23,
+            Some(Self::Prbs31) => switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_31,
+            None => switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_DISABLED,
+        }
+    }
+
+    fn from_raw(pattern: switchtec_diag_pattern) -> Option<Self> {
+        match pattern {
+            switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_7 => Some(Self::Prbs7),
+            switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_9 => Some(Self::Prbs9),
+            switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_11 => Some(Self::Prbs11),
+            switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_15 => Some(Self::Prbs15),
+            switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_23 => Some(Self::Prbs23),
+            switchtec_diag_pattern_SWITCHTEC_DIAG_PATTERN_PRBS_31 => Some(Self::Prbs31),
+            _ => None,
+        }
+    }
+}
+
+/// The state of a lane's pattern monitor, returned by [`SwitchtecDevice::pattern_monitor`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PatternMonitor {
+    /// Pattern being monitored, or `None` if the monitor is disabled
+    pub pattern: Option<Pattern>,
+    /// Number of pattern errors seen since the monitor was enabled
+    pub errors: u64,
+}
+
+/// A port put into loopback with [`SwitchtecDevice::loopback_guard`], which is set back to
+/// its previous loopback mode when the guard is dropped
+///
+/// Loopback disrupts normal traffic on the port, so this keeps it from outliving a test that
+/// returns early or panics. Errors restoring the mode are ignored on drop, use
+/// [`LoopbackGuard::release`] to handle them.
+#[must_use = "the previous loopback mode is restored as soon as the guard is dropped"]
+pub struct LoopbackGuard<'a> {
+    device: &'a SwitchtecDevice,
+    port: PortId,
+    previous: Option<LoopbackMode>,
+}
+
+impl LoopbackGuard<'_> {
+    /// Restore the previous loopback mode now, returning any error
+    pub fn release(mut self) -> io::Result<()> {
+        match self.previous.take() {
+            Some(mode) => self.device.set_loopback(self.port, mode),
+            None => Ok(()),
+        }
+    }
+
+    /// Leave the port in loopback after the guard is dropped
+    pub fn keep(mut self) {
+        self.previous = None;
+    }
+}
+
+impl Drop for LoopbackGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(mode) = self.previous.take() {
+            let _ = self.device.set_loopback(self.port, mode);
+        }
+    }
+}
+
+/// A transition recorded in a port's LTSSM log, returned by [`SwitchtecDevice::ltssm_log`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LtssmLogEntry {
+    /// Position of the entry in the log, oldest first
+    pub index: usize,
+    /// Timestamp of the transition, in the firmware's clock ticks
+    pub timestamp: u32,
+    /// Link rate at the time of the transition, in GT/s
+    pub link_rate: f32,
+    /// The state the link transitioned into
+    pub state: LtssmState,
+    /// The raw LTSSM code reported by the firmware, with the major state in the low byte and
+    /// the minor state in the next byte
+    pub raw_state: i32,
+}
+
+impl From<(usize, &switchtec_diag_ltssm_log)> for LtssmLogEntry {
+    fn from((index, log): (usize, &switchtec_diag_ltssm_log)) -> Self {
+        Self {
+            index,
+            timestamp: log.timestamp,
+            link_rate: log.link_rate,
+            state: LtssmState::from_code(log.link_state),
+            raw_state: log.link_state,
+        }
+    }
+}
+
+impl SwitchtecDevice {
+    /// Capture the eye diagram of a single `lane`
+    ///
+    /// `libswitchtec` polls the firmware until the capture finishes, so when `opts.timeout`
+    /// is set, the capture is cancelled through a second handle to the device (see
+    /// [`SwitchtecDevice::try_clone`]) after the timeout and a
+    /// [`TimedOut`](io::ErrorKind::TimedOut) error is returned.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::diag::EyeOpts;
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let eye = device.eye_capture(0, EyeOpts::default())?;
+    /// std::fs::write("lane0_eye.csv", eye.to_csv())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn eye_capture(&self, lane: u32, opts: EyeOpts) -> io::Result<EyeData> {
+        let (columns, rows) = (opts.x_range.len(), opts.y_range.len());
+        if lane >= 128 || columns == 0 || rows == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "eye capture needs a lane below 128 and non-empty X and Y ranges",
+            ));
+        }
+        // The capture blocks the thread fetching it, so it's cancelled from another handle
+        let canceller = match opts.timeout {
+            Some(timeout) => Some((timeout, self.try_clone()?)),
+            None => None,
+        };
+
+        let mut lane_mask: [c_int; 4] = [0; 4];
+        lane_mask[(lane / 32) as usize] = 1 << (lane % 32);
+        let mut x_range = range::from(opts.x_range);
+        let mut y_range = range::from(opts.y_range);
+
+        // SAFETY: We know that device holds a valid/open switchtec device, and the lane mask
+        // holds the 4 elements the library expects
+        unsafe {
+            if traced!(switchtec_diag_eye_set_mode(self.inner, opts.mode.raw())) < 0 {
+                return Err(get_switchtec_error());
+            }
+            if traced!(switchtec_diag_eye_start(
+                self.inner,
+                lane_mask.as_mut_ptr(),
+                &mut x_range,
+                &mut y_range,
+                opts.step_interval,
+            )) < 0
+            {
+                return Err(get_switchtec_error());
+            }
+        }
+
+        let mut pixels = vec![0f64; columns * rows];
+        let mut lane_id: c_int = 0;
+        let timed_out = AtomicBool::new(false);
+        let ret = std::thread::scope(|s| {
+            let (done, finished) = mpsc::channel::<()>();
+            if let Some((timeout, canceller)) = canceller {
+                let timed_out = &timed_out;
+                s.spawn(move || {
+                    if finished.recv_timeout(timeout) == Err(mpsc::RecvTimeoutError::Timeout) {
+                        timed_out.store(true, Ordering::SeqCst);
+                        let _ = canceller.eye_cancel();
+                    }
+                });
+            }
+
+            // SAFETY: `pixels` holds `pixels.len()` elements, which is the pixel count we pass
+            let ret = unsafe {
+                traced!(switchtec_diag_eye_fetch(
+                    self.inner,
+                    pixels.as_mut_ptr(),
+                    pixels.len(),
+                    &mut lane_id,
+                ))
+            };
+            drop(done);
+            ret
+        });
+
+        if timed_out.load(Ordering::SeqCst) {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "eye capture didn't finish before the timeout",
+            ));
+        }
+        if ret < 0 {
+            let err = get_switchtec_error();
+            // Don't leave a failed capture running on the device
+            let _ = self.eye_cancel();
+            return Err(err);
+        }
+
+        Ok(EyeData {
+            lane: lane_id as u32,
+            x_range: opts.x_range,
+            y_range: opts.y_range,
+            pixels: pixels.chunks(columns).map(<[f64]>::to_vec).collect(),
+        })
+    }
+
+    /// Cancel a running eye capture, E.g. one left behind by an interrupted process
+    pub fn eye_cancel(&self) -> io::Result<()> {
+        // SAFETY: We know that device holds a valid/open switchtec device
+        let ret = unsafe { traced!(switchtec_diag_eye_cancel(self.inner)) };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(())
+    }
+
+    /// Set the loopback mode of the physical `port`
+    ///
+    /// This is intended for manufacturing test: any mode other than
+    /// [`LoopbackMode::Disabled`] disrupts normal traffic on the port until loopback is
+    /// disabled again, see [`SwitchtecDevice::loopback_guard`] to have it disabled
+    /// automatically.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::diag::{LoopbackMode, LtssmSpeed};
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// device.set_loopback(PortId(8), LoopbackMode::Ltssm(LtssmSpeed::Gen4))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_loopback(&self, port: PortId, mode: LoopbackMode) -> io::Result<()> {
+        let (enable, speed) = mode.raw();
+        // SAFETY: We know that device holds a valid/open switchtec device
+        let ret = unsafe {
+            traced!(switchtec_diag_loopback_set(
+                self.inner,
+                c_int::from(port),
+                enable,
+                speed
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(())
+    }
+
+    /// Set the loopback mode of the physical `port` like [`SwitchtecDevice::set_loopback`],
+    /// returning a guard that restores the port's previous mode when it's dropped
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::diag::{LoopbackMode, LtssmSpeed};
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let loopback = device.loopback_guard(PortId(8), LoopbackMode::Ltssm(LtssmSpeed::Gen4))?;
+    /// // An early return here still takes the port out of loopback
+    /// device.set_pattern_generator(PortId(8), None)?;
+    /// loopback.release()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn loopback_guard(
+        &self,
+        port: PortId,
+        mode: LoopbackMode,
+    ) -> io::Result<LoopbackGuard<'_>> {
+        let previous = self.loopback(port)?;
+        self.set_loopback(port, mode)?;
+        Ok(LoopbackGuard {
+            device: self,
+            port,
+            previous: Some(previous),
+        })
+    }
+
+    /// Get the loopback mode of the physical `port`
+    ///
+    /// Returns an [`InvalidData`](io::ErrorKind::InvalidData) error if the port has more than
+    /// one loopback mode enabled, or a mode this crate doesn't know about.
+    pub fn loopback(&self, port: PortId) -> io::Result<LoopbackMode> {
+        let mut enabled: c_int = 0;
+        let mut speed: switchtec_diag_ltssm_speed = 0;
+        // SAFETY: We know that device holds a valid/open switchtec device, and both out-values
+        // are valid to be populated
+        let ret = unsafe {
+            traced!(switchtec_diag_loopback_get(
+                self.inner,
+                c_int::from(port),
+                &mut enabled,
+                &mut speed
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        LoopbackMode::from_raw(enabled, speed).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("port {port} has an unknown loopback mode ({enabled:#x})"),
+            )
+        })
+    }
+
+    /// Start generating the test `pattern` on the physical `port`, or stop generating one
+    /// when `pattern` is `None`
+    ///
+    /// This is intended for manufacturing test: the generated pattern replaces normal
+    /// traffic on the port until the generator is stopped.
+    pub fn set_pattern_generator(&self, port: PortId, pattern: Option<Pattern>) -> io::Result<()> {
+        // SAFETY: We know that device holds a valid/open switchtec device
+        let ret = unsafe {
+            traced!(switchtec_diag_pattern_gen_set(
+                self.inner,
+                c_int::from(port),
+                Pattern::raw(pattern)
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(())
+    }
+
+    /// Get the test pattern being generated on the physical `port`, if any
+    pub fn pattern_generator(&self, port: PortId) -> io::Result<Option<Pattern>> {
+        let mut pattern: switchtec_diag_pattern = 0;
+        // SAFETY: We know that device holds a valid/open switchtec device
+        let ret = unsafe {
+            traced!(switchtec_diag_pattern_gen_get(
+                self.inner,
+                c_int::from(port),
+                &mut pattern
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(Pattern::from_raw(pattern))
+    }
+
+    /// Start monitoring the physical `port` for the test `pattern`, or stop monitoring when
+    /// `pattern` is `None`
+    ///
+    /// The port must be receiving the pattern (E.g. from the pattern generator of its link
+    /// partner), so like the generator, this disrupts normal traffic on the port.
+    pub fn set_pattern_monitor(&self, port: PortId, pattern: Option<Pattern>) -> io::Result<()> {
+        // SAFETY: We know that device holds a valid/open switchtec device
+        let ret = unsafe {
+            traced!(switchtec_diag_pattern_mon_set(
+                self.inner,
+                c_int::from(port),
+                Pattern::raw(pattern)
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(())
+    }
+
+    /// Read the LTSSM log of the physical `port`, the history of its link training state
+    /// transitions
+    ///
+    /// This is useful to see where a link that won't come up (or keeps retraining) gets
+    /// stuck. The firmware keeps a fixed number of the most recent transitions, and an empty
+    /// log is returned as an empty `Vec`. LTSSM logs are only supported by Gen4 and newer
+    /// switches.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for entry in device.ltssm_log(PortId(8))? {
+    ///     println!(
+    ///         "{:>3} {:>10}: {} ({} GT/s)",
+    ///         entry.index, entry.timestamp, entry.state, entry.link_rate
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ltssm_log(&self, port: PortId) -> io::Result<Vec<LtssmLogEntry>> {
+        // SAFETY: `switchtec_diag_ltssm_log` is plain data, so all zeroes is a valid value
+        let empty: switchtec_diag_ltssm_log = unsafe { MaybeUninit::zeroed().assume_init() };
+        let mut logs = vec![empty; SWITCHTEC_LTSSM_MAX_LOGS as usize];
+        let mut count = logs.len() as c_int;
+        // SAFETY: We know that device holds a valid/open switchtec device, and `logs` holds
+        // the `count` entries the library may populate
+        let ret = unsafe {
+            traced!(switchtec_diag_ltssm_log(
+                self.inner,
+                c_int::from(port),
+                &mut count,
+                logs.as_mut_ptr()
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        logs.truncate(count.clamp(0, logs.len() as c_int) as usize);
+        Ok(logs.iter().enumerate().map(LtssmLogEntry::from).collect())
+    }
+
+    /// Get the pattern monitored on `lane` of the physical `port`, and the number of errors
+    /// accumulated since the monitor was started
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::diag::Pattern;
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let port = PortId(8);
+    /// device.set_pattern_monitor(port, Some(Pattern::Prbs31))?;
+    /// std::thread::sleep(std::time::Duration::from_secs(10));
+    /// for lane in 0..4 {
+    ///     let monitor = device.pattern_monitor(port, lane)?;
+    ///     println!("Lane {lane}: {} errors", monitor.errors);
+    /// }
+    /// device.set_pattern_monitor(port, None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pattern_monitor(&self, port: PortId, lane: u32) -> io::Result<PatternMonitor> {
+        let mut pattern: switchtec_diag_pattern = 0;
+        let mut errors: u64 = 0;
+        // SAFETY: We know that device holds a valid/open switchtec device, and both out-values
+        // are valid to be populated
+        let ret = unsafe {
+            traced!(switchtec_diag_pattern_mon_get(
+                self.inner,
+                c_int::from(port),
+                lane as c_int,
+                &mut pattern,
+                &mut errors,
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(PatternMonitor {
+            pattern: Pattern::from_raw(pattern),
+            errors,
+        })
+    }
+}
+
+#[test]
+fn test_eye_range() {
+    let r = EyeRange {
+        start: -4,
+        end: 4,
+        step: 2,
+    };
+    assert_eq!(r.len(), 5);
+    assert_eq!(r.steps().collect::<Vec<_>>(), vec![-4, -2, 0, 2, 4]);
+    assert_eq!(EyeRange { step: 3, ..r }.len(), 3);
+    assert!(EyeRange { step: 0, ..r }.is_empty());
+    assert!(EyeRange { end: -5, ..r }.is_empty());
+}
+
+#[test]
+fn test_loopback_mode_raw() {
+    let modes = [
+        LoopbackMode::Disabled,
+        LoopbackMode::RxToTx,
+        LoopbackMode::TxToRx,
+        LoopbackMode::Ltssm(LtssmSpeed::Gen3),
+    ];
+    for mode in modes {
+        let (enabled, speed) = mode.raw();
+        assert_eq!(LoopbackMode::from_raw(enabled, speed), Some(mode));
+    }
+    // Multiple modes at once aren't representable
+    let both = switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_RX_TO_TX
+        | switchtec_diag_loopback_enable_SWITCHTEC_DIAG_LOOPBACK_TX_TO_RX;
+    assert_eq!(LoopbackMode::from_raw(both as c_int, 0), None);
+
+    assert_eq!(Pattern::from_raw(Pattern::raw(None)), None);
+    assert_eq!(
+        Pattern::from_raw(Pattern::raw(Some(Pattern::Prbs15))),
+        Some(Pattern::Prbs15)
+    );
+}
+
+#[test]
+fn test_ltssm_log_entry() {
+    let log = switchtec_diag_ltssm_log {
+        timestamp: 1234,
+        link_rate: 16.0,
+        link_state: 0x0104,
+    };
+    let entry = LtssmLogEntry::from((3, &log));
+    assert_eq!(entry.index, 3);
+    assert_eq!(entry.timestamp, 1234);
+    assert_eq!(entry.state, LtssmState::Recovery);
+    assert_eq!(entry.raw_state, 0x0104);
+}
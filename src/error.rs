@@ -0,0 +1,255 @@
+//! Typed errors for failures reported by the `switchtec-user` library
+//!
+//! [`SwitchtecDevice`](crate::SwitchtecDevice) methods return [`io::Error`]s so they compose
+//! with other I/O code, but errors originating from the library carry a [`SwitchtecError`]
+//! that can be recovered with [`SwitchtecError::from_io`]
+
+use std::io;
+use std::path::PathBuf;
+use std::str::Utf8Error;
+
+use crate::{switchtec_strerror, CStrExt};
+
+/// Bit the C library sets in `errno` when a failure is an MRPC status from the firmware
+/// rather than an OS error (`SWITCHTEC_ERRNO_MRPC_FLAG_BIT` in `switchtec/errors.h`)
+const MRPC_ERRNO_FLAG: i32 = 1 << 30;
+
+/// An error reported by the `switchtec-user` library
+#[derive(Debug, thiserror::Error)]
+pub enum SwitchtecError {
+    /// The Switchtec device could not be opened
+    #[error("unable to open switchtec device {}: {message}", path.display())]
+    Open {
+        /// Path the device was opened with
+        path: PathBuf,
+        /// Raw `errno` set by the library
+        errno: i32,
+        /// Message from `switchtec_strerror`
+        message: String,
+    },
+    /// The device behind the handle is gone (E.g. it dropped off after a reset or firmware
+    /// activation), so the handle can't be used anymore
+    ///
+    /// This is reported for `ENODEV`. The device can be reopened in place with
+    /// [`SwitchtecDevice::reconnect_with_timeout`](crate::SwitchtecDevice::reconnect_with_timeout).
+    #[error("switchtec device is no longer available")]
+    DeviceClosed,
+    /// An MRPC command was rejected by the firmware
+    #[error("MRPC command failed: {message} (status {status:#x})")]
+    Mrpc {
+        /// MRPC status code returned by the firmware (E.g. `ERR_CMD_INVALID`)
+        status: i32,
+        /// Message from `switchtec_strerror`
+        message: String,
+    },
+    /// The device is busy, E.g. another process holds the MRPC lock
+    ///
+    /// This is reported for `EBUSY` and `EAGAIN`, and the operation can be retried later. See
+    /// [`SwitchtecDevice::with_retry_on_busy`](crate::SwitchtecDevice::with_retry_on_busy).
+    #[error("{message}")]
+    Busy {
+        /// Raw `errno` set by the library
+        errno: i32,
+        /// Message from `switchtec_strerror`
+        message: String,
+    },
+    /// A string returned by the library was not valid UTF-8
+    #[error("invalid UTF-8 in string from switchtec: {0}")]
+    Utf8(#[from] Utf8Error),
+    /// Any other error reported by the library
+    #[error("{message}")]
+    Library {
+        /// Raw `errno` set by the library
+        errno: i32,
+        /// Message from `switchtec_strerror`
+        message: String,
+    },
+}
+
+impl SwitchtecError {
+    /// Build an error from the last error set by the library
+    ///
+    /// This must be called directly after the failing library call (E.g. one made through the
+    /// raw bindings), before anything else can overwrite `errno`. See
+    /// [`SwitchtecDevice::perror`](crate::SwitchtecDevice::perror) to add context to the error.
+    pub fn last() -> Self {
+        let errno = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+        Self::from_errno(errno, last_error_message())
+    }
+
+    /// Classify a raw `errno` set by the library
+    fn from_errno(errno: i32, message: String) -> Self {
+        if errno & MRPC_ERRNO_FLAG != 0 {
+            return Self::Mrpc {
+                status: errno & !MRPC_ERRNO_FLAG,
+                message,
+            };
+        }
+        match errno {
+            libc::ENODEV => Self::DeviceClosed,
+            libc::EBUSY | libc::EAGAIN => Self::Busy { errno, message },
+            _ => Self::Library { errno, message },
+        }
+    }
+
+    /// Build an [`SwitchtecError::Open`] error from the last error set by the library
+    pub(crate) fn open_failed(path: PathBuf) -> Self {
+        let errno = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+        let message = last_error_message();
+        Self::Open {
+            path,
+            errno,
+            message,
+        }
+    }
+
+    /// Prefix the message of the error with `context`, like `switchtec_perror` does
+    ///
+    /// ```
+    /// use switchtec_user_sys::SwitchtecError;
+    ///
+    /// let err = SwitchtecError::Library {
+    ///     errno: 5,
+    ///     message: "Input/output error".to_owned(),
+    /// };
+    /// assert_eq!(
+    ///     err.with_context("die_temp").to_string(),
+    ///     "die_temp: Input/output error"
+    /// );
+    /// ```
+    pub fn with_context(mut self, context: &str) -> Self {
+        match &mut self {
+            Self::Open { message, .. }
+            | Self::Mrpc { message, .. }
+            | Self::Busy { message, .. }
+            | Self::Library { message, .. } => {
+                *message = format!("{context}: {message}");
+            }
+            Self::DeviceClosed | Self::Utf8(_) => {}
+        }
+        self
+    }
+
+    /// Get the raw OS `errno` for this error, if there is one
+    pub fn errno(&self) -> Option<i32> {
+        match self {
+            Self::Open { errno, .. } | Self::Busy { errno, .. } | Self::Library { errno, .. }
+                if *errno != 0 =>
+            {
+                Some(*errno)
+            }
+            _ => None,
+        }
+    }
+
+    /// Check if an [`io::Error`] returned from this crate is a [`SwitchtecError::Busy`] error
+    pub fn is_busy(err: &io::Error) -> bool {
+        matches!(Self::from_io(err), Some(Self::Busy { .. }))
+    }
+
+    /// Get the [`SwitchtecError`] carried by an [`io::Error`] returned from this crate
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{SwitchtecDevice, SwitchtecError};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// match device.die_temp() {
+    ///     Ok(temp) => println!("Temperature: {temp}"),
+    ///     Err(e) => match SwitchtecError::from_io(&e) {
+    ///         Some(SwitchtecError::Mrpc { status, .. }) => println!("MRPC status: {status:#x}"),
+    ///         _ => return Err(e.into()),
+    ///     },
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_io(err: &io::Error) -> Option<&Self> {
+        err.get_ref().and_then(|e| e.downcast_ref::<Self>())
+    }
+}
+
+impl From<SwitchtecError> for io::Error {
+    fn from(err: SwitchtecError) -> Self {
+        let kind = match &err {
+            SwitchtecError::DeviceClosed => io::ErrorKind::NotConnected,
+            SwitchtecError::Utf8(_) => io::ErrorKind::InvalidData,
+            SwitchtecError::Mrpc { .. } => io::ErrorKind::Other,
+            SwitchtecError::Open { .. }
+            | SwitchtecError::Busy { .. }
+            | SwitchtecError::Library { .. } => match err.errno() {
+                Some(errno) => io::Error::from_raw_os_error(errno).kind(),
+                None => io::ErrorKind::Other,
+            },
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// Get the message for the last error set by the library
+fn last_error_message() -> String {
+    // SAFETY: We're checking that the returned char* is not null
+    unsafe {
+        // https://microsemi.github.io/switchtec-user/group__Device.html#ga595e1d62336ba76c59344352c334fa18
+        let err_str = switchtec_strerror();
+        if err_str.is_null() {
+            return "Unknown error".to_owned();
+        }
+        err_str
+            .as_string()
+            .unwrap_or_else(|_| "Unknown error".to_owned())
+    }
+}
+
+#[test]
+fn test_error_kinds() {
+    let err: io::Error = SwitchtecError::Library {
+        errno: 2,
+        message: "No such file or directory".to_owned(),
+    }
+    .into();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert!(matches!(
+        SwitchtecError::from_io(&err),
+        Some(SwitchtecError::Library { errno: 2, .. })
+    ));
+
+    let err: io::Error = SwitchtecError::Mrpc {
+        status: 0x64004,
+        message: "Invalid command".to_owned(),
+    }
+    .into();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+    assert_eq!(SwitchtecError::from_io(&err).unwrap().errno(), None);
+
+    let err: io::Error = SwitchtecError::DeviceClosed.into();
+    assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+    assert!(!SwitchtecError::is_busy(&err));
+}
+
+#[test]
+fn test_busy_errors() {
+    for errno in [libc::EBUSY, libc::EAGAIN] {
+        let err: io::Error = SwitchtecError::from_errno(errno, "busy".to_owned()).into();
+        assert!(SwitchtecError::is_busy(&err));
+        assert_eq!(SwitchtecError::from_io(&err).unwrap().errno(), Some(errno));
+    }
+    let err = SwitchtecError::from_errno(2, "No such file or directory".to_owned());
+    assert!(matches!(err, SwitchtecError::Library { errno: 2, .. }));
+    let err = SwitchtecError::from_errno(MRPC_ERRNO_FLAG | 16, "Invalid command".to_owned());
+    assert!(matches!(err, SwitchtecError::Mrpc { status: 16, .. }));
+}
+
+#[test]
+fn test_device_closed_error() {
+    let err: io::Error =
+        SwitchtecError::from_errno(libc::ENODEV, "No such device".to_owned()).into();
+    assert!(matches!(
+        SwitchtecError::from_io(&err),
+        Some(SwitchtecError::DeviceClosed)
+    ));
+    assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+    // An MRPC status with the same value is still an MRPC error
+    let err = SwitchtecError::from_errno(MRPC_ERRNO_FLAG | libc::ENODEV, "MRPC error".to_owned());
+    assert!(matches!(err, SwitchtecError::Mrpc { .. }));
+}
@@ -0,0 +1,521 @@
+//! Reading and waiting on the events raised by a Switchtec device
+//!
+//! Events are grouped into global events, partition events and port (PFF) events. Partition
+//! and port events are raised for a specific index (partition or port number), which is why
+//! most APIs here take an `index` alongside the [`EventId`].
+
+use std::fmt;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::raw::c_int;
+use std::time::{Duration, Instant};
+
+use crate::ffi::{
+    switchtec_event_check, switchtec_event_ctl, switchtec_event_flags_SWITCHTEC_EVT_FLAG_CLEAR,
+    switchtec_event_id, switchtec_event_summary_iter, switchtec_event_summary_test,
+    switchtec_event_wait_for, SWITCHTEC_EVT_IDX_ALL, SWITCHTEC_EVT_IDX_LOCAL,
+};
+use crate::{
+    get_switchtec_error, switchtec_event_summary, LinkRate, LinkWidth, PortId, PortStatus,
+    SwitchtecDevice,
+};
+
+/// Event index referring to the partition the device was opened on
+pub const EVENT_INDEX_LOCAL: i32 = SWITCHTEC_EVT_IDX_LOCAL;
+/// Event index referring to every partition or port
+pub const EVENT_INDEX_ALL: i32 = SWITCHTEC_EVT_IDX_ALL;
+
+macro_rules! event_ids {
+    ($($(#[$doc:meta])* $variant:ident => $raw:ident,)*) => {
+        /// An event that can be raised by a Switchtec device, mapped to the C
+        /// `switchtec_event_id` constants
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+        pub enum EventId {
+            $($(#[$doc])* $variant,)*
+        }
+
+        impl EventId {
+            /// Every known event
+            pub const ALL: &'static [EventId] = &[$(EventId::$variant,)*];
+
+            /// The `switchtec_event_id` value for this event
+            pub fn raw(self) -> switchtec_event_id {
+                match self {
+                    $(EventId::$variant => crate::ffi::$raw,)*
+                }
+            }
+        }
+
+        impl TryFrom<switchtec_event_id> for EventId {
+            type Error = io::Error;
+
+            fn try_from(raw: switchtec_event_id) -> io::Result<Self> {
+                match raw {
+                    $(crate::ffi::$raw => Ok(EventId::$variant),)*
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("unknown switchtec event id: {raw}"),
+                    )),
+                }
+            }
+        }
+    };
+}
+
+event_ids! {
+    /// Global: stack error
+    StackError => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_STACK_ERROR,
+    /// Global: PPU error
+    PpuError => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_PPU_ERROR,
+    /// Global: ISP error
+    IspError => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_ISP_ERROR,
+    /// Global: system reset
+    SysReset => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_SYS_RESET,
+    /// Global: firmware exception
+    FwException => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_FW_EXC,
+    /// Global: firmware non-maskable interrupt
+    FwNmi => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_FW_NMI,
+    /// Global: firmware non-fatal error
+    FwNonFatal => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_FW_NON_FATAL,
+    /// Global: firmware fatal error
+    FwFatal => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_FW_FATAL,
+    /// Global: TWI MRPC command completed
+    TwiMrpcComplete => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_TWI_MRPC_COMP,
+    /// Global: asynchronous TWI MRPC command completed
+    TwiMrpcCompleteAsync => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_TWI_MRPC_COMP_ASYNC,
+    /// Global: CLI MRPC command completed
+    CliMrpcComplete => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_CLI_MRPC_COMP,
+    /// Global: asynchronous CLI MRPC command completed
+    CliMrpcCompleteAsync => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_CLI_MRPC_COMP_ASYNC,
+    /// Global: GPIO interrupt
+    GpioInterrupt => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_GPIO_INT,
+    /// Global: GFMS (fabric management) event
+    Gfms => switchtec_event_id_SWITCHTEC_GLOBAL_EVT_GFMS,
+    /// Partition: partition reset
+    PartitionReset => switchtec_event_id_SWITCHTEC_PART_EVT_PART_RESET,
+    /// Partition: MRPC command completed
+    MrpcComplete => switchtec_event_id_SWITCHTEC_PART_EVT_MRPC_COMP,
+    /// Partition: asynchronous MRPC command completed
+    MrpcCompleteAsync => switchtec_event_id_SWITCHTEC_PART_EVT_MRPC_COMP_ASYNC,
+    /// Partition: dynamic partition binding completed
+    DynPartitionBindComplete => switchtec_event_id_SWITCHTEC_PART_EVT_DYN_PART_BIND_COMP,
+    /// Port: AER in P2P port
+    AerInP2p => switchtec_event_id_SWITCHTEC_PFF_EVT_AER_IN_P2P,
+    /// Port: AER in VEP
+    AerInVep => switchtec_event_id_SWITCHTEC_PFF_EVT_AER_IN_VEP,
+    /// Port: downstream port containment
+    Dpc => switchtec_event_id_SWITCHTEC_PFF_EVT_DPC,
+    /// Port: completion timeout
+    Cts => switchtec_event_id_SWITCHTEC_PFF_EVT_CTS,
+    /// Port: unsupported request
+    Uec => switchtec_event_id_SWITCHTEC_PFF_EVT_UEC,
+    /// Port: hotplug
+    Hotplug => switchtec_event_id_SWITCHTEC_PFF_EVT_HOTPLUG,
+    /// Port: internal error
+    Ier => switchtec_event_id_SWITCHTEC_PFF_EVT_IER,
+    /// Port: event counter threshold reached
+    Threshold => switchtec_event_id_SWITCHTEC_PFF_EVT_THRESH,
+    /// Port: power management
+    PowerManagement => switchtec_event_id_SWITCHTEC_PFF_EVT_POWER_MGMT,
+    /// Port: TLP throttling
+    TlpThrottling => switchtec_event_id_SWITCHTEC_PFF_EVT_TLP_THROTTLING,
+    /// Port: force speed
+    ForceSpeed => switchtec_event_id_SWITCHTEC_PFF_EVT_FORCE_SPEED,
+    /// Port: credit timeout
+    CreditTimeout => switchtec_event_id_SWITCHTEC_PFF_EVT_CREDIT_TIMEOUT,
+    /// Port: link state change
+    LinkState => switchtec_event_id_SWITCHTEC_PFF_EVT_LINK_STATE,
+}
+
+/// The set of events pending on a device, returned by [`SwitchtecDevice::event_summary`],
+/// [`SwitchtecDevice::check_events`] and [`SwitchtecDevice::wait_for_event`]
+///
+/// This is an owned copy of the `switchtec_event_summary` struct
+#[derive(Clone, Copy)]
+pub struct EventSummary(switchtec_event_summary);
+
+impl EventSummary {
+    /// Bitmap of the pending global events
+    pub fn global(&self) -> u64 {
+        self.0.global
+    }
+
+    /// Bitmap of the partitions that have pending events
+    pub fn partition_bitmap(&self) -> u64 {
+        self.0.part_bitmap
+    }
+
+    /// Bitmap of the pending events in the local partition
+    pub fn local_partition(&self) -> u32 {
+        self.0.local_part
+    }
+
+    /// Bitmap of the pending events in the given partition, if it's in range
+    pub fn partition(&self, index: usize) -> Option<u32> {
+        self.0.part.get(index).copied()
+    }
+
+    /// Bitmap of the pending events on the given port (PFF), if it's in range
+    pub fn pff(&self, index: usize) -> Option<u32> {
+        self.0.pff.get(index).copied()
+    }
+
+    /// Check if `event` is pending at `index`
+    ///
+    /// `index` is ignored for global events, and can be [`EVENT_INDEX_LOCAL`] or
+    /// [`EVENT_INDEX_ALL`] for partition and port events
+    pub fn contains(&self, event: EventId, index: i32) -> bool {
+        // `switchtec_event_summary_test` only reads the summary, but takes a mutable pointer
+        let mut summary = self.0;
+        // SAFETY: `summary` is a valid, local copy of the event summary
+        unsafe { switchtec_event_summary_test(&mut summary, event.raw(), index) != 0 }
+    }
+
+    /// Check if no events are pending
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Iterate over the pending events, along with the partition or port index they were
+    /// raised for (`0` for global events)
+    pub fn iter(&self) -> impl Iterator<Item = (EventId, i32)> {
+        let mut summary = self.0;
+        std::iter::from_fn(move || loop {
+            let mut event: switchtec_event_id = 0;
+            let mut index: c_int = 0;
+            // SAFETY: `switchtec_event_summary_iter` clears each event from our local copy of
+            // the summary as it's returned, and returns 0 once no events remain
+            let found =
+                unsafe { switchtec_event_summary_iter(&mut summary, &mut event, &mut index) };
+            if found == 0 {
+                return None;
+            }
+            // Skip any events newer than this crate knows about
+            if let Ok(event) = EventId::try_from(event) {
+                return Some((event, index));
+            }
+        })
+    }
+}
+
+impl fmt::Debug for EventSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// The new link state of a port, returned by [`SwitchtecDevice::wait_for_link_change`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LinkEvent {
+    /// Physical port whose link changed
+    pub port: PortId,
+    /// Whether the link is now up
+    pub link_up: bool,
+    /// Negotiated link rate (PCIe generation)
+    pub link_rate: LinkRate,
+    /// Negotiated link width (number of lanes)
+    pub link_width: LinkWidth,
+}
+
+impl From<&PortStatus> for LinkEvent {
+    fn from(status: &PortStatus) -> Self {
+        Self {
+            port: status.phys_port,
+            link_up: status.link_up,
+            link_rate: status.link_rate,
+            link_width: status.link_width,
+        }
+    }
+}
+
+impl SwitchtecDevice {
+    /// Get the summary of all events currently pending on the device
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for (event, index) in device.event_summary()?.iter() {
+    ///     println!("{event:?} (index {index})");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn event_summary(&self) -> io::Result<EventSummary> {
+        let mut summary = MaybeUninit::<switchtec_event_summary>::zeroed();
+
+        // SAFETY: `summary` is only read after the library reports that it was populated
+        unsafe {
+            if traced!(switchtec_event_summary(self.inner, summary.as_mut_ptr())) < 0 {
+                return Err(get_switchtec_error());
+            }
+            Ok(EventSummary(summary.assume_init()))
+        }
+    }
+
+    /// Check which events are pending on the device, without blocking
+    ///
+    /// This is the non-blocking counterpart to [`SwitchtecDevice::wait_for_event`], for poll
+    /// loops. Every event is checked, and an empty summary is returned if none are pending.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// loop {
+    ///     let pending = device.check_events()?;
+    ///     if !pending.is_empty() {
+    ///         println!("Pending events: {pending:?}");
+    ///     }
+    ///     std::thread::sleep(Duration::from_secs(1));
+    /// }
+    /// # }
+    /// ```
+    pub fn check_events(&self) -> io::Result<EventSummary> {
+        let mut check = MaybeUninit::<switchtec_event_summary>::uninit();
+        let mut pending = MaybeUninit::<switchtec_event_summary>::zeroed();
+
+        // SAFETY: `check` is fully initialized with every event bit set before it's passed to
+        // the library, and `pending` is zeroed so it's an empty summary if nothing is pending
+        unsafe {
+            check.as_mut_ptr().write_bytes(0xff, 1);
+            let ret = traced!(switchtec_event_check(
+                self.inner,
+                check.as_mut_ptr(),
+                pending.as_mut_ptr()
+            ));
+            if ret < 0 {
+                return Err(get_switchtec_error());
+            }
+            Ok(EventSummary(pending.assume_init()))
+        }
+    }
+
+    /// Clear (acknowledge) `event` at `index`, so it's no longer reported as pending
+    ///
+    /// `index` is ignored for global events, and can be [`EVENT_INDEX_LOCAL`] or
+    /// [`EVENT_INDEX_ALL`] for partition and port events
+    ///
+    /// # Latched and level-sensitive events
+    ///
+    /// Most events are latched: they're raised once when something happens, and stay pending
+    /// in [`SwitchtecDevice::event_summary`] and [`SwitchtecDevice::check_events`] until
+    /// they're cleared, even if the cause is gone. Clearing one acknowledges that occurrence,
+    /// and it's only reported again the next time it happens. These are the reset events
+    /// ([`EventId::SysReset`], [`EventId::PartitionReset`]), the MRPC completion events, the
+    /// firmware error events ([`EventId::FwException`], [`EventId::FwNmi`],
+    /// [`EventId::FwNonFatal`], [`EventId::FwFatal`] and the stack, PPU and ISP errors),
+    /// [`EventId::DynPartitionBindComplete`], [`EventId::Gfms`], [`EventId::Hotplug`],
+    /// [`EventId::LinkState`], [`EventId::PowerManagement`], [`EventId::ForceSpeed`],
+    /// [`EventId::Cts`], [`EventId::Uec`], [`EventId::Ier`] and [`EventId::CreditTimeout`].
+    ///
+    /// The events that report an ongoing condition are level-sensitive: the switch raises them
+    /// again for as long as the condition holds, so they're pending again right after they're
+    /// cleared. These are:
+    /// - [`EventId::AerInP2p`] and [`EventId::AerInVep`], until the error status is cleared in
+    ///   the port's AER registers (E.g. by the host's AER driver)
+    /// - [`EventId::Dpc`], until the port is released from downstream port containment
+    /// - [`EventId::TlpThrottling`], while TLPs are being throttled
+    /// - [`EventId::Threshold`], while the event counter stays over its threshold
+    /// - [`EventId::GpioInterrupt`], while a level-triggered GPIO stays asserted
+    ///
+    /// Deal with the condition before clearing a level-sensitive event; clearing it in a loop
+    /// while the condition holds never makes it go away.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for (event, index) in device.check_events()?.iter() {
+    ///     println!("{event:?} (index {index})");
+    ///     device.clear_event(event, index)?;
+    /// }
+    /// // The events that were handled aren't reported again until they're raised again
+    /// println!("Still pending: {:?}", device.check_events()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clear_event(&self, event: EventId, index: i32) -> io::Result<()> {
+        // The library copies the event's header and data out, even though we don't use them
+        let mut data = [0u32; 5];
+        // SAFETY: We know that device holds a valid/open switchtec device, and `data` is large
+        // enough for the event data the library copies out
+        let ret = unsafe {
+            traced!(switchtec_event_ctl(
+                self.inner,
+                event.raw(),
+                index,
+                switchtec_event_flags_SWITCHTEC_EVT_FLAG_CLEAR as c_int,
+                data.as_mut_ptr(),
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(())
+    }
+
+    /// Clear (acknowledge) every pending event, returning the events that were cleared
+    ///
+    /// See [`SwitchtecDevice::clear_event`] for how events latch. Events raised between
+    /// reading the pending events and clearing them are left pending.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let cleared = device.clear_all_events()?;
+    /// println!("Cleared: {cleared:?}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clear_all_events(&self) -> io::Result<EventSummary> {
+        let pending = self.check_events()?;
+        for (event, index) in pending.iter() {
+            self.clear_event(event, index)?;
+        }
+        Ok(pending)
+    }
+
+    /// Block until `event` is raised at `index`, or until `timeout` elapses
+    ///
+    /// - A `timeout` of `None` waits forever
+    /// - Returns `Ok(None)` if the timeout elapsed before the event was raised, otherwise the
+    ///   summary of the events that fired
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use switchtec_user_sys::events::{EventId, EVENT_INDEX_ALL};
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let timeout = Some(Duration::from_secs(10));
+    /// match device.wait_for_event(EventId::LinkState, EVENT_INDEX_ALL, timeout)? {
+    ///     Some(fired) => println!("Link state changed: {fired:?}"),
+    ///     None => println!("No link state change in 10s"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wait_for_event(
+        &self,
+        event: EventId,
+        index: i32,
+        timeout: Option<Duration>,
+    ) -> io::Result<Option<EventSummary>> {
+        let mut summary = MaybeUninit::<switchtec_event_summary>::zeroed();
+
+        // SAFETY: `summary` is only read after the library reports that the event fired and
+        // the summary was populated
+        unsafe {
+            let ret = traced!(switchtec_event_wait_for(
+                self.inner,
+                event.raw(),
+                index,
+                summary.as_mut_ptr(),
+                timeout_ms(timeout),
+            ));
+            match ret {
+                0 => Ok(None),
+                ret if ret < 0 => Err(get_switchtec_error()),
+                _ => Ok(Some(EventSummary(summary.assume_init()))),
+            }
+        }
+    }
+
+    /// Block until the link of the physical `port` goes up or down, or changes rate or width
+    ///
+    /// A link state event can be raised for any port, and can fire without a visible change,
+    /// so the port's status is re-read after each event and waiting continues until it
+    /// differs from the status when this was called. A change that happens before the wait
+    /// starts is only noticed after the next link state event.
+    ///
+    /// - A `timeout` of `None` waits forever
+    /// - Returns a [`TimedOut`](io::ErrorKind::TimedOut) error if the timeout elapsed before
+    ///   the link changed, or an [`InvalidInput`](io::ErrorKind::InvalidInput) error if the
+    ///   device has no such port
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// loop {
+    ///     let link = device.wait_for_link_change(PortId(8), None)?;
+    ///     if link.link_up {
+    ///         println!("Link up: {} at {}", link.link_width, link.link_rate);
+    ///     } else {
+    ///         println!("Link down");
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn wait_for_link_change(
+        &self,
+        port: PortId,
+        timeout: Option<Duration>,
+    ) -> io::Result<LinkEvent> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let initial = self.link_state(port)?;
+        loop {
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let fired = match remaining {
+                Some(Duration::ZERO) => None,
+                remaining => self.wait_for_event(EventId::LinkState, EVENT_INDEX_ALL, remaining)?,
+            };
+            if fired.is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("link of port {port} didn't change before the timeout"),
+                ));
+            }
+            let current = self.link_state(port)?;
+            if current != initial {
+                return Ok(current);
+            }
+        }
+    }
+
+    /// Read the current link state of the physical `port`
+    fn link_state(&self, port: PortId) -> io::Result<LinkEvent> {
+        self.ports()?
+            .find(|status| status.phys_port == port)
+            .map(|status| LinkEvent::from(&status))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("device has no port {port}"),
+                )
+            })
+    }
+}
+
+/// Convert an optional timeout to the milliseconds expected by `libswitchtec`, where
+/// `-1` blocks forever
+fn timeout_ms(timeout: Option<Duration>) -> c_int {
+    match timeout {
+        Some(timeout) => c_int::try_from(timeout.as_millis()).unwrap_or(c_int::MAX),
+        None => -1,
+    }
+}
+
+#[test]
+fn test_event_ids() {
+    for event in EventId::ALL {
+        assert_eq!(EventId::try_from(event.raw()).unwrap(), *event);
+    }
+    assert!(EventId::try_from(-1).is_err());
+
+    assert_eq!(timeout_ms(None), -1);
+    assert_eq!(timeout_ms(Some(Duration::from_millis(1500))), 1500);
+    assert_eq!(timeout_ms(Some(Duration::MAX)), c_int::MAX);
+}
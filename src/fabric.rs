@@ -0,0 +1,353 @@
+//! Fabric topology for PAX switches running in fabric mode
+//!
+//! The GFMS database dump (the hosts, end devices, their PCI properties and bindings the
+//! fabric manager tracks) isn't wrapped yet. Its `switchtec_fab_gfms_db_dump_*` functions
+//! fill nested, firmware-version dependent structs with variable length sections, and need
+//! owned types checked against captured dumps before they can be read safely. Until then,
+//! they can be called through the raw bindings in [`ffi`](crate::ffi).
+
+use std::fmt;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::raw::c_int;
+
+use crate::ffi::{
+    switchtec_fab_port_info, switchtec_fab_topo_info, switchtec_gfms_bind, switchtec_gfms_bind_req,
+    switchtec_gfms_unbind, switchtec_gfms_unbind_req, switchtec_topo_info_dump, switchtec_variant,
+    SWITCHTEC_FABRIC_MULTI_FUNC_NUM,
+};
+use crate::{get_switchtec_error, PortId, SwitchtecDevice, Variant};
+
+/// Maximum number of end device functions that can be bound in one [`SwitchtecDevice::gfms_bind`]
+pub const MAX_BIND_FUNCTIONS: usize = SWITCHTEC_FABRIC_MULTI_FUNC_NUM as usize;
+
+/// A physical port of a fabric switch, as reported by [`SwitchtecDevice::topology`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FabricPort {
+    /// Physical port number
+    pub phys_port: PortId,
+    /// Type of the port in the fabric (E.g. fabric link, endpoint or host port), as the raw
+    /// value reported by the firmware
+    pub port_type: u8,
+    /// Clock channel the port uses
+    pub clock_channel: u8,
+    /// Connector the port is wired to
+    pub connector: u8,
+    /// Configured link width (number of lanes)
+    pub cfg_link_width: u8,
+    /// Negotiated link width (number of lanes)
+    pub link_width: u8,
+    /// Configured link rate (PCIe generation)
+    pub cfg_link_rate: u8,
+    /// Negotiated link rate (PCIe generation)
+    pub link_rate: u8,
+    /// Major LTSSM state of the link
+    pub ltssm_major: u8,
+    /// Minor LTSSM state of the link
+    pub ltssm_minor: u8,
+}
+
+impl From<&switchtec_fab_port_info> for FabricPort {
+    fn from(info: &switchtec_fab_port_info) -> Self {
+        Self {
+            phys_port: PortId(info.phys_port_id),
+            port_type: info.port_type,
+            clock_channel: info.port_clock_channel,
+            connector: info.port_connector_id,
+            cfg_link_width: info.port_cfg_width,
+            link_width: info.port_neg_width,
+            cfg_link_rate: info.port_cfg_rate,
+            link_rate: info.port_neg_rate,
+            ltssm_major: info.port_major_ltssm,
+            ltssm_minor: info.port_minor_ltssm,
+        }
+    }
+}
+
+/// The fabric topology of a PAX switch, returned by [`SwitchtecDevice::topology`]
+///
+/// This is an owned copy of the `switchtec_fab_topo_info` struct populated by
+/// `switchtec_topo_info_dump`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Topology {
+    /// Index of this switch in the fabric
+    pub switch_index: i32,
+    /// Bifurcation of each stack
+    pub stack_bifurcation: Vec<u16>,
+    /// Port each other switch in the fabric is routed through, indexed by switch index
+    pub route_ports: Vec<u8>,
+    /// The enabled physical ports of the switch
+    pub ports: Vec<FabricPort>,
+}
+
+impl From<&switchtec_fab_topo_info> for Topology {
+    fn from(info: &switchtec_fab_topo_info) -> Self {
+        let num_stack_bif = (info.num_stack_bif.max(0) as usize).min(info.stack_bif.len());
+        let ports = info
+            .port_info_list
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| *idx < 64 && info.port_bitmap & (1 << idx) != 0)
+            .map(|(_, port)| port.into())
+            .collect();
+        Self {
+            switch_index: info.sw_idx,
+            stack_bifurcation: info.stack_bif[..num_stack_bif].to_vec(),
+            route_ports: info.route_port.to_vec(),
+            ports,
+        }
+    }
+}
+
+/// A host port in the fabric, that end device functions are bound to with
+/// [`SwitchtecDevice::gfms_bind`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HostPort {
+    /// Index of the switch the host is connected to (See [`Topology::switch_index`])
+    pub switch_index: u8,
+    /// Physical port number the host is connected to
+    pub phys_port: PortId,
+    /// Logical port number of the host's downstream port to bind the functions under
+    pub log_port: u8,
+}
+
+impl fmt::Display for HostPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "switch {} port {} (logical port {})",
+            self.switch_index, self.phys_port, self.log_port
+        )
+    }
+}
+
+impl SwitchtecDevice {
+    /// Get the fabric topology of a PAX switch
+    ///
+    /// Returns an [`Unsupported`](io::ErrorKind::Unsupported) error for switches that aren't
+    /// PAX variants, since they have no fabric to report
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let topology = device.topology()?;
+    /// for port in &topology.ports {
+    ///     println!("Port {}: x{} Gen{}", port.phys_port, port.link_width, port.link_rate);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn topology(&self) -> io::Result<Topology> {
+        self.check_fabric("fabric topology")?;
+
+        let mut info = MaybeUninit::<switchtec_fab_topo_info>::zeroed();
+
+        // SAFETY: `info` is only read after the library reports that it was populated
+        unsafe {
+            if traced!(switchtec_topo_info_dump(self.inner, info.as_mut_ptr())) < 0 {
+                return Err(get_switchtec_error());
+            }
+            Ok((&info.assume_init()).into())
+        }
+    }
+
+    /// Bind end device functions to a host port, through the Global Fabric Management Service
+    /// (GFMS) of a PAX switch
+    ///
+    /// Each function is identified by its PCIe Domain Function ID (PDFID). Binding changes what
+    /// the host sees, so the functions must not already be bound to another host, and the host
+    /// should be ready to rescan its PCIe hierarchy. At most [`MAX_BIND_FUNCTIONS`] functions
+    /// can be bound at once.
+    ///
+    /// Returns an [`Unsupported`](io::ErrorKind::Unsupported) error for switches that aren't PAX
+    /// variants, and an [`InvalidInput`](io::ErrorKind::InvalidInput) error if `pdfids` is
+    /// empty or too long. Errors reported by the firmware (E.g. for an invalid binding) include
+    /// the host port and functions in their message.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::fabric::HostPort;
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let host = HostPort {
+    ///     switch_index: 0,
+    ///     phys_port: PortId(0),
+    ///     log_port: 1,
+    /// };
+    /// device.gfms_bind(host, &[0x0100])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn gfms_bind(&self, host: HostPort, pdfids: &[u16]) -> io::Result<()> {
+        self.check_fabric("GFMS binding")?;
+        check_bind_functions(pdfids)?;
+
+        let mut req = bind_req(host, pdfids);
+        // SAFETY: We know that device holds a valid/open switchtec device, and `req` is only
+        // read by the library
+        if unsafe { traced!(switchtec_gfms_bind(self.inner, &mut req)) } < 0 {
+            return Err(self.perror(&format!("binding {pdfids:#06x?} to {host}")));
+        }
+        Ok(())
+    }
+
+    /// Unbind an end device function from a host port, through the Global Fabric Management
+    /// Service (GFMS) of a PAX switch
+    ///
+    /// The function is identified by its PCIe Domain Function ID (PDFID). The host loses access
+    /// to the function immediately, so it should be removed from the host's PCIe hierarchy
+    /// first.
+    ///
+    /// Returns an [`Unsupported`](io::ErrorKind::Unsupported) error for switches that aren't PAX
+    /// variants. Errors reported by the firmware (E.g. if the function isn't bound to `host`)
+    /// include the host port and function in their message.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::fabric::HostPort;
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let host = HostPort {
+    ///     switch_index: 0,
+    ///     phys_port: PortId(0),
+    ///     log_port: 1,
+    /// };
+    /// device.gfms_unbind(host, 0x0100)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn gfms_unbind(&self, host: HostPort, pdfid: u16) -> io::Result<()> {
+        self.check_fabric("GFMS unbinding")?;
+
+        let mut req = unbind_req(host, pdfid);
+        // SAFETY: We know that device holds a valid/open switchtec device, and `req` is only
+        // read by the library
+        if unsafe { traced!(switchtec_gfms_unbind(self.inner, &mut req)) } < 0 {
+            return Err(self.perror(&format!("unbinding {pdfid:#06x} from {host}")));
+        }
+        Ok(())
+    }
+
+    /// Check that the device is a PAX switch, which is the only variant with a fabric
+    fn check_fabric(&self, feature: &str) -> io::Result<()> {
+        // SAFETY: `switchtec_variant` only reads from the open device
+        let variant = Variant::from(unsafe { switchtec_variant(self.inner) });
+        if !variant.is_fabric() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{feature} is only available on PAX switches"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn check_bind_functions(pdfids: &[u16]) -> io::Result<()> {
+    if pdfids.is_empty() || pdfids.len() > MAX_BIND_FUNCTIONS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} functions given, but between 1 and {MAX_BIND_FUNCTIONS} can be bound at once",
+                pdfids.len()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Build the `switchtec_gfms_bind_req` binding `pdfids` (already checked with
+/// [`check_bind_functions`]) to `host`
+fn bind_req(host: HostPort, pdfids: &[u16]) -> switchtec_gfms_bind_req {
+    // SAFETY: `switchtec_gfms_bind_req` is plain data, so all zeroes is a valid value
+    let mut req: switchtec_gfms_bind_req = unsafe { MaybeUninit::zeroed().assume_init() };
+    req.host_sw_idx = host.switch_index;
+    req.host_phys_port_id = host.phys_port.0;
+    req.host_log_port_id = host.log_port;
+    req.ep_number = pdfids.len() as c_int;
+    req.ep_pdfid[..pdfids.len()].copy_from_slice(pdfids);
+    req
+}
+
+/// Build the `switchtec_gfms_unbind_req` unbinding `pdfid` from `host`
+fn unbind_req(host: HostPort, pdfid: u16) -> switchtec_gfms_unbind_req {
+    // SAFETY: `switchtec_gfms_unbind_req` is plain data, so all zeroes is a valid value
+    let mut req: switchtec_gfms_unbind_req = unsafe { MaybeUninit::zeroed().assume_init() };
+    req.host_sw_idx = host.switch_index;
+    req.host_phys_port_id = host.phys_port.0;
+    req.host_log_port_id = host.log_port;
+    req.pdfid = pdfid;
+    req
+}
+
+#[test]
+fn test_topology_from_info() {
+    // SAFETY: `switchtec_fab_topo_info` is plain data, so all zeroes is a valid value
+    let mut info: switchtec_fab_topo_info = unsafe { MaybeUninit::zeroed().assume_init() };
+    info.sw_idx = 1;
+    info.num_stack_bif = 2;
+    info.stack_bif[0] = 0x11;
+    info.stack_bif[1] = 0x22;
+    info.port_bitmap = 0b101;
+    info.port_info_list[0].phys_port_id = 0;
+    info.port_info_list[2].phys_port_id = 2;
+    info.port_info_list[2].port_neg_width = 4;
+
+    let topology = Topology::from(&info);
+    assert_eq!(topology.switch_index, 1);
+    assert_eq!(topology.stack_bifurcation, vec![0x11, 0x22]);
+    assert_eq!(topology.ports.len(), 2);
+    assert_eq!(topology.ports[1].phys_port, PortId(2));
+    assert_eq!(topology.ports[1].link_width, 4);
+}
+
+#[test]
+fn test_check_bind_functions() {
+    assert!(check_bind_functions(&[0x100]).is_ok());
+    assert!(check_bind_functions(&[0; MAX_BIND_FUNCTIONS]).is_ok());
+    for pdfids in [&[][..], &[0; MAX_BIND_FUNCTIONS + 1]] {
+        let err = check_bind_functions(pdfids).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+    let host = HostPort {
+        switch_index: 1,
+        phys_port: PortId(8),
+        log_port: 2,
+    };
+    assert_eq!(host.to_string(), "switch 1 port 8 (logical port 2)");
+}
+
+#[test]
+fn test_gfms_reqs() {
+    let host = HostPort {
+        switch_index: 1,
+        phys_port: PortId(8),
+        log_port: 2,
+    };
+
+    let req = bind_req(host, &[0x0100, 0x0101]);
+    assert_eq!(
+        (req.host_sw_idx, req.host_phys_port_id, req.host_log_port_id),
+        (1, 8, 2)
+    );
+    assert_eq!(req.ep_number, 2);
+    assert_eq!(req.ep_pdfid.len(), MAX_BIND_FUNCTIONS);
+    assert_eq!(&req.ep_pdfid[..3], &[0x0100, 0x0101, 0]);
+    // Every slot of the request can be filled
+    let req = bind_req(host, &[0x0200; MAX_BIND_FUNCTIONS]);
+    assert_eq!(req.ep_number as usize, MAX_BIND_FUNCTIONS);
+
+    let req = unbind_req(host, 0x0100);
+    assert_eq!(
+        (req.host_sw_idx, req.host_phys_port_id, req.host_log_port_id),
+        (1, 8, 2)
+    );
+    assert_eq!(req.pdfid, 0x0100);
+    assert_eq!(req.option, 0);
+}
@@ -0,0 +1,1501 @@
+//! Safe wrappers for working with the firmware partitions stored in a Switchtec device's flash
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use crate::crc::switchtec_crc;
+use crate::ffi::{
+    switchtec_fw_dlstatus, switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_COMPLETES,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_CRC_INCORRECT,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_DOWNLOAD_TIMEOUT,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_HARDWARE_ERR,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_HEADER_INCORRECT,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_INPROGRESS,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_LENGTH_INCORRECT,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_NOT_ALLOWED,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_NO_FILE,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_OFFSET_INCORRECT,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_PACKAGE_TOO_SMALL,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_PROGRAM_RUNNING,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_READONLY_PARTITION,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_READY, switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SEEPROM,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SEEPROM_TWI_NOT_ENABLED,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SIG_MEM_ALLOC,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SUCCESS_DATA_ACT,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SUCCESS_FIRM_ACT,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_UNKNOWN_ACT,
+    switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_XML_MISMATCH_ACT, switchtec_fw_read, switchtec_fw_type,
+};
+use crate::fwlog::{random_u64, TEMP_FILE_ATTEMPTS};
+use crate::mrpc::{
+    mrpc_bg_status, mrpc_bg_status_MRPC_BG_STAT_DONE, mrpc_bg_status_MRPC_BG_STAT_ERROR,
+    mrpc_bg_status_MRPC_BG_STAT_INPROGRESS,
+};
+use crate::open::Transport;
+use crate::progress::{percent, with_byte_progress, with_progress};
+use crate::{
+    char_buf_to_string, get_switchtec_error, switchtec_fw_body_read_fd, switchtec_fw_file_info,
+    switchtec_fw_image_info, switchtec_fw_image_type, switchtec_fw_img_write_hdr,
+    switchtec_fw_is_boot_ro, switchtec_fw_part_summary, switchtec_fw_part_summary_free,
+    switchtec_fw_part_summary_switchtec_fw_part_type, switchtec_fw_ro_SWITCHTEC_FW_RO,
+    switchtec_fw_toggle_active_partition, switchtec_fw_type_SWITCHTEC_FW_TYPE_BL2,
+    switchtec_fw_type_SWITCHTEC_FW_TYPE_BOOT, switchtec_fw_type_SWITCHTEC_FW_TYPE_CFG,
+    switchtec_fw_type_SWITCHTEC_FW_TYPE_IMG, switchtec_fw_type_SWITCHTEC_FW_TYPE_KEY,
+    switchtec_fw_type_SWITCHTEC_FW_TYPE_MAP, switchtec_fw_type_SWITCHTEC_FW_TYPE_NVLOG,
+    switchtec_fw_type_SWITCHTEC_FW_TYPE_SEEPROM, switchtec_fw_write_fd, CStrExt, MrpcRetry,
+    SwitchtecDevice,
+};
+
+/// Options for [`SwitchtecDevice::firmware_update`]
+///
+/// ```
+/// use switchtec_user_sys::fw::FwUpdateOpts;
+///
+/// let opts = FwUpdateOpts::new()
+///     .dont_activate(true)
+///     .progress(|percent| println!("{percent}%"));
+/// ```
+#[derive(Default)]
+pub struct FwUpdateOpts<'a> {
+    dont_activate: bool,
+    force: bool,
+    retry: Option<MrpcRetry>,
+    progress: Option<Box<dyn FnMut(u32) + 'a>>,
+}
+
+impl<'a> FwUpdateOpts<'a> {
+    /// Create the default options: activate the new image, don't force, and no progress reporting
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the image without activating it, so the currently active partition is still
+    /// booted on the next reset
+    pub fn dont_activate(mut self, dont_activate: bool) -> Self {
+        self.dont_activate = dont_activate;
+        self
+    }
+
+    /// Force the download, even if the firmware would otherwise reject the image (E.g. an
+    /// image for a different generation)
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Restart the download with backoff as described by `retry` if it times out, instead of
+    /// failing on the first timeout
+    ///
+    /// A timeout is either an MRPC command timing out while the image is written, or the
+    /// firmware reporting [`FwDownloadError::Timeout`]. Each retry writes the whole image again
+    /// from the start, so progress restarts from 0%. By default the download isn't retried.
+    pub fn retry(mut self, retry: MrpcRetry) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Report progress of the download as a percentage (0-100)
+    pub fn progress(mut self, progress: impl FnMut(u32) + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+}
+
+impl fmt::Debug for FwUpdateOpts<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FwUpdateOpts")
+            .field("dont_activate", &self.dont_activate)
+            .field("force", &self.force)
+            .field("retry", &self.retry)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// Header details of a firmware image file, read without needing an open device
+///
+/// ```no_run
+/// use switchtec_user_sys::fw::FirmwareImage;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let image = FirmwareImage::from_file("switchtec_fw.pmc")?;
+/// println!("{} image, version {}", image.type_name, image.version);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FirmwareImage {
+    /// Image type
+    pub image_type: FirmwareImageType,
+    /// Human readable image type (E.g. "IMG")
+    pub type_name: String,
+    /// Firmware version of the image
+    pub version: String,
+    /// Length of the image body, in bytes
+    pub length: usize,
+    /// CRC of the image body
+    pub crc: u32,
+}
+
+impl FirmwareImage {
+    /// Parse the header of the firmware image file at `path`
+    ///
+    /// Returns an [`io::ErrorKind::NotFound`] error if the file doesn't exist, or an error
+    /// from the library if the file isn't a valid firmware image
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Firmware.html>
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mut info = MaybeUninit::<switchtec_fw_image_info>::zeroed();
+
+        // SAFETY: `file` is open for the duration of the call, and `info` is only read after
+        // the library reports that it was populated
+        unsafe {
+            let ret = switchtec_fw_file_info(file.as_raw_fd(), info.as_mut_ptr());
+            if ret.is_negative() {
+                return Err(get_switchtec_error());
+            }
+            let info = info.assume_init();
+            Ok(Self {
+                image_type: info.type_.into(),
+                type_name: switchtec_fw_image_type(&info).as_string()?,
+                version: char_buf_to_string(&info.version)?,
+                length: info.image_len,
+                crc: info.image_crc as u32,
+            })
+        }
+    }
+}
+
+/// A firmware version split into its parts, returned by
+/// [`SwitchtecDevice::firmware_version_parts`]
+///
+/// The library formats versions as `"<major>.<minor> B<build>"` (E.g. "4.70 B0A8"), with the
+/// major and minor versions in hex. The build tag is optional, so plain versions like "1.09"
+/// are parsed too.
+///
+/// ```
+/// use switchtec_user_sys::fw::FirmwareVersion;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let version: FirmwareVersion = "4.70 B0A8".parse()?;
+/// assert_eq!((version.major, version.minor), (0x4, 0x70));
+/// assert_eq!(version.build.as_deref(), Some("B0A8"));
+/// assert!(version.at_least(0x3, 0x70));
+/// assert_eq!(version.to_string(), "4.70 B0A8");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FirmwareVersion {
+    /// Major version
+    pub major: u32,
+    /// Minor version
+    pub minor: u32,
+    /// Build tag (E.g. "B0A8"), if the version has one
+    pub build: Option<String>,
+    /// The version string as reported by the library
+    pub raw: String,
+}
+
+impl FirmwareVersion {
+    /// Check if this version is `major.minor` or newer, ignoring the build tag
+    pub fn at_least(&self, major: u32, minor: u32) -> bool {
+        (self.major, self.minor) >= (major, minor)
+    }
+}
+
+impl FromStr for FirmwareVersion {
+    type Err = io::Error;
+
+    fn from_str(version: &str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid firmware version: {version:?}"),
+            )
+        };
+        let mut parts = version.split_whitespace();
+        let (major, minor) = parts
+            .next()
+            .and_then(|number| number.split_once('.'))
+            .ok_or_else(invalid)?;
+        let build = parts.next().map(str::to_owned);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            major: u32::from_str_radix(major, 16).map_err(|_| invalid())?,
+            minor: u32::from_str_radix(minor, 16).map_err(|_| invalid())?,
+            build,
+            raw: version.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+/// Type of a firmware image, from its header
+///
+/// The library maps the generation-specific partition IDs in image headers (E.g.
+/// `SWITCHTEC_FW_PART_ID_G4_IMG0` and `SWITCHTEC_FW_PART_ID_G4_IMG1`) to these types.
+///
+/// ```
+/// use switchtec_user_sys::fw::FirmwareImageType;
+/// use switchtec_user_sys::switchtec_fw_type_SWITCHTEC_FW_TYPE_IMG;
+///
+/// let image_type = FirmwareImageType::from(switchtec_fw_type_SWITCHTEC_FW_TYPE_IMG);
+/// assert_eq!(image_type, FirmwareImageType::Image);
+/// assert_eq!(image_type.to_string(), "IMG");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FirmwareImageType {
+    /// Boot loader
+    Boot,
+    /// Flash map
+    Map,
+    /// Main firmware image
+    Image,
+    /// Configuration
+    Config,
+    /// Non-volatile log
+    NvLog,
+    /// SEEPROM contents
+    Seeprom,
+    /// Key manifest
+    Key,
+    /// BL2 boot loader
+    Bl2,
+    /// `SWITCHTEC_FW_TYPE_UNKNOWN`, or a raw type this crate doesn't know about
+    Unknown(switchtec_fw_type),
+}
+
+impl From<switchtec_fw_type> for FirmwareImageType {
+    fn from(image_type: switchtec_fw_type) -> Self {
+        match image_type {
+            switchtec_fw_type_SWITCHTEC_FW_TYPE_BOOT => Self::Boot,
+            switchtec_fw_type_SWITCHTEC_FW_TYPE_MAP => Self::Map,
+            switchtec_fw_type_SWITCHTEC_FW_TYPE_IMG => Self::Image,
+            switchtec_fw_type_SWITCHTEC_FW_TYPE_CFG => Self::Config,
+            switchtec_fw_type_SWITCHTEC_FW_TYPE_NVLOG => Self::NvLog,
+            switchtec_fw_type_SWITCHTEC_FW_TYPE_SEEPROM => Self::Seeprom,
+            switchtec_fw_type_SWITCHTEC_FW_TYPE_KEY => Self::Key,
+            switchtec_fw_type_SWITCHTEC_FW_TYPE_BL2 => Self::Bl2,
+            image_type => Self::Unknown(image_type),
+        }
+    }
+}
+
+impl From<FirmwareImageType> for switchtec_fw_type {
+    fn from(image_type: FirmwareImageType) -> Self {
+        match image_type {
+            FirmwareImageType::Boot => switchtec_fw_type_SWITCHTEC_FW_TYPE_BOOT,
+            FirmwareImageType::Map => switchtec_fw_type_SWITCHTEC_FW_TYPE_MAP,
+            FirmwareImageType::Image => switchtec_fw_type_SWITCHTEC_FW_TYPE_IMG,
+            FirmwareImageType::Config => switchtec_fw_type_SWITCHTEC_FW_TYPE_CFG,
+            FirmwareImageType::NvLog => switchtec_fw_type_SWITCHTEC_FW_TYPE_NVLOG,
+            FirmwareImageType::Seeprom => switchtec_fw_type_SWITCHTEC_FW_TYPE_SEEPROM,
+            FirmwareImageType::Key => switchtec_fw_type_SWITCHTEC_FW_TYPE_KEY,
+            FirmwareImageType::Bl2 => switchtec_fw_type_SWITCHTEC_FW_TYPE_BL2,
+            FirmwareImageType::Unknown(image_type) => image_type,
+        }
+    }
+}
+
+impl fmt::Display for FirmwareImageType {
+    /// Display the type the way `switchtec_fw_image_type` names it (E.g. "IMG")
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Boot => write!(f, "BOOT"),
+            Self::Map => write!(f, "MAP"),
+            Self::Image => write!(f, "IMG"),
+            Self::Config => write!(f, "CFG"),
+            Self::NvLog => write!(f, "NVLOG"),
+            Self::Seeprom => write!(f, "SEEPROM"),
+            Self::Key => write!(f, "KEY"),
+            Self::Bl2 => write!(f, "BL2"),
+            Self::Unknown(_) => write!(f, "UNKNOWN"),
+        }
+    }
+}
+
+/// Which copy of a redundant firmware partition to read
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FwSlot {
+    /// The partition that is (or will be, after a toggle) booted
+    Active,
+    /// The standby partition
+    Inactive,
+}
+
+/// A firmware flash partition that holds a versioned image
+///
+/// ```
+/// use switchtec_user_sys::fw::{FwPartition, FwSlot};
+///
+/// let standby_image = FwPartition::Image(FwSlot::Inactive);
+/// assert_eq!(standby_image.to_string(), "IMG (inactive)");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FwPartition {
+    /// Boot loader partition
+    Boot(FwSlot),
+    /// Flash map partition
+    Map(FwSlot),
+    /// Main firmware image partition
+    Image(FwSlot),
+    /// Configuration partition
+    Config(FwSlot),
+    /// Key manifest partition
+    Key(FwSlot),
+    /// BL2 boot loader partition
+    Bl2(FwSlot),
+}
+
+impl FwPartition {
+    /// Every partition with a versioned image, in the order `switchtec fw-info` lists them
+    const ALL: [fn(FwSlot) -> Self; 6] = [
+        Self::Boot,
+        Self::Map,
+        Self::Key,
+        Self::Bl2,
+        Self::Image,
+        Self::Config,
+    ];
+
+    fn slot(&self) -> FwSlot {
+        match *self {
+            Self::Boot(slot)
+            | Self::Map(slot)
+            | Self::Image(slot)
+            | Self::Config(slot)
+            | Self::Key(slot)
+            | Self::Bl2(slot) => slot,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Boot(_) => "BOOT",
+            Self::Map(_) => "MAP",
+            Self::Image(_) => "IMG",
+            Self::Config(_) => "CFG",
+            Self::Key(_) => "KEY",
+            Self::Bl2(_) => "BL2",
+        }
+    }
+
+    /// Select the matching partition type from a `switchtec_fw_part_summary`
+    fn part_type<'a>(
+        &self,
+        summary: &'a switchtec_fw_part_summary,
+    ) -> &'a switchtec_fw_part_summary_switchtec_fw_part_type {
+        match self {
+            Self::Boot(_) => &summary.boot,
+            Self::Map(_) => &summary.map,
+            Self::Image(_) => &summary.img,
+            Self::Config(_) => &summary.cfg,
+            Self::Key(_) => &summary.key,
+            Self::Bl2(_) => &summary.bl2,
+        }
+    }
+
+    /// Select the matching image info from a `switchtec_fw_part_summary`, which is null if the
+    /// device doesn't have this partition
+    fn image_info(&self, summary: &switchtec_fw_part_summary) -> *mut switchtec_fw_image_info {
+        let part_type = self.part_type(summary);
+        match self.slot() {
+            FwSlot::Active => part_type.active,
+            FwSlot::Inactive => part_type.inactive,
+        }
+    }
+}
+
+/// A firmware partition in the device's flash, returned by [`SwitchtecDevice::flash_partitions`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FlashPartition {
+    /// Which partition this is
+    pub partition: FwPartition,
+    /// Firmware version of the image in the partition
+    pub version: String,
+    /// Flash address of the partition
+    pub address: usize,
+    /// Length of the partition, in bytes
+    pub length: usize,
+    /// Length of the image stored in the partition, in bytes
+    pub image_length: usize,
+    /// Whether the partition holds a valid image
+    pub valid: bool,
+    /// Whether the partition is active (booted on the next reset)
+    pub active: bool,
+    /// Whether the image in the partition is currently running
+    pub running: bool,
+    /// Whether the partition is read-only
+    pub read_only: bool,
+}
+
+impl FlashPartition {
+    fn new(partition: FwPartition, info: &switchtec_fw_image_info) -> io::Result<Self> {
+        Ok(Self {
+            partition,
+            version: char_buf_to_string(&info.version)?,
+            address: info.part_addr,
+            length: info.part_len,
+            image_length: info.image_len,
+            valid: info.valid,
+            active: info.active,
+            running: info.running,
+            read_only: info.read_only,
+        })
+    }
+}
+
+impl fmt::Display for FwPartition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let slot = match self.slot() {
+            FwSlot::Active => "active",
+            FwSlot::Inactive => "inactive",
+        };
+        write!(f, "{} ({slot})", self.name())
+    }
+}
+
+/// An error reported by the firmware for a download, see [`FwDownloadStatus`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FwDownloadError {
+    /// The image header is incorrect
+    HeaderIncorrect,
+    /// A chunk was written at the wrong offset
+    OffsetIncorrect,
+    /// The image CRC is incorrect
+    CrcIncorrect,
+    /// The image length is incorrect
+    LengthIncorrect,
+    /// A hardware error occurred while writing the flash
+    HardwareError,
+    /// The image is shorter than the 32 byte minimum
+    PackageTooSmall,
+    /// Memory for the image signature couldn't be allocated
+    SignatureMemoryAlloc,
+    /// Writing the SEEPROM failed
+    Seeprom,
+    /// The target partition is read-only
+    ReadOnlyPartition,
+    /// The download timed out
+    Timeout,
+    /// The SEEPROM or its TWI bus isn't enabled
+    SeepromTwiNotEnabled,
+    /// The target partition is the one currently running
+    PartitionRunning,
+    /// Downloads aren't allowed over this interface
+    NotAllowed,
+    /// Activating the image failed because of an XML version mismatch
+    XmlMismatch,
+    /// Activating the image failed for an unknown reason
+    ActivationFailed,
+    /// No image was transferred
+    NoImage,
+    /// A raw download status this crate doesn't know about
+    Other(u32),
+}
+
+impl From<switchtec_fw_dlstatus> for FwDownloadError {
+    fn from(status: switchtec_fw_dlstatus) -> Self {
+        match status {
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_HEADER_INCORRECT => Self::HeaderIncorrect,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_OFFSET_INCORRECT => Self::OffsetIncorrect,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_CRC_INCORRECT => Self::CrcIncorrect,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_LENGTH_INCORRECT => Self::LengthIncorrect,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_HARDWARE_ERR => Self::HardwareError,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_PACKAGE_TOO_SMALL => Self::PackageTooSmall,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SIG_MEM_ALLOC => Self::SignatureMemoryAlloc,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SEEPROM => Self::Seeprom,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_READONLY_PARTITION => Self::ReadOnlyPartition,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_DOWNLOAD_TIMEOUT => Self::Timeout,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SEEPROM_TWI_NOT_ENABLED => {
+                Self::SeepromTwiNotEnabled
+            }
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_PROGRAM_RUNNING => Self::PartitionRunning,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_NOT_ALLOWED => Self::NotAllowed,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_XML_MISMATCH_ACT => Self::XmlMismatch,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_UNKNOWN_ACT => Self::ActivationFailed,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_NO_FILE => Self::NoImage,
+            status => Self::Other(status),
+        }
+    }
+}
+
+impl fmt::Display for FwDownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HeaderIncorrect => write!(f, "header incorrect"),
+            Self::OffsetIncorrect => write!(f, "offset incorrect"),
+            Self::CrcIncorrect => write!(f, "CRC incorrect"),
+            Self::LengthIncorrect => write!(f, "length incorrect"),
+            Self::HardwareError => write!(f, "hardware error"),
+            Self::PackageTooSmall => write!(f, "package length less than 32 bytes"),
+            Self::SignatureMemoryAlloc => write!(f, "signature memory allocation failed"),
+            Self::Seeprom => write!(f, "SEEPROM download failed"),
+            Self::ReadOnlyPartition => write!(f, "programming a read-only partition"),
+            Self::Timeout => write!(f, "download timeout"),
+            Self::SeepromTwiNotEnabled => write!(f, "SEEPROM or related TWI bus isn't enabled"),
+            Self::PartitionRunning => write!(f, "programming a running partition"),
+            Self::NotAllowed => write!(f, "programming not allowed over this interface"),
+            Self::XmlMismatch => write!(f, "activation failed due to XML version mismatch"),
+            Self::ActivationFailed => write!(f, "activation failed due to unknown error"),
+            Self::NoImage => write!(f, "no image transferred"),
+            Self::Other(status) => write!(f, "unknown download status {status:#x}"),
+        }
+    }
+}
+
+impl std::error::Error for FwDownloadError {}
+
+/// Status of the most recent firmware download, returned by
+/// [`SwitchtecDevice::firmware_download_status`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FwDownloadStatus {
+    /// No download is running
+    Idle,
+    /// A download is running
+    ///
+    /// The firmware only reports the phase of a download, so the byte counts are only known
+    /// for downloads started with [`SwitchtecDevice::firmware_update`] in this process. Both
+    /// are `0` for any other download (E.g. one started by the `switchtec` CLI).
+    InProgress {
+        /// Number of bytes of the image written so far
+        bytes_written: usize,
+        /// Total number of bytes in the image
+        total: usize,
+    },
+    /// The last download completed successfully
+    Complete,
+    /// The last download failed
+    Error(FwDownloadError),
+}
+
+impl FwDownloadStatus {
+    /// Build the status from the download status and MRPC background status reported by
+    /// `switchtec_fw_dlstatus`, and the `(bytes_written, total)` progress of the download
+    fn new(
+        status: switchtec_fw_dlstatus,
+        bg_status: mrpc_bg_status,
+        (bytes_written, total): (usize, usize),
+    ) -> Self {
+        let in_progress = Self::InProgress {
+            bytes_written,
+            total,
+        };
+        let succeeded = matches!(
+            status,
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_READY
+                | switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_COMPLETES
+                | switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SUCCESS_FIRM_ACT
+                | switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SUCCESS_DATA_ACT
+        );
+        match bg_status {
+            _ if status == switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_INPROGRESS => in_progress,
+            mrpc_bg_status_MRPC_BG_STAT_INPROGRESS => in_progress,
+            mrpc_bg_status_MRPC_BG_STAT_DONE | mrpc_bg_status_MRPC_BG_STAT_ERROR if succeeded => {
+                Self::Complete
+            }
+            mrpc_bg_status_MRPC_BG_STAT_DONE | mrpc_bg_status_MRPC_BG_STAT_ERROR => {
+                Self::Error(status.into())
+            }
+            _ => Self::Idle,
+        }
+    }
+}
+
+impl SwitchtecDevice {
+    /// Write the firmware image file at `image` to the device's inactive partition
+    ///
+    /// A firmware update is a sequence of:
+    /// 1. Write: the image is downloaded to the inactive partition for its type, and the
+    ///    library waits for the firmware to report that the download completed
+    /// 2. Toggle: unless [`FwUpdateOpts::dont_activate`] is set, the written partition is
+    ///    made active. Otherwise it can be done later with [`SwitchtecDevice::firmware_activate`]
+    /// 3. Reset: the new image only runs after the device is reset (E.g. with
+    ///    `switchtec_hard_reset`) or power-cycled
+    ///
+    /// The boot partition can't be written while it's read-only, which returns an
+    /// [`io::ErrorKind::PermissionDenied`] error. A download that times out fails right away,
+    /// unless [`FwUpdateOpts::retry`] is set.
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use switchtec_user_sys::{fw::FwUpdateOpts, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let opts = FwUpdateOpts::new().progress(|percent| println!("Writing: {percent}%"));
+    /// device.firmware_update(Path::new("switchtec_fw.pmc"), opts)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Firmware.html>
+    pub fn firmware_update(&self, image: &Path, opts: FwUpdateOpts) -> io::Result<()> {
+        let image_info = FirmwareImage::from_file(image)?;
+        if image_info.image_type == FirmwareImageType::Boot {
+            // SAFETY: We know that device holds a valid/open switchtec device
+            let boot_ro = unsafe { traced!(switchtec_fw_is_boot_ro(self.inner)) };
+            if boot_ro.is_negative() {
+                return Err(get_switchtec_error());
+            }
+            if boot_ro as u32 == switchtec_fw_ro_SWITCHTEC_FW_RO {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "boot partition is read-only",
+                ));
+            }
+        }
+
+        let FwUpdateOpts {
+            dont_activate,
+            force,
+            retry,
+            progress,
+        } = opts;
+        let mut progress = progress.unwrap_or_else(|| Box::new(|_| {}));
+
+        let retry = retry.unwrap_or(MrpcRetry {
+            attempts: 1,
+            ..Default::default()
+        });
+        let mut backoffs = retry.backoffs();
+        let download = DownloadTracker::start(&self.transport);
+        let mut progress = |written, total| {
+            download.update(written, total);
+            progress(percent(written, total));
+        };
+        loop {
+            match self.write_image(image, dont_activate, force, &mut progress) {
+                Err(e) if is_download_timeout(&e) => match backoffs.next() {
+                    Some(backoff) => thread::sleep(backoff),
+                    None => return Err(e),
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Download the image file at `image` once, with `switchtec_fw_write_fd`
+    fn write_image(
+        &self,
+        image: &Path,
+        dont_activate: bool,
+        force: bool,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> io::Result<()> {
+        // Opened for each attempt, since the library reads the image from the current offset
+        let file = File::open(image)?;
+        let ret = with_byte_progress(progress, |callback| {
+            // SAFETY: We know that device holds a valid/open switchtec device, and `file` is
+            // open for the duration of the call
+            unsafe {
+                traced!(switchtec_fw_write_fd(
+                    self.inner,
+                    file.as_raw_fd(),
+                    dont_activate as i32,
+                    force as i32,
+                    callback,
+                ))
+            }
+        });
+        match ret {
+            0 => Ok(()),
+            ret if ret.is_negative() => Err(get_switchtec_error()),
+            // Positive value is the download status reported by the firmware
+            status => Err(io::Error::new(
+                io::ErrorKind::Other,
+                FwDownloadError::from(status as switchtec_fw_dlstatus),
+            )),
+        }
+    }
+
+    /// Get the status of the most recent firmware download
+    ///
+    /// This can be polled from another handle (or process) while
+    /// [`SwitchtecDevice::firmware_update`] is writing an image. The firmware doesn't report
+    /// how many bytes have been written, so [`FwDownloadStatus::InProgress`] only has byte
+    /// counts when the update is running in this process (on any handle to the same device).
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::fw::FwDownloadStatus;
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// match device.firmware_download_status()? {
+    ///     FwDownloadStatus::Error(e) => println!("Last download failed: {e}"),
+    ///     status => println!("Download status: {status:?}"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn firmware_download_status(&self) -> io::Result<FwDownloadStatus> {
+        let mut status: switchtec_fw_dlstatus = 0;
+        let mut bg_status: mrpc_bg_status = 0;
+        // SAFETY: We know that device holds a valid/open switchtec device, and the out-values
+        // are only read after the library reports success
+        let ret = unsafe {
+            traced!(switchtec_fw_dlstatus(
+                self.inner,
+                &mut status,
+                &mut bg_status
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(FwDownloadStatus::new(
+            status,
+            bg_status,
+            DownloadTracker::progress(&self.transport),
+        ))
+    }
+
+    /// Toggle the active image and/or configuration partition, so the standby partition is
+    /// booted instead
+    ///
+    /// This is the activation step of [`SwitchtecDevice::firmware_update`] on its own, for
+    /// staged rollouts: write the image with [`FwUpdateOpts::dont_activate`] set, then call
+    /// this (E.g. during a maintenance window) to switch to it. `img` toggles the firmware
+    /// image partitions and `cfg` the configuration partitions; passing `false` for both
+    /// returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error.
+    ///
+    /// The toggle only takes effect after the device is reset (E.g. with
+    /// `switchtec_hard_reset`) or power-cycled. Check the standby image with
+    /// [`SwitchtecDevice::partition_fw_version`] before toggling to it.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// // Boot the standby firmware image (but keep the current config) after the next reset
+    /// device.firmware_activate(true, false)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Firmware.html>
+    pub fn firmware_activate(&self, img: bool, cfg: bool) -> io::Result<()> {
+        if !img && !cfg {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "nothing to activate, neither img nor cfg is set",
+            ));
+        }
+        // SAFETY: We know that device holds a valid/open switchtec device
+        let ret = unsafe {
+            traced!(switchtec_fw_toggle_active_partition(
+                self.inner, 0, 0, img as i32, cfg as i32, 0
+            ))
+        };
+        if ret < 0 {
+            return Err(self.perror("firmware activate"));
+        }
+        Ok(())
+    }
+
+    /// Get the running firmware version, split into its major and minor versions and build tag
+    ///
+    /// [`SwitchtecDevice::firmware_version`] returns the same version as a string. Returns an
+    /// [`InvalidData`](io::ErrorKind::InvalidData) error if the version can't be parsed.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let version = device.firmware_version_parts()?;
+    /// if !version.at_least(0x4, 0x40) {
+    ///     println!("Firmware {version} is too old, please update");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn firmware_version_parts(&self) -> io::Result<FirmwareVersion> {
+        self.firmware_version()?.parse()
+    }
+
+    /// Get the firmware version stored in the given flash `partition`
+    ///
+    /// Unlike [`SwitchtecDevice::firmware_version`], which reports the running firmware, this
+    /// reads the version from the partition's header, so it can be used to check the
+    /// standby image before toggling to it.
+    ///
+    /// Returns an [`io::ErrorKind::NotFound`] error if the device doesn't have the requested partition
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Firmware.html>
+    pub fn partition_fw_version(&self, partition: FwPartition) -> io::Result<String> {
+        // SAFETY: We know that device holds a valid/open switchtec device, and we're checking
+        // that the returned summary is not null before reading it
+        unsafe {
+            let summary = traced!(switchtec_fw_part_summary(self.inner));
+            if summary.is_null() {
+                return Err(get_switchtec_error());
+            }
+            let info = partition.image_info(&*summary);
+            let version = if info.is_null() {
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no {partition} partition found"),
+                ))
+            } else {
+                char_buf_to_string(&(*info).version)
+            };
+
+            // Must be called after switchtec_fw_part_summary to free the summary and its image infos
+            switchtec_fw_part_summary_free(summary);
+
+            version
+        }
+    }
+
+    /// List the firmware partitions in the device's flash
+    ///
+    /// Both the active and inactive copy of each partition type are listed, skipping any
+    /// the device doesn't have.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for part in device.flash_partitions()? {
+    ///     let running = if part.running { " (running)" } else { "" };
+    ///     println!(
+    ///         "{}: {} @ {:#x} ({} bytes){running}",
+    ///         part.partition, part.version, part.address, part.length
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Firmware.html>
+    pub fn flash_partitions(&self) -> io::Result<Vec<FlashPartition>> {
+        // SAFETY: We know that device holds a valid/open switchtec device, and we're checking
+        // that the returned summary and each image info are not null before reading them
+        unsafe {
+            let summary = traced!(switchtec_fw_part_summary(self.inner));
+            if summary.is_null() {
+                return Err(get_switchtec_error());
+            }
+            let partitions = FwPartition::ALL
+                .iter()
+                .flat_map(|partition| [partition(FwSlot::Active), partition(FwSlot::Inactive)])
+                .filter_map(|partition| {
+                    let info = partition.image_info(&*summary).as_ref()?;
+                    Some(FlashPartition::new(partition, info))
+                })
+                .collect();
+
+            // Must be called after switchtec_fw_part_summary to free the summary and its image infos
+            switchtec_fw_part_summary_free(summary);
+
+            partitions
+        }
+    }
+
+    /// Read the firmware image in a flash `partition` (from
+    /// [`SwitchtecDevice::flash_partitions`]) into a new image file at `dest`
+    ///
+    /// The image is streamed from flash to the file, with `progress` called with the
+    /// percentage read so far. The file is written in the same format as the images accepted
+    /// by [`SwitchtecDevice::firmware_update`].
+    ///
+    /// Reading doesn't modify flash, so read-only partitions and the partition that's currently
+    /// running can be read. Returns an [`io::ErrorKind::InvalidData`] error if the partition
+    /// doesn't hold a valid image.
+    ///
+    /// The image is read into a temporary file next to `dest` (`dest` with `.part` appended),
+    /// which only replaces `dest` once the whole image has been read. An existing file at
+    /// `dest` (E.g. an older backup) is left untouched if anything fails.
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use switchtec_user_sys::fw::{FwPartition, FwSlot};
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let partitions = device.flash_partitions()?;
+    /// if let Some(image) = partitions
+    ///     .iter()
+    ///     .find(|p| p.partition == FwPartition::Image(FwSlot::Active))
+    /// {
+    ///     device.firmware_read(image, Path::new("backup.pmc"), |percent| {
+    ///         println!("Reading: {percent}%")
+    ///     })?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Firmware.html>
+    pub fn firmware_read(
+        &self,
+        partition: &FlashPartition,
+        dest: &Path,
+        mut progress: impl FnMut(u32),
+    ) -> io::Result<()> {
+        // SAFETY: We know that device holds a valid/open switchtec device, and we're checking
+        // that the returned summary is not null before reading it. The summary (and the image
+        // header metadata it points to) is only freed after the image has been read.
+        unsafe {
+            let summary = traced!(switchtec_fw_part_summary(self.inner));
+            if summary.is_null() {
+                return Err(get_switchtec_error());
+            }
+            let info = partition.partition.image_info(&*summary);
+            let ret = if info.is_null() {
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no {} partition found", partition.partition),
+                ))
+            } else if !(*info).valid {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} partition doesn't hold a valid image",
+                        partition.partition
+                    ),
+                ))
+            } else {
+                // Read into a temporary file that only replaces `dest` once it's complete
+                create_part_file(dest).and_then(|(part, file)| {
+                    let ret = self
+                        .read_image(info, &file, &mut progress)
+                        .and_then(|()| fs::rename(&part, dest));
+                    if ret.is_err() {
+                        let _ = fs::remove_file(&part);
+                    }
+                    ret
+                })
+            };
+
+            // Must be called after switchtec_fw_part_summary to free the summary and its image infos
+            switchtec_fw_part_summary_free(summary);
+
+            ret
+        }
+    }
+
+    /// Check that the image in a flash `partition` is intact, by reading its body back and
+    /// comparing its CRC with the one in its header
+    ///
+    /// Use this after writing an image with [`FwUpdateOpts::dont_activate`] set, to make sure
+    /// a corrupted write isn't activated with [`SwitchtecDevice::firmware_activate`]. Returns
+    /// `false` if the CRC doesn't match or the partition doesn't hold a valid image header,
+    /// and an [`io::ErrorKind::NotFound`] error if the device doesn't have the partition.
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use switchtec_user_sys::fw::{FwPartition, FwSlot, FwUpdateOpts};
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let opts = FwUpdateOpts::new().dont_activate(true);
+    /// device.firmware_update(Path::new("switchtec_fw.pmc"), opts)?;
+    /// if device.firmware_verify(FwPartition::Image(FwSlot::Inactive))? {
+    ///     device.firmware_activate(true, false)?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn firmware_verify(&self, partition: FwPartition) -> io::Result<bool> {
+        // SAFETY: We know that device holds a valid/open switchtec device, and we're checking
+        // that the returned summary is not null before reading it. The image info is copied
+        // out before the summary is freed.
+        let info = unsafe {
+            let summary = traced!(switchtec_fw_part_summary(self.inner));
+            if summary.is_null() {
+                return Err(get_switchtec_error());
+            }
+            let info = partition.image_info(&*summary).as_ref().copied();
+
+            // Must be called after switchtec_fw_part_summary to free the summary and its image infos
+            switchtec_fw_part_summary_free(summary);
+
+            info.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no {partition} partition found"),
+                )
+            })?
+        };
+        if !info.valid {
+            return Ok(false);
+        }
+
+        let mut body = vec![0u8; info.image_len];
+        let addr = info.part_addr + info.part_body_offset;
+        // SAFETY: We know that device holds a valid/open switchtec device, and `body` holds
+        // the `image_len` bytes we ask the library to read into it
+        let ret = unsafe {
+            traced!(switchtec_fw_read(
+                self.inner,
+                addr as _,
+                body.len(),
+                body.as_mut_ptr() as *mut _,
+            ))
+        };
+        if ret < 0 {
+            return Err(self.perror(&format!("read {partition} image")));
+        }
+        Ok(switchtec_crc(&body) == info.image_crc as u32)
+    }
+
+    /// Save the active configuration (CFG) partition to a new image file at `dest`
+    ///
+    /// The file can be written back with [`SwitchtecDevice::config_restore`]
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// device.config_backup(Path::new("config.pmc"))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn config_backup(&self, dest: &Path) -> io::Result<()> {
+        let config = FwPartition::Config(FwSlot::Active);
+        let partition = self
+            .flash_partitions()?
+            .into_iter()
+            .find(|p| p.partition == config)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no {config} partition found"),
+                )
+            })?;
+        self.firmware_read(&partition, dest, |_| {})
+    }
+
+    /// Write a configuration image file saved by [`SwitchtecDevice::config_backup`] back to
+    /// the device
+    ///
+    /// The image is checked before anything is written to flash: an image that isn't a
+    /// configuration image returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error, and
+    /// one whose body doesn't match the CRC in its header returns an
+    /// [`InvalidData`](io::ErrorKind::InvalidData) error. The image is then written to the
+    /// standby configuration partition with [`SwitchtecDevice::firmware_update`].
+    ///
+    /// The restored configuration is only made active (so it's used after the next reset)
+    /// when `activate` is `true`. Otherwise the current configuration stays active, and the
+    /// restored one can be activated later with [`SwitchtecDevice::firmware_activate`].
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// // Write the saved config to the standby partition, then boot it after the next reset
+    /// device.config_restore(Path::new("config.pmc"), false)?;
+    /// device.firmware_activate(false, true)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn config_restore(&self, src: &Path, activate: bool) -> io::Result<()> {
+        let image = FirmwareImage::from_file(src)?;
+        if image.image_type != FirmwareImageType::Config {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is a {} image, not CFG", src.display(), image.type_name),
+            ));
+        }
+        check_image_crc(&image, &std::fs::read(src)?)?;
+        self.firmware_update(src, FwUpdateOpts::new().dont_activate(!activate))
+    }
+
+    /// Write the header and body of the image described by `info` to `file`
+    ///
+    /// `info` must point to a valid image info from a `switchtec_fw_part_summary` that hasn't
+    /// been freed
+    unsafe fn read_image(
+        &self,
+        info: *mut switchtec_fw_image_info,
+        file: &File,
+        progress: &mut dyn FnMut(u32),
+    ) -> io::Result<()> {
+        if switchtec_fw_img_write_hdr(file.as_raw_fd(), info).is_negative() {
+            return Err(get_switchtec_error());
+        }
+        let ret = with_progress(progress, |callback| {
+            traced!(switchtec_fw_body_read_fd(
+                self.inner,
+                file.as_raw_fd(),
+                info,
+                callback
+            ))
+        });
+        if ret.is_negative() {
+            return Err(get_switchtec_error());
+        }
+        file.sync_all()
+    }
+}
+
+/// The downloads running through [`SwitchtecDevice::firmware_update`] in this process
+static DOWNLOADS: Mutex<Vec<Download>> = Mutex::new(Vec::new());
+
+/// A download registered in [`DOWNLOADS`]
+struct Download {
+    id: u64,
+    /// The device it's writing to
+    transport: Transport,
+    /// `(bytes_written, total)`
+    progress: (usize, usize),
+}
+
+/// ID of the next download registered in [`DOWNLOADS`]
+static NEXT_DOWNLOAD_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A download registered in [`DOWNLOADS`] for the duration of a
+/// [`SwitchtecDevice::firmware_update`], so [`SwitchtecDevice::firmware_download_status`] can
+/// report its progress from another handle
+///
+/// Each download gets its own ID, so concurrent updates to the same device (E.g. from two
+/// handles) only ever update and remove their own entry.
+struct DownloadTracker {
+    id: u64,
+}
+
+impl DownloadTracker {
+    fn start(transport: &Transport) -> Self {
+        let id = NEXT_DOWNLOAD_ID.fetch_add(1, Ordering::Relaxed);
+        Self::downloads().push(Download {
+            id,
+            transport: transport.clone(),
+            progress: (0, 0),
+        });
+        Self { id }
+    }
+
+    fn update(&self, bytes_written: usize, total: usize) {
+        let mut downloads = Self::downloads();
+        if let Some(download) = downloads.iter_mut().find(|d| d.id == self.id) {
+            download.progress = (bytes_written, total);
+        }
+    }
+
+    /// Get the progress of the download to the device opened with `transport`, or `(0, 0)`
+    /// if there isn't one running in this process
+    ///
+    /// If there are several, the most recently started one is reported.
+    fn progress(transport: &Transport) -> (usize, usize) {
+        Self::downloads()
+            .iter()
+            .rev()
+            .find(|d| d.transport == *transport)
+            .map_or((0, 0), |d| d.progress)
+    }
+
+    fn downloads() -> MutexGuard<'static, Vec<Download>> {
+        // The list is only ever pushed to or removed from, so it's intact even if a holder
+        // of the lock panicked
+        DOWNLOADS.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for DownloadTracker {
+    fn drop(&mut self) {
+        Self::downloads().retain(|d| d.id != self.id);
+    }
+}
+
+/// Check if a firmware download failed by timing out, either submitting an MRPC command or as
+/// reported by the firmware
+fn is_download_timeout(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::TimedOut
+        || err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<FwDownloadError>())
+            .is_some_and(|e| *e == FwDownloadError::Timeout)
+}
+
+/// Create the temporary file an image is read into before it replaces `dest`, next to `dest`
+/// so it can be renamed over it
+///
+/// Like [`fwlog`](crate::fwlog)'s temporary files, it gets a random name (`<dest>.<random>.part`)
+/// that only its owner can open, and a name that's already taken is skipped for a new one.
+fn create_part_file(dest: &Path) -> io::Result<(PathBuf, File)> {
+    for _ in 0..TEMP_FILE_ATTEMPTS {
+        let mut part = dest.as_os_str().to_owned();
+        part.push(format!(".{:016x}.part", random_u64()));
+        let part = PathBuf::from(part);
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&part);
+        match file {
+            Ok(file) => return Ok((part, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!(
+            "unable to create a temporary file with a unique name for {}",
+            dest.display()
+        ),
+    ))
+}
+
+/// Check that the body of an image file (its last `image.length` bytes) matches the CRC in
+/// the image's header
+fn check_image_crc(image: &FirmwareImage, file: &[u8]) -> io::Result<()> {
+    let body = file
+        .len()
+        .checked_sub(image.length)
+        .map(|offset| &file[offset..])
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "image is {} bytes, shorter than its {} byte body",
+                    file.len(),
+                    image.length
+                ),
+            )
+        })?;
+    let crc = switchtec_crc(body);
+    if crc != image.crc {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "image CRC {crc:#010x} doesn't match its header ({:#010x})",
+                image.crc
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_create_part_file() {
+    use std::env;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dest = env::temp_dir().join(format!("switchtec-fw-{:016x}.pmc", random_u64()));
+    let (part, file) = create_part_file(&dest).unwrap();
+    let (other, _) = create_part_file(&dest).unwrap();
+    let name = part.file_name().unwrap().to_str().unwrap();
+    let dest_name = dest.file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with(dest_name) && name.ends_with(".part"));
+    assert_eq!(part.parent(), dest.parent());
+    assert_ne!(part, other);
+    assert_eq!(file.metadata().unwrap().permissions().mode() & 0o777, 0o600);
+    fs::remove_file(&part).unwrap();
+    fs::remove_file(&other).unwrap();
+}
+
+#[test]
+fn test_firmware_image_type_round_trip() {
+    use crate::switchtec_fw_type_SWITCHTEC_FW_TYPE_UNKNOWN;
+
+    for raw in [
+        switchtec_fw_type_SWITCHTEC_FW_TYPE_UNKNOWN,
+        switchtec_fw_type_SWITCHTEC_FW_TYPE_BOOT,
+        switchtec_fw_type_SWITCHTEC_FW_TYPE_MAP,
+        switchtec_fw_type_SWITCHTEC_FW_TYPE_IMG,
+        switchtec_fw_type_SWITCHTEC_FW_TYPE_CFG,
+        switchtec_fw_type_SWITCHTEC_FW_TYPE_NVLOG,
+        switchtec_fw_type_SWITCHTEC_FW_TYPE_SEEPROM,
+        switchtec_fw_type_SWITCHTEC_FW_TYPE_KEY,
+        switchtec_fw_type_SWITCHTEC_FW_TYPE_BL2,
+        0x42,
+    ] {
+        assert_eq!(switchtec_fw_type::from(FirmwareImageType::from(raw)), raw);
+    }
+    assert_eq!(
+        FirmwareImageType::from(switchtec_fw_type_SWITCHTEC_FW_TYPE_UNKNOWN).to_string(),
+        "UNKNOWN"
+    );
+    assert_eq!(FirmwareImageType::Config.to_string(), "CFG");
+}
+
+#[test]
+fn test_firmware_version() {
+    let version: FirmwareVersion = "3.70 B04F".parse().unwrap();
+    assert_eq!(
+        version,
+        FirmwareVersion {
+            major: 0x3,
+            minor: 0x70,
+            build: Some("B04F".to_owned()),
+            raw: "3.70 B04F".to_owned(),
+        }
+    );
+    assert!(version.at_least(0x3, 0x70));
+    assert!(version.at_least(0x2, 0x99));
+    assert!(!version.at_least(0x3, 0x71));
+    assert!(!version.at_least(0x4, 0x00));
+
+    let version: FirmwareVersion = "4.0a B10C".parse().unwrap();
+    assert_eq!((version.major, version.minor), (0x4, 0x0a));
+
+    // Versions without a build tag
+    let version: FirmwareVersion = "1.09".parse().unwrap();
+    assert_eq!(
+        (version.major, version.minor, version.build.as_deref()),
+        (0x1, 0x09, None)
+    );
+    assert_eq!(version.to_string(), "1.09");
+
+    for invalid in ["", "B04F", "3 B04F", "3.7x B04F", "3.70 B04F extra"] {
+        let err = invalid.parse::<FirmwareVersion>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{invalid:?}");
+    }
+}
+
+#[test]
+fn test_firmware_image_from_file() {
+    let err = FirmwareImage::from_file("does/not/exist.pmc").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    // Any file without a firmware image header should be rejected by the library
+    assert!(FirmwareImage::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).is_err());
+}
+
+#[test]
+fn test_firmware_download_status() {
+    use crate::mrpc::mrpc_bg_status_MRPC_BG_STAT_IDLE;
+
+    let status = |status, bg_status| FwDownloadStatus::new(status, bg_status, (1024, 4096));
+    assert_eq!(
+        status(
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_READY,
+            mrpc_bg_status_MRPC_BG_STAT_IDLE
+        ),
+        FwDownloadStatus::Idle
+    );
+    assert_eq!(
+        status(
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_INPROGRESS,
+            mrpc_bg_status_MRPC_BG_STAT_IDLE
+        ),
+        FwDownloadStatus::InProgress {
+            bytes_written: 1024,
+            total: 4096
+        }
+    );
+    assert_eq!(
+        status(
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_SUCCESS_FIRM_ACT,
+            mrpc_bg_status_MRPC_BG_STAT_DONE
+        ),
+        FwDownloadStatus::Complete
+    );
+    assert_eq!(
+        status(
+            switchtec_fw_dlstatus_SWITCHTEC_DLSTAT_CRC_INCORRECT,
+            mrpc_bg_status_MRPC_BG_STAT_ERROR
+        ),
+        FwDownloadStatus::Error(FwDownloadError::CrcIncorrect)
+    );
+    assert_eq!(
+        FwDownloadError::from(0x1234).to_string(),
+        "unknown download status 0x1234"
+    );
+}
+
+#[test]
+fn test_check_image_crc() {
+    let mut file = b"header--123456789".to_vec();
+    let image = FirmwareImage {
+        image_type: FirmwareImageType::Config,
+        type_name: "CFG".to_owned(),
+        version: "4.70 B0A8".to_owned(),
+        length: 9,
+        crc: 0xfc89_1918,
+    };
+    assert!(check_image_crc(&image, &file).is_ok());
+
+    let err = check_image_crc(&image, &file[..8]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    file[10] ^= 0xff;
+    let err = check_image_crc(&image, &file).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_is_download_timeout() {
+    assert!(is_download_timeout(&io::ErrorKind::TimedOut.into()));
+    assert!(is_download_timeout(&io::Error::new(
+        io::ErrorKind::Other,
+        FwDownloadError::Timeout
+    )));
+    assert!(!is_download_timeout(&io::Error::new(
+        io::ErrorKind::Other,
+        FwDownloadError::CrcIncorrect
+    )));
+    assert!(!is_download_timeout(&io::ErrorKind::NotFound.into()));
+}
+
+#[test]
+fn test_download_tracker() {
+    let transport = Transport::Path(PathBuf::from("/dev/pciswitch-test"));
+    let other = Transport::Path(PathBuf::from("/dev/pciswitch-other"));
+    {
+        let download = DownloadTracker::start(&transport);
+        assert_eq!(DownloadTracker::progress(&transport), (0, 0));
+        download.update(1024, 4096);
+        assert_eq!(DownloadTracker::progress(&transport), (1024, 4096));
+        assert_eq!(DownloadTracker::progress(&other), (0, 0));
+    }
+    // Removed once the download is done
+    assert_eq!(DownloadTracker::progress(&transport), (0, 0));
+}
+
+#[test]
+fn test_download_tracker_same_transport() {
+    let transport = Transport::Path(PathBuf::from("/dev/pciswitch-concurrent"));
+    let first = DownloadTracker::start(&transport);
+    let second = DownloadTracker::start(&transport);
+    first.update(1024, 4096);
+    second.update(512, 8192);
+    // The most recent download is reported, and each only updated its own entry
+    assert_eq!(DownloadTracker::progress(&transport), (512, 8192));
+
+    // Finishing the first download leaves the second one's entry in place
+    drop(first);
+    assert_eq!(DownloadTracker::progress(&transport), (512, 8192));
+    second.update(1024, 8192);
+    assert_eq!(DownloadTracker::progress(&transport), (1024, 8192));
+    drop(second);
+    assert_eq!(DownloadTracker::progress(&transport), (0, 0));
+}
@@ -0,0 +1,556 @@
+//! Reading the event log the firmware keeps in flash
+//!
+//! The log is stored as binary entries, which are decoded using the log definitions (the
+//! module names and message formats) of the firmware that wrote them.
+
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::mem::MaybeUninit;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
+use std::path::Path;
+use std::process;
+use std::ptr;
+use std::time::Duration;
+
+use crate::ffi::{
+    fclose, fdopen, fflush, rewind, switchtec_log_def_to_file,
+    switchtec_log_def_type_SWITCHTEC_LOG_DEF_TYPE_APP, switchtec_log_file_info,
+    switchtec_log_parse_type_SWITCHTEC_LOG_PARSE_TYPE_APP, switchtec_log_to_file,
+    switchtec_log_type, switchtec_log_type_SWITCHTEC_LOG_FLASH,
+    switchtec_log_type_SWITCHTEC_LOG_RAM, switchtec_parse_log, FILE,
+};
+use crate::{get_switchtec_error, Generation, SwitchtecDevice};
+
+/// Severity of a firmware log entry, see [`LogEntry`]
+///
+/// ```
+/// use switchtec_user_sys::fwlog::LogSeverity;
+///
+/// let severity = LogSeverity::from("HIGH");
+/// assert_eq!(severity, LogSeverity::High);
+/// assert_eq!(severity.to_string(), "HIGH");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LogSeverity {
+    /// Logged with its severity disabled
+    Disabled,
+    /// Highest severity, E.g. fatal errors
+    Highest,
+    /// High severity
+    High,
+    /// Medium severity
+    Medium,
+    /// Low severity
+    Low,
+    /// Lowest severity, E.g. debug messages
+    Lowest,
+    /// A severity this crate doesn't know about
+    Unknown,
+}
+
+impl LogSeverity {
+    /// Get the name of the severity the way the library prints it (E.g. "HIGH")
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Disabled => "DISABLED",
+            Self::Highest => "HIGHEST",
+            Self::High => "HIGH",
+            Self::Medium => "MEDIUM",
+            Self::Low => "LOW",
+            Self::Lowest => "LOWEST",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl From<&str> for LogSeverity {
+    fn from(severity: &str) -> Self {
+        match severity.trim() {
+            "DISABLED" => Self::Disabled,
+            "HIGHEST" => Self::Highest,
+            "HIGH" => Self::High,
+            "MEDIUM" => Self::Medium,
+            "LOW" => Self::Low,
+            "LOWEST" => Self::Lowest,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for LogSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An entry of the firmware's event log, returned by [`SwitchtecDevice::event_log`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LogEntry {
+    /// Position of the entry in the log, oldest first
+    pub index: u32,
+    /// Time since the switch booted when the entry was logged
+    pub timestamp: Duration,
+    /// Firmware module that logged the entry (E.g. "PCIE_LINK")
+    pub module: String,
+    /// Severity of the entry
+    pub severity: LogSeverity,
+    /// ID of the entry's message within its module
+    pub event_id: u16,
+    /// The decoded message
+    pub message: String,
+}
+
+impl LogEntry {
+    /// Parse an entry from a line of a log decoded by the library, which is formatted like
+    /// `"0012|000d 01:02:03.004,005,006|PCIE_LINK    |HIGH     |0x0021   |Link up"`
+    ///
+    /// Returns `None` for lines that aren't entries, like the column headings
+    fn parse(line: &str) -> Option<Self> {
+        let mut columns = line.splitn(6, '|');
+        let index = columns.next()?.trim().parse().ok()?;
+        let timestamp = parse_timestamp(columns.next()?.trim())?;
+        let module = columns.next()?.trim().to_owned();
+        let severity = LogSeverity::from(columns.next()?);
+        let event_id = columns.next()?.trim();
+        let event_id = u16::from_str_radix(event_id.strip_prefix("0x")?, 16).ok()?;
+        let message = columns.next()?.trim_end().to_owned();
+        Some(Self {
+            index,
+            timestamp,
+            module,
+            severity,
+            event_id,
+            message,
+        })
+    }
+}
+
+/// Parse a log timestamp formatted like `"001d 02:03:04.005,006,007"` (days, then hours,
+/// minutes and seconds, then milliseconds, microseconds and nanoseconds)
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let (days, time) = timestamp.split_once("d ")?;
+    let (hms, fraction) = time.split_once('.')?;
+    let hms = parse_fields(hms, ':', 2)?;
+    let fraction = parse_fields(fraction, ',', 3)?;
+    let days: u64 = days.parse().ok()?;
+    let secs = ((days * 24 + hms[0]) * 60 + hms[1]) * 60 + hms[2];
+    let nanos = (fraction[0] * 1000 + fraction[1]) * 1000 + fraction[2];
+    Some(Duration::from_secs(secs) + Duration::from_nanos(nanos))
+}
+
+/// Parse the three `separator` separated numbers of a timestamp, each exactly `width` digits
+fn parse_fields(fields: &str, separator: char, width: usize) -> Option<[u64; 3]> {
+    let mut parsed = [0; 3];
+    let mut fields = fields.split(separator);
+    for value in &mut parsed {
+        let field = fields.next().filter(|field| field.len() == width)?;
+        *value = field.parse().ok()?;
+    }
+    fields.next().is_none().then_some(parsed)
+}
+
+/// Which of the firmware's logs to read with [`SwitchtecDevice::log_entries`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LogType {
+    /// The log the firmware keeps in RAM, which is lost on reset
+    Ram,
+    /// The log the firmware keeps in flash, which persists across resets
+    Flash,
+}
+
+impl From<LogType> for switchtec_log_type {
+    fn from(log_type: LogType) -> Self {
+        match log_type {
+            LogType::Ram => switchtec_log_type_SWITCHTEC_LOG_RAM,
+            LogType::Flash => switchtec_log_type_SWITCHTEC_LOG_FLASH,
+        }
+    }
+}
+
+/// Iterator over the entries of a decoded firmware log, returned by
+/// [`SwitchtecDevice::log_entries`]
+///
+/// The decoded log is kept in an anonymous temporary file and read a line at a time, so memory
+/// use doesn't grow with the size of the log. Lines that aren't entries (E.g. the column
+/// headings, or a record the library couldn't decode) are skipped, and an error reading the
+/// file is returned without ending the iteration.
+pub struct LogEntries {
+    lines: io::Lines<BufReader<File>>,
+}
+
+impl LogEntries {
+    fn new(parsed: File) -> Self {
+        Self {
+            lines: BufReader::new(parsed).lines(),
+        }
+    }
+}
+
+impl Iterator for LogEntries {
+    type Item = io::Result<LogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.lines.next()? {
+                Ok(line) => {
+                    if let Some(entry) = LogEntry::parse(&line) {
+                        return Some(Ok(entry));
+                    }
+                }
+                // A record that isn't valid UTF-8 can't be parsed either
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for LogEntries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogEntries").finish_non_exhaustive()
+    }
+}
+
+impl SwitchtecDevice {
+    /// Read the event log the firmware keeps in flash, like `switchtec log-dump` followed by
+    /// `switchtec log-parse`
+    ///
+    /// The log is decoded with the log definitions read from the running firmware, so it
+    /// needs a firmware that provides them (Gen4 and newer). The flash log persists across
+    /// resets, which makes it useful for looking into what happened before an incident.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::fwlog::LogSeverity;
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for entry in device.event_log()? {
+    ///     if matches!(entry.severity, LogSeverity::Highest | LogSeverity::High) {
+    ///         println!("{:?} {}: {}", entry.timestamp, entry.module, entry.message);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Use [`SwitchtecDevice::log_entries`] to go through a large log without holding all of
+    /// its entries in memory.
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Device.html>
+    pub fn event_log(&self) -> io::Result<Vec<LogEntry>> {
+        self.log_entries(LogType::Flash)?.collect()
+    }
+
+    /// Read and decode one of the firmware's logs, returning an iterator over its entries
+    ///
+    /// The log is read from the device and decoded (with the log definitions of the running
+    /// firmware, like [`SwitchtecDevice::event_log`]) into an anonymous temporary file, which
+    /// the iterator then reads one entry at a time. This keeps memory use flat for logs of
+    /// several MB, E.g. when tailing a large fault log. A line that can't be parsed as an entry
+    /// is skipped rather than ending the iteration.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::fwlog::{LogSeverity, LogType};
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for entry in device.log_entries(LogType::Ram)? {
+    ///     let entry = entry?;
+    ///     if entry.severity == LogSeverity::Highest {
+    ///         println!("{:?} {}: {}", entry.timestamp, entry.module, entry.message);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn log_entries(&self, log_type: LogType) -> io::Result<LogEntries> {
+        let defs = CFile::new(temp_file()?, "w+")?;
+        // SAFETY: We know that device holds a valid/open switchtec device, and `defs` is an
+        // open stream for the duration of the call
+        let ret = unsafe {
+            traced!(switchtec_log_def_to_file(
+                self.inner,
+                switchtec_log_def_type_SWITCHTEC_LOG_DEF_TYPE_APP,
+                defs.0,
+            ))
+        };
+        if ret < 0 {
+            return Err(self.perror("read log definitions"));
+        }
+        defs.rewind()?;
+
+        let log = temp_file()?;
+        let mut info = MaybeUninit::<switchtec_log_file_info>::zeroed();
+        // SAFETY: We know that device holds a valid/open switchtec device, and `log` is open for
+        // the duration of the call. Without a definitions file, the raw log is written to it
+        let ret = unsafe {
+            traced!(switchtec_log_to_file(
+                self.inner,
+                log_type.into(),
+                log.as_raw_fd(),
+                ptr::null_mut(),
+                info.as_mut_ptr(),
+            ))
+        };
+        if ret < 0 {
+            return Err(self.perror("read event log"));
+        }
+
+        let (parsed, _) = parse_log(log, &defs, self.generation())?;
+        Ok(LogEntries::new(parsed))
+    }
+
+    /// Decode a raw firmware log (E.g. saved by `switchtec log-dump`) into text, using the log
+    /// definitions file (`.dat` or `.def`) of the firmware that wrote it
+    ///
+    /// The log is decoded like `switchtec log-parse` does, for the PCIe generation of this
+    /// device, and the decoded lines are returned starting with the column headings. Use
+    /// [`SwitchtecDevice::event_log`] to read and decode the log of the running firmware
+    /// directly.
+    ///
+    /// - Returns a [`NotFound`](io::ErrorKind::NotFound) error if `defs` doesn't exist
+    /// - Returns an [`InvalidData`](io::ErrorKind::InvalidData) error if the definitions are
+    ///   for a different firmware version than the log, since the messages would be decoded
+    ///   with the wrong formats
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let raw = std::fs::read("flash_log.bin")?;
+    /// for line in device.decode_log(&raw, Path::new("log_defs.dat"))? {
+    ///     println!("{line}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn decode_log(&self, raw: &[u8], defs: &Path) -> io::Result<Vec<String>> {
+        let defs_file = File::open(defs).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("unable to open log definitions {}: {e}", defs.display()),
+            )
+        })?;
+        let defs_stream = CFile::new(defs_file, "r")?;
+        let mut log = temp_file()?;
+        log.write_all(raw)?;
+
+        let (mut parsed, info) = parse_log(log, &defs_stream, self.generation())
+            .map_err(|e| io::Error::new(e.kind(), format!("unable to decode log: {e}")))?;
+        if info.version_mismatch {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "log definitions {} are for a different firmware version than the log",
+                    defs.display()
+                ),
+            ));
+        }
+        let mut text = Vec::new();
+        parsed.read_to_end(&mut text)?;
+        Ok(String::from_utf8_lossy(&text)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_owned)
+            .collect())
+    }
+}
+
+/// Decode the raw log in `log` with the log definitions in `defs`, returning a temporary file
+/// holding the decoded text (rewound to its start) and the version details the library found
+/// in both files
+fn parse_log(
+    mut log: File,
+    defs: &CFile,
+    gen: Generation,
+) -> io::Result<(File, switchtec_log_file_info)> {
+    log.rewind()?;
+    let log = CFile::new(log, "r")?;
+    let mut parsed = temp_file()?;
+    let parsed_stream = CFile::new(parsed.try_clone()?, "w")?;
+    let mut info = MaybeUninit::<switchtec_log_file_info>::zeroed();
+    // SAFETY: All three streams are open for the duration of the call, and `info` is only
+    // read after the library reports that it was populated
+    let info = unsafe {
+        let ret = switchtec_parse_log(
+            log.0,
+            defs.0,
+            parsed_stream.0,
+            switchtec_log_parse_type_SWITCHTEC_LOG_PARSE_TYPE_APP,
+            gen.into(),
+            info.as_mut_ptr(),
+        );
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        info.assume_init()
+    };
+    // Closing the stream flushes the decoded log to the file
+    drop(parsed_stream);
+
+    parsed.rewind()?;
+    Ok((parsed, info))
+}
+
+/// A C stdio stream, for the library functions that read or write a `FILE`. It's closed when
+/// it's dropped.
+struct CFile(*mut FILE);
+
+impl CFile {
+    /// Open a stream on `file` with the given `fopen` style `mode`, which takes ownership of
+    /// the file
+    fn new(file: File, mode: &str) -> io::Result<Self> {
+        let mode = CString::new(mode)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        let fd = file.into_raw_fd();
+        // SAFETY: `fd` is an open file descriptor that the stream takes ownership of
+        let stream = unsafe { fdopen(fd, mode.as_ptr()) };
+        if stream.is_null() {
+            let err = io::Error::last_os_error();
+            // SAFETY: The stream wasn't opened, so `fd` is still ours to close
+            drop(unsafe { File::from_raw_fd(fd) });
+            return Err(err);
+        }
+        Ok(Self(stream))
+    }
+
+    /// Flush anything written to the stream, and seek back to its start
+    fn rewind(&self) -> io::Result<()> {
+        // SAFETY: The stream is open until `self` is dropped
+        unsafe {
+            if fflush(self.0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            rewind(self.0);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CFile {
+    fn drop(&mut self) {
+        // SAFETY: The stream is open, and isn't used again after it's closed
+        unsafe { fclose(self.0) };
+    }
+}
+
+/// Number of names [`temp_file`] tries before giving up
+pub(crate) const TEMP_FILE_ATTEMPTS: u32 = 64;
+
+/// Create an anonymous temporary file, which is removed as soon as it's created and goes
+/// away once it's closed
+///
+/// The file gets a random name that only its owner can open, so other users can't predict
+/// (and take) it. A name that's already taken is skipped for a new one.
+fn temp_file() -> io::Result<File> {
+    for _ in 0..TEMP_FILE_ATTEMPTS {
+        let path = env::temp_dir().join(format!("switchtec-log-{:016x}", random_u64()));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path);
+        match file {
+            Ok(file) => {
+                fs::remove_file(&path)?;
+                return Ok(file);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "unable to create a temporary file with a unique name",
+    ))
+}
+
+/// Get a random number, from the randomly seeded keys std uses for `HashMap`
+pub(crate) fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(process::id());
+    hasher.finish()
+}
+
+#[test]
+fn test_parse_log_entry() {
+    assert_eq!(
+        LogEntry::parse(
+            "0012|001d 01:02:03.004,005,006|PCIE_LINK    |HIGH     |0x0021   |Link up\n"
+        ),
+        Some(LogEntry {
+            index: 12,
+            timestamp: Duration::new(24 * 3600 + 3600 + 2 * 60 + 3, 4_005_006),
+            module: "PCIE_LINK".to_owned(),
+            severity: LogSeverity::High,
+            event_id: 0x21,
+            message: "Link up".to_owned(),
+        })
+    );
+    // A message can contain the column separator
+    let entry =
+        LogEntry::parse("0000|000d 00:00:00.000,000,001|MAIN |LOWEST |0x0001 |a|b").unwrap();
+    assert_eq!(entry.message, "a|b");
+    assert_eq!(entry.timestamp, Duration::from_nanos(1));
+
+    for line in [
+        "   #|Timestamp                |Module       |Severity |Event ID |Event",
+        "",
+        "0001|00:00:01.000,000,000|MAIN |HIGH |0x0001 |No days",
+        "0001|000d 00:00:01.0,0,0|MAIN |HIGH |0x0001 |Short fraction",
+        "0001|000d 00:00:01.000,000,000|MAIN |HIGH |12 |Not hex",
+    ] {
+        assert_eq!(LogEntry::parse(line), None, "{line}");
+    }
+}
+
+#[test]
+fn test_temp_file() {
+    let mut file = temp_file().unwrap();
+    file.write_all(b"log").unwrap();
+    file.rewind().unwrap();
+    let mut text = String::new();
+    file.read_to_string(&mut text).unwrap();
+    assert_eq!(text, "log");
+}
+
+#[test]
+fn test_random_u64() {
+    assert_ne!(random_u64(), random_u64());
+}
+
+#[test]
+fn test_log_entries() {
+    let mut parsed = temp_file().unwrap();
+    parsed
+        .write_all(
+            b"   #|Timestamp                |Module       |Severity |Event ID |Event\n\
+              0000|000d 00:00:01.000,000,000|MAIN |HIGH |0x0001 |First\n\
+              0001|garbled record\n\
+              0001|000d 00:00:01.500,000,000|MAIN |HIGH |0x0001 |Not UTF-8 \xff\n\
+              0002|000d 00:00:02.000,000,000|MAIN |LOW  |0x0002 |Third\n",
+        )
+        .unwrap();
+    parsed.rewind().unwrap();
+
+    // The headings and the records that can't be parsed are skipped
+    let entries: Vec<_> = LogEntries::new(parsed).collect::<io::Result<_>>().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].message, "First");
+    assert_eq!(entries[1].index, 2);
+    assert_eq!(entries[1].severity, LogSeverity::Low);
+}
@@ -0,0 +1,247 @@
+//! Safe access to a Switchtec device's Global Address Space (GAS)
+//!
+//! The GAS holds the device's memory-mapped registers. It's mapped with
+//! [`SwitchtecDevice::gas_map`], and all accesses through the returned [`Gas`] are
+//! bounds-checked against the size of the mapped region.
+
+use std::io;
+use std::mem;
+use std::ptr;
+
+use crate::ffi::{
+    gas_read16, gas_read32, gas_read64, gas_read8, gas_write16, gas_write32, gas_write64,
+    gas_write8, gasptr_t, memcpy_from_gas, switchtec_gas_map, switchtec_gas_unmap,
+};
+use crate::{get_switchtec_error, SwitchtecDevice};
+
+/// A mapped Global Address Space region for a [`SwitchtecDevice`]
+///
+/// The region is unmapped when the `Gas` goes out of scope, and it borrows the device so it
+/// can't outlive the handle it was mapped from.
+///
+/// ```no_run
+/// use switchtec_user_sys::SwitchtecDevice;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+/// let gas = device.gas_map(false)?;
+/// println!("First GAS dword: {:#010x}", gas.read32(0)?);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Gas<'a> {
+    device: &'a SwitchtecDevice,
+    map: gasptr_t,
+    len: usize,
+    writable: bool,
+}
+
+impl SwitchtecDevice {
+    /// Map the Global Address Space of the device, so registers can be read (and written if
+    /// `writable` is true) through the returned [`Gas`]
+    ///
+    /// Mapping the GAS usually requires root privileges.
+    pub fn gas_map(&self, writable: bool) -> io::Result<Gas<'_>> {
+        let mut len: usize = 0;
+        // SAFETY: We know that device holds a valid/open switchtec device, and we're checking
+        // the returned map for failure before using it
+        let map = unsafe {
+            traced!(switchtec_gas_map(
+                self.inner,
+                writable as i32,
+                ptr::addr_of_mut!(len)
+            ))
+        };
+        // The library returns `SWITCHTEC_MAP_FAILED` (`MAP_FAILED`, or -1) on error
+        if map.is_null() || map as isize == -1 {
+            return Err(get_switchtec_error());
+        }
+        Ok(Gas {
+            device: self,
+            map,
+            len,
+            writable,
+        })
+    }
+}
+
+impl Gas<'_> {
+    /// Size of the mapped region, in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the mapped region is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if the region was mapped as writable
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Read a `u8` register at `offset`
+    pub fn read8(&self, offset: usize) -> io::Result<u8> {
+        let addr = self.addr::<u8>(offset)?;
+        // SAFETY: `addr` has been bounds-checked against the mapped region
+        Ok(unsafe { gas_read8(self.device.inner, addr) })
+    }
+
+    /// Read a `u16` register at `offset`
+    pub fn read16(&self, offset: usize) -> io::Result<u16> {
+        let addr = self.addr::<u16>(offset)?;
+        // SAFETY: `addr` has been bounds-checked against the mapped region
+        Ok(unsafe { gas_read16(self.device.inner, addr) })
+    }
+
+    /// Read a `u32` register at `offset`
+    pub fn read32(&self, offset: usize) -> io::Result<u32> {
+        let addr = self.addr::<u32>(offset)?;
+        // SAFETY: `addr` has been bounds-checked against the mapped region
+        Ok(unsafe { gas_read32(self.device.inner, addr) })
+    }
+
+    /// Read a `u64` register at `offset`
+    pub fn read64(&self, offset: usize) -> io::Result<u64> {
+        let addr = self.addr::<u64>(offset)?;
+        // SAFETY: `addr` has been bounds-checked against the mapped region
+        Ok(unsafe { gas_read64(self.device.inner, addr) })
+    }
+
+    /// Read `buf.len()` bytes starting at `offset` into `buf`
+    ///
+    /// The whole range is copied in one call, which is much faster than a loop of
+    /// [`Gas::read32`] for dumping a block of registers. Unlike the single register reads,
+    /// `offset` doesn't need to be aligned.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let gas = device.gas_map(false)?;
+    /// let mut block = vec![0u8; 0x1000];
+    /// gas.read_into(0, &mut block)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_into(&self, offset: usize, buf: &mut [u8]) -> io::Result<()> {
+        check_range(offset, buf.len(), self.len)?;
+        // SAFETY: The range has been bounds-checked against the mapped region, and `buf` is
+        // valid for `buf.len()` bytes
+        unsafe {
+            memcpy_from_gas(
+                self.device.inner,
+                buf.as_mut_ptr() as *mut _,
+                (self.map as *const u8).add(offset) as *const _,
+                buf.len(),
+            )
+        };
+        Ok(())
+    }
+
+    /// Write a `u8` register at `offset`
+    pub fn write8(&self, offset: usize, value: u8) -> io::Result<()> {
+        let addr = self.writable_addr::<u8>(offset)?;
+        // SAFETY: `addr` has been bounds-checked against the (writable) mapped region
+        unsafe { gas_write8(self.device.inner, value, addr) };
+        Ok(())
+    }
+
+    /// Write a `u16` register at `offset`
+    pub fn write16(&self, offset: usize, value: u16) -> io::Result<()> {
+        let addr = self.writable_addr::<u16>(offset)?;
+        // SAFETY: `addr` has been bounds-checked against the (writable) mapped region
+        unsafe { gas_write16(self.device.inner, value, addr) };
+        Ok(())
+    }
+
+    /// Write a `u32` register at `offset`
+    pub fn write32(&self, offset: usize, value: u32) -> io::Result<()> {
+        let addr = self.writable_addr::<u32>(offset)?;
+        // SAFETY: `addr` has been bounds-checked against the (writable) mapped region
+        unsafe { gas_write32(self.device.inner, value, addr) };
+        Ok(())
+    }
+
+    /// Write a `u64` register at `offset`
+    pub fn write64(&self, offset: usize, value: u64) -> io::Result<()> {
+        let addr = self.writable_addr::<u64>(offset)?;
+        // SAFETY: `addr` has been bounds-checked against the (writable) mapped region
+        unsafe { gas_write64(self.device.inner, value, addr) };
+        Ok(())
+    }
+
+    /// Get the address of a `T` register at `offset`, if it's in bounds and aligned
+    fn addr<T>(&self, offset: usize) -> io::Result<*mut T> {
+        check_access(offset, mem::size_of::<T>(), self.len)?;
+        // SAFETY: `offset` is within the mapped region
+        Ok(unsafe { (self.map as *mut u8).add(offset) as *mut T })
+    }
+
+    fn writable_addr<T>(&self, offset: usize) -> io::Result<*mut T> {
+        if !self.writable {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "GAS was not mapped as writable",
+            ));
+        }
+        self.addr(offset)
+    }
+}
+
+impl Drop for Gas<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `map` was successfully mapped from this device in `gas_map`
+        unsafe {
+            switchtec_gas_unmap(self.device.inner, self.map);
+        }
+    }
+}
+
+/// Check that an access of `size` bytes at `offset` is aligned and within a `len` byte region
+fn check_access(offset: usize, size: usize, len: usize) -> io::Result<()> {
+    check_range(offset, size, len)?;
+    // Register sizes are powers of two
+    if offset & (size - 1) != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("GAS access of {size} bytes at {offset:#x} is not aligned"),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that an access of `size` bytes at `offset` is within a `len` byte region
+fn check_range(offset: usize, size: usize, len: usize) -> io::Result<()> {
+    match offset.checked_add(size) {
+        Some(end) if end <= len => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "GAS access of {size} bytes at {offset:#x} is outside the {len:#x} byte mapping"
+            ),
+        )),
+    }
+}
+
+#[test]
+fn test_gas_check_access() {
+    assert!(check_access(0, 4, 0x100).is_ok());
+    assert!(check_access(0xfc, 4, 0x100).is_ok());
+    assert!(check_access(0xff, 1, 0x100).is_ok());
+    // Out of bounds
+    assert!(check_access(0x100, 1, 0x100).is_err());
+    assert!(check_access(0xfd, 4, 0x100).is_err());
+    assert!(check_access(usize::MAX, 8, 0x100).is_err());
+    // Unaligned
+    assert!(check_access(0x2, 4, 0x100).is_err());
+    assert!(check_access(0x4, 8, 0x100).is_err());
+
+    // Bulk reads only need to be in bounds
+    assert!(check_range(0x3, 0xfd, 0x100).is_ok());
+    assert!(check_range(0x100, 0, 0x100).is_ok());
+    assert!(check_range(0x3, 0xfe, 0x100).is_err());
+    assert!(check_range(usize::MAX, 2, 0x100).is_err());
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+use std::io;
+
+use crate::{
+    switchtec_gen, switchtec_gen_SWITCHTEC_GEN3, switchtec_gen_SWITCHTEC_GEN4,
+    switchtec_gen_SWITCHTEC_GEN5, switchtec_gen_SWITCHTEC_GEN_UNKNOWN, SwitchtecDevice,
+};
+
+/// PCIe generation of a Switchtec device
+///
+/// ```
+/// use switchtec_user_sys::{switchtec_gen, switchtec_gen_SWITCHTEC_GEN4, Generation};
+///
+/// let generation = Generation::from(switchtec_gen_SWITCHTEC_GEN4);
+/// assert_eq!(generation, Generation::Gen4);
+/// assert_eq!(generation.to_string(), "Gen4");
+/// assert_eq!(switchtec_gen::from(generation), switchtec_gen_SWITCHTEC_GEN4);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Generation {
+    /// PCIe Gen3
+    Gen3,
+    /// PCIe Gen4
+    Gen4,
+    /// PCIe Gen5
+    Gen5,
+    /// The device reported a generation this crate doesn't know about
+    Unknown,
+}
+
+impl Generation {
+    /// Get the name of the generation (E.g. "Gen4")
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Gen3 => "Gen3",
+            Self::Gen4 => "Gen4",
+            Self::Gen5 => "Gen5",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
+impl From<switchtec_gen> for Generation {
+    fn from(generation: switchtec_gen) -> Self {
+        match generation {
+            switchtec_gen_SWITCHTEC_GEN3 => Self::Gen3,
+            switchtec_gen_SWITCHTEC_GEN4 => Self::Gen4,
+            switchtec_gen_SWITCHTEC_GEN5 => Self::Gen5,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl From<Generation> for switchtec_gen {
+    fn from(generation: Generation) -> Self {
+        match generation {
+            Generation::Gen3 => switchtec_gen_SWITCHTEC_GEN3,
+            Generation::Gen4 => switchtec_gen_SWITCHTEC_GEN4,
+            Generation::Gen5 => switchtec_gen_SWITCHTEC_GEN5,
+            Generation::Unknown => switchtec_gen_SWITCHTEC_GEN_UNKNOWN,
+        }
+    }
+}
+
+impl fmt::Display for Generation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl SwitchtecDevice {
+    /// Get the PCIe generation of the device, or an error if it's unknown
+    ///
+    /// [`SwitchtecDevice::generation`] returns [`Generation::Unknown`] for the
+    /// `SWITCHTEC_GEN_UNKNOWN` sentinel (or any other value this crate doesn't know about).
+    /// This returns an [`InvalidData`](io::ErrorKind::InvalidData) error instead, for code
+    /// that must not act on an unidentified device.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{Generation, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// if device.try_generation()? >= Generation::Gen4 {
+    ///     println!("Secure boot is supported");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_generation(&self) -> io::Result<Generation> {
+        known(self.generation())
+    }
+
+    /// Check if the device is a Gen3 switch
+    ///
+    /// Like the other generation checks, this is derived from
+    /// [`SwitchtecDevice::generation`], which `libswitchtec` reads once when the device is
+    /// opened, so it doesn't send a command to the device.
+    pub fn is_gen3(&self) -> bool {
+        self.generation() == Generation::Gen3
+    }
+
+    /// Check if the device is a Gen4 switch
+    pub fn is_gen4(&self) -> bool {
+        self.generation() == Generation::Gen4
+    }
+
+    /// Check if the device is a Gen5 switch
+    pub fn is_gen5(&self) -> bool {
+        self.generation() == Generation::Gen5
+    }
+
+    /// Check if the device is a `min` generation switch or newer
+    ///
+    /// Always false for a device with an [`Unknown`](Generation::Unknown) generation.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{Generation, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// if device.gen_at_least(Generation::Gen4) {
+    ///     println!("{:?}", device.security_config()?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn gen_at_least(&self, min: Generation) -> bool {
+        at_least(self.generation(), min)
+    }
+}
+
+/// Get `generation` if it's known, or an error if it's [`Generation::Unknown`]
+fn known(generation: Generation) -> io::Result<Generation> {
+    if generation == Generation::Unknown {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "device reported an unknown PCIe generation",
+        ));
+    }
+    Ok(generation)
+}
+
+/// Check if `generation` is known, and `min` or newer
+fn at_least(generation: Generation, min: Generation) -> bool {
+    generation != Generation::Unknown && generation >= min
+}
+
+#[test]
+fn test_generation_names() {
+    assert_eq!(
+        Generation::from(switchtec_gen_SWITCHTEC_GEN3).to_string(),
+        "Gen3"
+    );
+    assert_eq!(
+        Generation::from(switchtec_gen_SWITCHTEC_GEN4).to_string(),
+        "Gen4"
+    );
+    assert_eq!(
+        Generation::from(switchtec_gen_SWITCHTEC_GEN5).to_string(),
+        "Gen5"
+    );
+    assert_eq!(
+        Generation::from(switchtec_gen_SWITCHTEC_GEN_UNKNOWN).to_string(),
+        "Unknown"
+    );
+    assert_eq!(Generation::from(0x42), Generation::Unknown);
+}
+
+#[test]
+fn test_generation_round_trip() {
+    for raw in [
+        switchtec_gen_SWITCHTEC_GEN3,
+        switchtec_gen_SWITCHTEC_GEN4,
+        switchtec_gen_SWITCHTEC_GEN5,
+        switchtec_gen_SWITCHTEC_GEN_UNKNOWN,
+    ] {
+        assert_eq!(switchtec_gen::from(Generation::from(raw)), raw);
+    }
+    for generation in [
+        Generation::Gen3,
+        Generation::Gen4,
+        Generation::Gen5,
+        Generation::Unknown,
+    ] {
+        assert_eq!(
+            Generation::from(switchtec_gen::from(generation)),
+            generation
+        );
+    }
+    // Values this crate doesn't know about can't be round-tripped
+    assert_eq!(
+        switchtec_gen::from(Generation::from(0x42)),
+        switchtec_gen_SWITCHTEC_GEN_UNKNOWN
+    );
+}
+
+#[test]
+fn test_generation_at_least() {
+    assert!(at_least(Generation::Gen4, Generation::Gen3));
+    assert!(at_least(Generation::Gen4, Generation::Gen4));
+    assert!(!at_least(Generation::Gen4, Generation::Gen5));
+    assert!(!at_least(Generation::Unknown, Generation::Gen3));
+}
+
+#[test]
+fn test_known_generation() {
+    assert_eq!(known(Generation::Gen5).unwrap(), Generation::Gen5);
+    let err = known(Generation::Unknown).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
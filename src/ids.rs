@@ -0,0 +1,140 @@
+use std::fmt;
+
+/// A physical port number on a Switchtec device
+///
+/// ```
+/// use switchtec_user_sys::PortId;
+///
+/// let port = PortId::from(8);
+/// assert_eq!(u8::from(port), 8);
+/// assert_eq!(port.to_string(), "8");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PortId(pub u8);
+
+/// A partition number on a Switchtec device
+///
+/// ```
+/// use switchtec_user_sys::PartitionId;
+///
+/// let partition = PartitionId::from(1);
+/// assert_eq!(u8::from(partition), 1);
+/// assert_eq!(partition.to_string(), "1");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartitionId(pub u8);
+
+/// Where a port sits on a Switchtec device: its partition, stack and port within the stack
+///
+/// Locations are ordered by partition, then stack, then port, so sorting results by their
+/// location (E.g. with [`PortStatus::location`](crate::PortStatus::location)) gives a stable
+/// layout regardless of the order the hardware reported them in.
+///
+/// ```
+/// use switchtec_user_sys::{PartitionId, PortLocation};
+///
+/// let mut ports = vec![
+///     PortLocation::new(PartitionId(1), 0, 0),
+///     PortLocation::new(PartitionId(0), 1, 0),
+///     PortLocation::new(PartitionId(0), 0, 2),
+/// ];
+/// ports.sort();
+/// assert_eq!(ports[0], PortLocation::new(PartitionId(0), 0, 2));
+/// assert_eq!(ports[2].to_string(), "1/0/0");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PortLocation {
+    /// Partition the port is in
+    pub partition: PartitionId,
+    /// Stack the port is in
+    pub stack: u8,
+    /// Port number within the stack
+    pub port: u8,
+}
+
+impl PortLocation {
+    /// Create a port location
+    pub const fn new(partition: PartitionId, stack: u8, port: u8) -> Self {
+        Self {
+            partition,
+            stack,
+            port,
+        }
+    }
+}
+
+impl fmt::Display for PortLocation {
+    /// Display the location as "partition/stack/port"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.partition, self.stack, self.port)
+    }
+}
+
+macro_rules! id_conversions {
+    ($id:ident) => {
+        impl $id {
+            /// Create an ID from its raw value
+            pub const fn new(id: u8) -> Self {
+                Self(id)
+            }
+
+            /// Get the raw value of the ID
+            pub const fn get(self) -> u8 {
+                self.0
+            }
+        }
+
+        impl From<u8> for $id {
+            fn from(id: u8) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$id> for u8 {
+            fn from(id: $id) -> Self {
+                id.0
+            }
+        }
+
+        impl From<$id> for i32 {
+            fn from(id: $id) -> Self {
+                i32::from(id.0)
+            }
+        }
+
+        impl fmt::Display for $id {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+id_conversions!(PortId);
+id_conversions!(PartitionId);
+
+#[test]
+fn test_id_conversions() {
+    const PORT: PortId = PortId::new(12);
+    assert_eq!(PORT.get(), 12);
+    assert_eq!(i32::from(PORT), 12);
+    assert_eq!(PartitionId::from(3), PartitionId(3));
+    assert_eq!(format!("Port {PORT}"), "Port 12");
+}
+
+#[test]
+fn test_port_location_order() {
+    // Partition takes precedence over stack, which takes precedence over port
+    let a = PortLocation::new(PartitionId(0), 1, 7);
+    let b = PortLocation::new(PartitionId(0), 2, 0);
+    let c = PortLocation::new(PartitionId(1), 0, 0);
+    assert!(a < b && b < c);
+    assert!(PortLocation::new(PartitionId(0), 1, 0) < a);
+
+    let mut locations = vec![c, a, b];
+    locations.sort();
+    assert_eq!(locations, [a, b, c]);
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::ptr;
+
+use crate::ffi::{
+    switchtec_device_id, switchtec_get_device_info, switchtec_rev, switchtec_variant,
+};
+use crate::open::Transport;
+use crate::{
+    get_switchtec_error, switchtec_boot_phase, switchtec_gen, BootPhase, Generation, PartitionId,
+    Revision, SwitchtecDevice, Temperature, Variant,
+};
+
+/// A summary of the device's identity and firmware, returned by [`SwitchtecDevice::info`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceSummary {
+    /// Device name (E.g. "pciswitch0")
+    pub name: String,
+    /// PCI vendor ID of the switch's management function, if the device was opened from its
+    /// character device (E.g. "/dev/pciswitch0") on Linux
+    pub vendor_id: Option<u16>,
+    /// PCI device ID of the switch
+    pub device_id: u16,
+    /// PCI class code of the switch's management function (E.g. `0x058000`), if the device
+    /// was opened from its character device on Linux
+    pub device_class: Option<u32>,
+    /// PCIe generation of the switch
+    pub generation: Generation,
+    /// Product variant of the switch (E.g. PFX or PAX)
+    pub variant: Variant,
+    /// Silicon revision of the switch
+    pub revision: Revision,
+    /// Boot phase the switch is currently running in
+    pub boot_phase: BootPhase,
+    /// Partition the device was opened on
+    pub partition: PartitionId,
+    /// Number of partitions of the switch, see [`SwitchtecDevice::partition_count`]
+    pub partition_count: usize,
+    /// Firmware version as a user readable string
+    pub firmware_version: String,
+}
+
+/// A snapshot of a device's health, returned by [`SwitchtecDevice::health`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HealthSnapshot {
+    /// Die temperature of the switch
+    pub temperature: Temperature,
+    /// Firmware version as a user readable string
+    pub firmware_version: String,
+    /// Number of ports with their link up
+    pub linked_ports: usize,
+}
+
+impl SwitchtecDevice {
+    /// Get a summary of the device's identity and firmware
+    ///
+    /// The generation, revision and boot phase are read from the device with a single
+    /// `switchtec_get_device_info` call, so prefer this over the individual getters (E.g.
+    /// [`SwitchtecDevice::generation`]) when more than one of them is needed. The vendor ID
+    /// and class are read from sysfs, the same way `libswitchtec` reads the device ID, so
+    /// they're only reported for devices opened from their character device on Linux.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let info = device.info()?;
+    /// println!(
+    ///     "{} {} ({:04x}:{:04x}) with {} partitions, running {}",
+    ///     info.variant,
+    ///     info.revision,
+    ///     info.vendor_id.unwrap_or_default(),
+    ///     info.device_id,
+    ///     info.partition_count,
+    ///     info.firmware_version
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Device.html>
+    pub fn info(&self) -> io::Result<DeviceSummary> {
+        let mut boot_phase: switchtec_boot_phase = 0;
+        let mut generation: switchtec_gen = 0;
+        let mut revision: switchtec_rev = 0;
+
+        // SAFETY: We know that device holds a valid/open switchtec device, and each out-value
+        // is a valid enum value to be populated
+        let ret = unsafe {
+            traced!(switchtec_get_device_info(
+                self.inner,
+                ptr::addr_of_mut!(boot_phase),
+                ptr::addr_of_mut!(generation),
+                ptr::addr_of_mut!(revision),
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+
+        // SAFETY: These only read values cached in the device when it was opened
+        let (device_id, variant) = unsafe {
+            (
+                switchtec_device_id(self.inner),
+                switchtec_variant(self.inner),
+            )
+        };
+
+        Ok(DeviceSummary {
+            name: self.name()?,
+            vendor_id: self
+                .pci_attr("vendor")
+                .and_then(|id| u16::try_from(id).ok()),
+            device_id: device_id as u16,
+            device_class: self.pci_attr("class"),
+            generation: generation.into(),
+            variant: variant.into(),
+            revision: revision.into(),
+            boot_phase: boot_phase.into(),
+            partition: self.try_partition()?,
+            partition_count: self.partition_count()?,
+            firmware_version: self.firmware_version()?,
+        })
+    }
+
+    /// Read a hex attribute (E.g. "vendor") of the PCI function behind the device from sysfs,
+    /// if it was opened from its character device
+    fn pci_attr(&self, attr: &str) -> Option<u32> {
+        let Transport::Path(path) = &self.transport else {
+            return None;
+        };
+        let rdev = fs::metadata(path).ok()?.rdev();
+        if rdev == 0 {
+            return None;
+        }
+        let (major, minor) = dev_numbers(rdev);
+        let value = fs::read_to_string(format!("/sys/dev/char/{major}:{minor}/device/{attr}"));
+        parse_hex_attr(&value.ok()?)
+    }
+
+    /// Get a snapshot of the device's temperature, firmware version and linked ports
+    ///
+    /// Each is read with a single library call, so this is cheap enough to poll
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let health = device.health()?;
+    /// println!("{} with {} links up", health.temperature, health.linked_ports);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn health(&self) -> io::Result<HealthSnapshot> {
+        Ok(HealthSnapshot {
+            temperature: self.die_temp()?,
+            firmware_version: self.firmware_version()?,
+            linked_ports: self.linked_ports()?.count(),
+        })
+    }
+
+    /// Get a [`HealthSnapshot`] of each of the `devices`
+    ///
+    /// Each device's result is returned separately, in the same order as `devices`, so one
+    /// failing switch doesn't hide the health of the others
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let devices = SwitchtecDevice::list()?
+    ///     .iter()
+    ///     .map(|info| SwitchtecDevice::open(&info.path))
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// for (device, health) in devices.iter().zip(SwitchtecDevice::poll_health(&devices)) {
+    ///     match health {
+    ///         Ok(health) => println!("{device:?}: {}", health.temperature),
+    ///         Err(e) => println!("{device:?}: {e}"),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn poll_health(devices: &[SwitchtecDevice]) -> Vec<io::Result<HealthSnapshot>> {
+        devices.iter().map(SwitchtecDevice::health).collect()
+    }
+}
+
+/// Split a Linux device number into its major and minor numbers, like glibc's `major()` and
+/// `minor()`
+fn dev_numbers(rdev: u64) -> (u64, u64) {
+    let major = ((rdev >> 8) & 0xfff) | ((rdev >> 32) & 0xffff_f000);
+    let minor = (rdev & 0xff) | ((rdev >> 12) & 0xffff_ff00);
+    (major, minor)
+}
+
+/// Parse a sysfs hex attribute (E.g. "0x11f8\n")
+fn parse_hex_attr(value: &str) -> Option<u32> {
+    let value = value.trim();
+    u32::from_str_radix(value.strip_prefix("0x").unwrap_or(value), 16).ok()
+}
+
+#[test]
+fn test_pci_attr_parsing() {
+    assert_eq!(dev_numbers(0xef00), (239, 0));
+    assert_eq!(dev_numbers(0xef01), (239, 1));
+    // Minor numbers above 255 and major numbers above 4095 use the extended encoding
+    assert_eq!(dev_numbers(0x1000_0100_0005), (0x1000, 0x1005));
+
+    assert_eq!(parse_hex_attr("0x11f8\n"), Some(0x11f8));
+    assert_eq!(parse_hex_attr("0x058000\n"), Some(0x058000));
+    assert_eq!(parse_hex_attr("garbage"), None);
+}
@@ -5,12 +5,15 @@
 #![allow(clippy::missing_safety_doc)]
 #![doc = include_str!("../README.md")]
 
+use std::cell::OnceCell;
 use std::ffi::{CStr, CString};
 use std::fmt;
 use std::io;
-use std::mem::MaybeUninit;
+use std::mem::ManuallyDrop;
+use std::os::raw::c_char;
 use std::os::unix::ffi::OsStrExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::ptr;
 
 /// The raw FFI bindings to `libswitchtec`
 pub mod ffi {
@@ -21,14 +24,113 @@ pub mod ffi {
 mod prelude;
 pub use prelude::*;
 
+// Declared first, so the `traced!` macro is available to the other modules
+#[macro_use]
+mod trace;
+
+mod boot_phase;
+mod cmd;
+mod crc;
+pub mod diag;
+pub mod error;
+pub mod events;
+pub mod fabric;
+pub mod fw;
+pub mod fwlog;
+pub mod gas;
+mod generation;
+mod ids;
+mod info;
+mod link;
+mod list;
+pub mod mfg;
+mod open;
+mod partition;
+pub mod pmon;
+#[cfg(feature = "tokio")]
+pub mod poller;
+mod progress;
+mod status;
+mod temperature;
+mod translate;
+mod variant;
+
+pub use boot_phase::BootPhase;
+pub use cmd::{MrpcCommand, MrpcRetry, MRPC_MAX_PAYLOAD_LEN};
+pub use crc::switchtec_crc;
+pub use error::SwitchtecError;
+pub use generation::Generation;
+pub use ids::{PartitionId, PortId, PortLocation};
+pub use info::{DeviceSummary, HealthSnapshot};
+pub use link::{LinkRate, LinkWidth, LtssmState};
+pub use list::DeviceInfo;
+pub use open::OpenBuilder;
+pub use partition::{PartitionDevice, PartitionInfo};
+pub use status::PortStatus;
+pub use temperature::Temperature;
+pub use variant::{Revision, Variant};
+
+use ffi::switchtec_echo;
+use open::Transport;
+
 /// `SwitchtecDevice` offers an safer way to work with the underlying [`switchtec_dev`] and
 /// represents an open Switchtec PCI Switch device that can be passed into `switchtec-user` C library functions
 ///
 /// - [`SwitchtecDevice`] closes the Switchtec character device when it goes out of scope
+/// - [`SwitchtecDevice`] is [`Send`], so an open device can be moved to another thread:
+///
+/// ```no_run
+/// use switchtec_user_sys::SwitchtecDevice;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+/// let temp = std::thread::spawn(move || device.die_temp()).join().unwrap()?;
+/// println!("Temperature: {temp}");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// - [`SwitchtecDevice`] is not [`Sync`], so it can't be shared between threads without
+///   synchronization (E.g. a [`Mutex`](std::sync::Mutex)):
+///
+/// ```compile_fail
+/// use switchtec_user_sys::SwitchtecDevice;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+/// std::thread::scope(|s| {
+///     s.spawn(|| device.die_temp());
+///     s.spawn(|| device.die_temp());
+/// });
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Attributes that can't change while the device is open are read from the library once and
+/// cached: [`name`](SwitchtecDevice::name), [`generation`](SwitchtecDevice::generation) and
+/// [`partition`](SwitchtecDevice::partition). Everything else (E.g. temperature, port status
+/// and firmware version) is read from the device on every call.
 pub struct SwitchtecDevice {
     inner: *mut switchtec_dev,
+    transport: Transport,
+    cache: DeviceCache,
 }
 
+/// Attributes of an open device that are read at most once, see [`SwitchtecDevice`]
+#[derive(Debug, Default)]
+struct DeviceCache {
+    name: OnceCell<String>,
+    generation: OnceCell<Generation>,
+    partition: OnceCell<i32>,
+}
+
+// SAFETY: `SwitchtecDevice` exclusively owns its `switchtec_dev` handle (a file descriptor
+// plus library state that isn't tied to the thread that opened it), so it can be moved to
+// and used from another thread. Errors are reported through the thread-local `errno`, which
+// is read on the same thread that made the library call. It's intentionally not `Sync`: the
+// library doesn't synchronize concurrent commands issued on the same handle.
+unsafe impl Send for SwitchtecDevice {}
+
 impl SwitchtecDevice {
     /// Open the Switchtec PCIe Switch character device at the given `path`,
     /// returning a `SwitchtecDevice` that can be used to pass into
@@ -53,102 +155,403 @@ impl SwitchtecDevice {
             //       https://github.com/rust-lang/rust/issues/86442
             io::Error::new(io::ErrorKind::Other, e.to_string())
         })?;
-        // SAFETY: Checking that the returned `dev` is not null prior to successfully returning
-        // a valid `Self` struct
+        // SAFETY: `path_c` is a valid C string for the duration of the call
+        let dev = unsafe { traced!(switchtec_open(path_c.as_ptr())) };
+        let path = path.as_ref().to_path_buf();
+        open::opened(dev, path.clone(), Transport::Path(path))
+    }
+
+    /// Open the Switchtec device at the given `path`, checking that it's bound to the
+    /// expected `partition`
+    ///
+    /// A device handle always operates on the partition it was opened on, which `libswitchtec`
+    /// determines from the device itself (E.g. each partition of a switch has its own
+    /// character device). This doesn't switch partitions: it opens `path` like
+    /// [`SwitchtecDevice::open`] and returns an [`InvalidInput`](io::ErrorKind::InvalidInput)
+    /// error if the device is in a different partition, so commands aren't accidentally sent
+    /// to the wrong one.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{PartitionId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open_with_partition("/dev/pciswitch1", PartitionId(1))?;
+    /// assert_eq!(device.partition(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_with_partition<T: AsRef<Path>>(
+        path: T,
+        partition: PartitionId,
+    ) -> io::Result<Self> {
+        Self::builder().partition(partition).path(path)
+    }
+
+    /// Take ownership of a `switchtec_dev` handle opened outside of this crate (E.g. with
+    /// [`switchtec_open`] directly), returning `None` if `ptr` is null
+    ///
+    /// The handle is closed when the returned device is dropped. `path` is the path the handle
+    /// was opened with, as reported by [`SwitchtecDevice::path`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or an open handle returned by `libswitchtec` that isn't closed or
+    /// owned by anything else
+    ///
+    /// ```no_run
+    /// use std::ffi::CString;
+    /// use std::path::PathBuf;
+    /// use switchtec_user_sys::{switchtec_open, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let path = CString::new("/dev/pciswitch0")?;
+    /// // SAFETY: The handle is owned by `device` from here on
+    /// let device = unsafe {
+    ///     SwitchtecDevice::from_raw(switchtec_open(path.as_ptr()), PathBuf::from("/dev/pciswitch0"))
+    /// };
+    /// if let Some(device) = device {
+    ///     println!("Temperature: {}", device.die_temp()?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub unsafe fn from_raw(ptr: *mut switchtec_dev, path: PathBuf) -> Option<Self> {
+        Self::from_handle(ptr, Transport::Path(path))
+    }
+
+    /// Take ownership of a handle returned by one of the library's open functions, or return
+    /// `None` if it's null
+    ///
+    /// Every `SwitchtecDevice` is constructed here, once the handle and everything else it
+    /// owns is ready, so a device that reaches [`Drop`] always holds an open handle.
+    ///
+    /// # Safety
+    ///
+    /// `dev` must be null or an open handle that isn't closed or owned by anything else
+    unsafe fn from_handle(dev: *mut switchtec_dev, transport: Transport) -> Option<Self> {
+        (!dev.is_null()).then(|| Self {
+            inner: dev,
+            transport,
+            cache: DeviceCache::default(),
+        })
+    }
+
+    /// Release ownership of the `switchtec_dev` handle without closing it
+    ///
+    /// The caller is responsible for closing the returned handle with [`switchtec_close`], or
+    /// passing it back to [`SwitchtecDevice::from_raw`].
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{switchtec_close, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let dev = device.into_raw();
+    /// // SAFETY: `dev` is an open handle that nothing else owns
+    /// unsafe { switchtec_close(dev) };
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_raw(self) -> *mut switchtec_dev {
+        let mut device = ManuallyDrop::new(self);
+        // SAFETY: `device` isn't used after its fields are dropped here, and `Drop` isn't run
+        // for it, so the handle stays open
         unsafe {
-            let dev = switchtec_open(path_c.as_ptr());
-            if dev.is_null() {
-                Err(get_switchtec_error())
-            } else {
-                Ok(Self { inner: dev })
-            }
+            ptr::drop_in_place(&mut device.transport);
+            ptr::drop_in_place(&mut device.cache);
         }
+        device.inner
+    }
+
+    /// Get the path this device was opened with (E.g. "/dev/pciswitch0")
+    ///
+    /// For devices opened over I2C or UART, this is the path of the adapter or serial port.
+    /// Returns `None` for devices that weren't opened from a path
+    pub fn path(&self) -> Option<&Path> {
+        self.transport.path()
     }
 
     /// Get the device name (E.g. "pciswitch0" in "/dev/pciswitch0")
     ///
     /// This can fail if the device name is not valid UTF-8
     ///
+    /// See [`SwitchtecDevice::info`] to get this along with the rest of the device's details
+    ///
     /// <https://microsemi.github.io/switchtec-user/group__Device.html#ga8d416a587f5e37e818ee937bd0c0dab1>
     pub fn name(&self) -> io::Result<String> {
+        if let Some(name) = self.cache.name.get() {
+            return Ok(name.clone());
+        }
         // SAFETY: We know that device holds a valid/open switchtec device
         let device_name = unsafe { switchtec_name(self.inner) };
         if device_name.is_null() {
-            Err(io::Error::new(
+            return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "no device name returned",
-            ))
-        } else {
-            device_name.as_string()
+            ));
         }
+        let name = device_name.as_string()?;
+        Ok(self.cache.name.get_or_init(|| name).clone())
     }
 
-    /// Get the PCIe generation of the device
+    /// Get the boot phase of the device
+    ///
+    /// Convert the result [`Into`] a raw [`switchtec_boot_phase`](type@switchtec_boot_phase) to
+    /// pass to the library
     ///
     /// <https://microsemi.github.io/switchtec-user/group__Device.html#ga9eab19beb39d2104b5defd28787177ae>
-    pub fn boot_phase(&self) -> switchtec_boot_phase {
+    pub fn boot_phase(&self) -> BootPhase {
         // SAFETY: We know that device holds a valid/open switchtec device
-        unsafe { switchtec_boot_phase(self.inner) }
+        unsafe { switchtec_boot_phase(self.inner) }.into()
     }
 
     /// Get the firmware version as a user readable string
     ///
-    /// This can fail if the firmware version is not valid UTF-8
+    /// This can fail if the firmware version is not valid UTF-8. Use
+    /// [`SwitchtecDevice::firmware_version_parts`] to compare versions.
+    ///
+    /// See [`SwitchtecDevice::info`] to get this along with the rest of the device's details
     ///
     /// <https://microsemi.github.io/switchtec-user/group__Device.html#gad16f110712bd23170ad69450c361122e>
     pub fn firmware_version(&self) -> io::Result<String> {
-        const buf_size: usize = 64;
-        let mut buf = MaybeUninit::<[u8; buf_size]>::uninit();
-        // SAFETY: We know that device holds a valid/open switchtec device
-        unsafe {
-            let len = switchtec_get_fw_version(self.inner, buf.as_mut_ptr() as *mut _, buf_size);
+        // Long enough for any version string seen so far, but doubled (up to a limit) if the
+        // version fills the buffer and may have been truncated
+        const initial_buf_size: usize = 64;
+        const max_buf_size: usize = 1024;
+
+        let mut buf_size = initial_buf_size;
+        loop {
+            let mut buf = vec![0u8; buf_size];
+            // SAFETY: We know that device holds a valid/open switchtec device, and `buf` holds
+            // `buf_size` bytes for the version to be written into
+            let len = unsafe {
+                traced!(switchtec_get_fw_version(
+                    self.inner,
+                    buf.as_mut_ptr() as *mut _,
+                    buf_size
+                ))
+            };
             if len.is_negative() {
-                Err(get_switchtec_error())
-            } else {
-                buf_to_string(&buf.assume_init())
+                return Err(get_switchtec_error());
+            }
+            if !may_be_truncated(&buf) {
+                return buf_to_string(&buf);
             }
+            if buf_size >= max_buf_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("firmware version is longer than {max_buf_size} bytes"),
+                ));
+            }
+            buf_size *= 2;
         }
     }
 
     /// Get the PCIe generation of the device
     ///
+    /// Use [`SwitchtecDevice::try_generation`] to get an error for an unknown generation, or
+    /// convert the result [`Into`] a raw [`switchtec_gen`](type@switchtec_gen) to pass to the library
+    ///
+    /// See [`SwitchtecDevice::info`] to get this along with the rest of the device's details
+    ///
     /// <https://microsemi.github.io/switchtec-user/group__Device.html#gab9f59d48c410e8dde13acdc519943a26>
-    pub fn generation(&self) -> switchtec_gen {
-        // SAFETY: We know that device holds a valid/open switchtec device
-        unsafe { switchtec_gen(self.inner) }
+    pub fn generation(&self) -> Generation {
+        *self.cache.generation.get_or_init(|| {
+            // SAFETY: We know that device holds a valid/open switchtec device
+            unsafe { switchtec_gen(self.inner) }.into()
+        })
     }
 
     /// Get the partition of the device
     ///
+    /// Use [`SwitchtecDevice::try_partition`] to get an error for an invalid partition number
+    ///
+    /// See [`SwitchtecDevice::info`] to get this along with the rest of the device's details
+    ///
     /// <https://microsemi.github.io/switchtec-user/group__Device.html#gac70f47bb86ac6ba1666446f27673cdcf>
     pub fn partition(&self) -> i32 {
-        // SAFETY: We know that device holds a valid/open switchtec device
-        unsafe { switchtec_partition(self.inner) }
+        *self.cache.partition.get_or_init(|| {
+            // SAFETY: We know that device holds a valid/open switchtec device
+            unsafe { switchtec_partition(self.inner) }
+        })
     }
 
-    /// Get the die temperature of the switchtec device (in Celcius)
+    /// Get the die temperature of the switchtec device
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let temp = device.die_temp()?;
+    /// println!("Temperature: {temp} ({:.1}°F)", temp.fahrenheit());
+    /// # Ok(())
+    /// # }
+    /// ```
     ///
     /// <https://microsemi.github.io/switchtec-user/group__Misc.html#ga56317f0a31a83eb896e4a987dbd645df>
-    pub fn die_temp(&self) -> io::Result<f32> {
+    pub fn die_temp(&self) -> io::Result<Temperature> {
         // SAFETY: We know that device holds a valid/open switchtec device
-        let temp = unsafe { switchtec_die_temp(self.inner) };
+        let temp = unsafe { traced!(switchtec_die_temp(self.inner)) };
         if temp.is_sign_negative() {
             // Negative value represents an error
             // https://microsemi.github.io/switchtec-user/group__Misc.html#ga56317f0a31a83eb896e4a987dbd645df
             return Err(get_switchtec_error());
         }
-        Ok(temp)
+        // `switchtec_die_temp` has already scaled the firmware's reading to degrees Celsius
+        Ok(Temperature::from_celsius(temp))
+    }
+
+    /// Send `value` to the firmware and check that it echoes back the bitwise complement
+    ///
+    /// This is a cheap way to check that the management path to the firmware is working.
+    /// A failure to send the command is reported as the underlying MRPC error, while a reply
+    /// that isn't `!value` is reported as an [`InvalidData`](io::ErrorKind::InvalidData) error.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let reply = device.echo(0xdeadbeef)?;
+    /// assert_eq!(reply, !0xdeadbeef);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Misc.html>
+    pub fn echo(&self, value: u32) -> io::Result<u32> {
+        let mut reply: u32 = 0;
+        // SAFETY: We know that device holds a valid/open switchtec device, and `reply` is a
+        // valid u32 to be populated
+        let ret = unsafe { traced!(switchtec_echo(self.inner, value, &mut reply)) };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        if reply != !value {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "echo of {value:#010x} returned {reply:#010x}, expected {:#010x}",
+                    !value
+                ),
+            ));
+        }
+        Ok(reply)
+    }
+
+    /// Check whether the device is ready to run MRPC commands
+    ///
+    /// A device can be opened while it's still in one of its boot loaders, which only handle a
+    /// small subset of commands. This returns `true` only when the device is running its main
+    /// firmware ([`BootPhase::Firmware`]) and answers an [`echo`](SwitchtecDevice::echo), so
+    /// health checks can tell "opened" apart from "can actually run commands".
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// if !device.mrpc_available() {
+    ///     let phase = device.boot_phase();
+    ///     eprintln!("Device is not ready for commands (boot phase: {phase})");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mrpc_available(&self) -> bool {
+        self.boot_phase() == BootPhase::Firmware && self.echo(0x5a5a_a5a5).is_ok()
+    }
+
+    /// Get the last error set by the library, with its message prefixed by `context`
+    ///
+    /// This is the equivalent of `switchtec_perror` for code calling the raw bindings, but
+    /// returns the error (carrying a [`SwitchtecError`]) instead of printing it. Like
+    /// [`SwitchtecError::last`], it must be called directly after the failing library call.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{switchtec_die_temp, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// // SAFETY: We know that device holds a valid/open switchtec device
+    /// let temp = unsafe { switchtec_die_temp(*device) };
+    /// if temp < 0.0 {
+    ///     return Err(device.perror("die_temp").into());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn perror(&self, context: &str) -> io::Error {
+        SwitchtecError::last().with_context(context).into()
+    }
+
+    /// Perform a hard reset of the Switchtec device
+    ///
+    /// The whole switch is reset, which tears down this device handle along with the links
+    /// through the switch, so this consumes the `SwitchtecDevice`. The device can be opened
+    /// again once it has come back up.
+    ///
+    /// Since the management link drops during the reset, the command may report an error
+    /// (typically EIO or ENODEV) even though the reset was issued successfully.
+    ///
+    /// ```no_run
+    /// use std::{thread, time::Duration};
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// device.hard_reset()?;
+    ///
+    /// thread::sleep(Duration::from_secs(5));
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Device.html>
+    pub fn hard_reset(self) -> io::Result<()> {
+        // SAFETY: We know that device holds a valid/open switchtec device
+        let ret = unsafe { traced!(switchtec_hard_reset(self.inner)) };
+        if ret != 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(())
     }
 }
 
 impl fmt::Debug for SwitchtecDevice {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The transport is always available, even if the name can't be read from the device
         f.debug_struct("SwitchtecDevice")
+            .field("transport", &self.transport)
             .field("name", &self.name().as_deref().unwrap_or("unknown"))
             .finish()
     }
 }
 
+impl fmt::Display for SwitchtecDevice {
+    /// Display a one-line summary of the device, E.g.
+    /// `pciswitch0 (Gen4, partition 0, fw 3.70 B04F, 53.2°C)`
+    ///
+    /// Each field is read from the device, and shown as "unknown" if it can't be read
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn or_unknown<T: fmt::Display>(value: io::Result<T>) -> String {
+            value.map_or_else(|_| "unknown".to_owned(), |value| value.to_string())
+        }
+
+        write!(
+            f,
+            "{} ({}, partition {}, fw {}, {})",
+            or_unknown(self.name()),
+            or_unknown(self.try_generation()),
+            or_unknown(self.try_partition()),
+            or_unknown(self.firmware_version()),
+            or_unknown(self.die_temp()),
+        )
+    }
+}
+
 impl std::ops::Deref for SwitchtecDevice {
     type Target = *mut switchtec_dev;
 
@@ -159,10 +562,10 @@ impl std::ops::Deref for SwitchtecDevice {
 
 impl std::ops::Drop for SwitchtecDevice {
     fn drop(&mut self) {
-        // SAFETY: SwitchtecDevice is only successfully constructed if the `inner` `switchtec_dev`
-        // is not null;
+        // SAFETY: SwitchtecDevice is only constructed (by `from_handle`) if the `inner`
+        // `switchtec_dev` is not null, and `inner` is only ever replaced by another open handle
         unsafe {
-            switchtec_close(self.inner);
+            traced!(switchtec_close(self.inner));
         }
     }
 }
@@ -172,19 +575,26 @@ pub trait CStrExt {
     ///
     /// Returns an [`io::Error`] if the string pointer is null or cannot be
     fn as_string(&self) -> io::Result<String>;
+
+    /// Convert a C-style string (E.g. `char*`) to a Rust [`String`], replacing any invalid
+    /// UTF-8 sequences with `U+FFFD REPLACEMENT CHARACTER`
+    ///
+    /// Returns an empty string if the string pointer is null
+    fn as_string_lossy(&self) -> String;
 }
 
-impl CStrExt for *const i8 {
-    /// Copy a C-style `*const i8` string to a [`String`]
+impl CStrExt for *const c_char {
+    /// Copy a C-style `*const c_char` string to a [`String`]
     ///
     /// ```
     /// use switchtec_user_sys::CStrExt;
     /// # use std::ffi::CString;
+    /// use std::os::raw::c_char;
     ///
     /// # fn main() -> anyhow::Result<()> {
     /// let cstr = CString::new(*b"hello")?;
     /// // This is a type you might receive from an extern "C" function:
-    /// let str_value: *const i8 = cstr.as_ptr() as *const i8;
+    /// let str_value: *const c_char = cstr.as_ptr();
     ///
     /// let rust_string: String = str_value.as_string()?;
     /// assert_eq!(&rust_string, "hello");
@@ -195,19 +605,40 @@ impl CStrExt for *const i8 {
     fn as_string(&self) -> io::Result<String> {
         cstr_to_string(*self)
     }
+
+    /// Copy a C-style `*const c_char` string to a [`String`], replacing invalid UTF-8
+    ///
+    /// ```
+    /// use switchtec_user_sys::CStrExt;
+    /// # use std::ffi::CString;
+    /// use std::os::raw::c_char;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let cstr = CString::new(*b"caf\xe9")?;
+    /// let str_value: *const c_char = cstr.as_ptr();
+    ///
+    /// assert_eq!(&str_value.as_string_lossy(), "caf\u{FFFD}");
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn as_string_lossy(&self) -> String {
+        cstr_to_string_lossy(*self)
+    }
 }
 
-impl CStrExt for *mut i8 {
-    /// Copy a C-style `*mut i8` string to a [`String`]
+impl CStrExt for *mut c_char {
+    /// Copy a C-style `*mut c_char` string to a [`String`]
     ///
     /// ```
     /// use switchtec_user_sys::CStrExt;
     /// # use std::ffi::CString;
+    /// use std::os::raw::c_char;
     ///
     /// # fn main() -> anyhow::Result<()> {
     /// let cstr = CString::new(*b"hello")?;
     /// // This is a type you might receive from an extern "C" function:
-    /// let str_value: *mut i8 = cstr.as_ptr() as *mut i8;
+    /// let str_value: *mut c_char = cstr.as_ptr() as *mut c_char;
     ///
     /// let rust_string: String = str_value.as_string()?;
     /// assert_eq!(&rust_string, "hello");
@@ -218,25 +649,51 @@ impl CStrExt for *mut i8 {
     fn as_string(&self) -> io::Result<String> {
         cstr_to_string(*self)
     }
+
+    /// Copy a C-style `*mut c_char` string to a [`String`], replacing invalid UTF-8
+    ///
+    /// ```
+    /// use switchtec_user_sys::CStrExt;
+    /// # use std::ffi::CString;
+    /// use std::os::raw::c_char;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let cstr = CString::new(*b"caf\xe9")?;
+    /// let str_value: *mut c_char = cstr.as_ptr() as *mut c_char;
+    ///
+    /// assert_eq!(&str_value.as_string_lossy(), "caf\u{FFFD}");
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn as_string_lossy(&self) -> String {
+        cstr_to_string_lossy(*self)
+    }
 }
 
-fn cstr_to_string(cstr: *const i8) -> io::Result<String> {
+fn cstr_to_string(cstr: *const c_char) -> io::Result<String> {
     if cstr.is_null() {
         Ok("".to_owned())
     } else {
         // SAFETY: cstr has been checked for null, we can safely dereference
         unsafe {
-            let s = CStr::from_ptr(cstr).to_owned();
-            s.into_string().map_err(|e| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("error decoding String from {cstr:?}: {e}"),
-                )
-            })
+            let s = CStr::from_ptr(cstr)
+                .to_str()
+                .map_err(SwitchtecError::from)?;
+            Ok(s.to_owned())
         }
     }
 }
 
+fn cstr_to_string_lossy(cstr: *const c_char) -> String {
+    if cstr.is_null() {
+        String::new()
+    } else {
+        // SAFETY: cstr has been checked for null, we can safely dereference
+        unsafe { CStr::from_ptr(cstr).to_string_lossy().into_owned() }
+    }
+}
+
 /// Parse a String from a buffer that may have tail-padding
 fn buf_to_string(buf: &[u8]) -> io::Result<String> {
     let valid_bytes: Vec<u8> = buf
@@ -249,19 +706,35 @@ fn buf_to_string(buf: &[u8]) -> io::Result<String> {
     cstring.into_raw().as_string()
 }
 
+/// Check if a null-terminated string written to `buf` (E.g. with `snprintf`) may have been
+/// truncated, which is the case when it fills every byte before the terminator
+fn may_be_truncated(buf: &[u8]) -> bool {
+    match buf.iter().position(|b| *b == 0) {
+        Some(len) => len + 1 >= buf.len(),
+        None => true,
+    }
+}
+
+/// Parse a String from a C `char` array (E.g. `char version[32]`) that may have tail-padding
+fn char_buf_to_string(buf: &[c_char]) -> io::Result<String> {
+    let bytes: Vec<u8> = buf.iter().map(|&c| c as u8).collect();
+    buf_to_string(&bytes)
+}
+
+/// Parse a String from a C `char` array that may have tail-padding, replacing any invalid
+/// UTF-8 sequences with `U+FFFD REPLACEMENT CHARACTER`
+fn char_buf_to_string_lossy(buf: &[c_char]) -> String {
+    let bytes: Vec<u8> = buf
+        .iter()
+        .map(|&c| c as u8)
+        .take_while(|&b| b != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Get the last error set by the library as an [`io::Error`] carrying a [`SwitchtecError`]
 fn get_switchtec_error() -> io::Error {
-    // SAFETY: We're checking that the returned char* is not null
-    let err_message = unsafe {
-        // https://microsemi.github.io/switchtec-user/group__Device.html#ga595e1d62336ba76c59344352c334fa18
-        let err_str = switchtec_strerror();
-        if err_str.is_null() {
-            return io::Error::new(io::ErrorKind::Other, "Unknown error".to_owned());
-        }
-        err_str
-            .as_string()
-            .unwrap_or_else(|_| "Unknown error".to_owned())
-    };
-    io::Error::new(io::ErrorKind::Other, err_message)
+    SwitchtecError::last().into()
 }
 
 #[test]
@@ -269,3 +742,70 @@ fn test_buf_to_string() {
     let buf = [51, 46, 55, 48, 32, 66, 48, 52, 70, 0, 0, 0, 0, 0, 0, 0];
     assert_eq!(&buf_to_string(&buf).unwrap(), "3.70 B04F");
 }
+
+#[test]
+fn test_char_buf_to_string_lossy() {
+    let buf = [80, 70, 88, -1, 0, 0].map(|b: i8| b as c_char);
+    assert_eq!(char_buf_to_string_lossy(&buf), "PFX\u{FFFD}");
+}
+
+#[test]
+fn test_may_be_truncated() {
+    // Room to spare after the terminator
+    assert!(!may_be_truncated(b"3.70 B04F\0\0\0"));
+    // Exact fit: the string fills the buffer up to the terminator, so it may have been cut off
+    assert!(may_be_truncated(b"3.70 B04F\0"));
+    // Overflow: no terminator at all
+    assert!(may_be_truncated(b"3.70 B04F"));
+    assert!(may_be_truncated(b""));
+}
+
+#[test]
+fn test_cstr_to_string_lossy() {
+    let cstr = CString::new(*b"B04\xff").unwrap();
+    assert!(cstr_to_string(cstr.as_ptr()).is_err());
+    assert_eq!(cstr_to_string_lossy(cstr.as_ptr()), "B04\u{FFFD}");
+    assert_eq!(cstr_to_string_lossy(std::ptr::null()), "");
+}
+
+#[test]
+fn test_from_raw_null() {
+    // SAFETY: A null handle is never adopted
+    let device = unsafe { SwitchtecDevice::from_raw(ptr::null_mut(), PathBuf::new()) };
+    assert!(device.is_none());
+}
+
+#[test]
+fn test_from_handle_null() {
+    // Every constructor goes through `from_handle`, so a null handle can never reach `Drop`
+    // (and `switchtec_close`), whichever transport it was opened with
+    for transport in [
+        Transport::Path(PathBuf::from("/dev/pciswitch0")),
+        Transport::I2c {
+            path: PathBuf::from("/dev/i2c-1"),
+            addr: 0x10,
+        },
+        Transport::Uart(PathBuf::from("/dev/ttyUSB0")),
+        Transport::Eth {
+            host: "10.0.0.10".to_owned(),
+            instance: 0,
+        },
+    ] {
+        // SAFETY: A null handle is never adopted
+        let device = unsafe { SwitchtecDevice::from_handle(ptr::null_mut(), transport) };
+        assert!(device.is_none());
+    }
+}
+
+#[test]
+fn test_open_bad_path() {
+    // A failed open returns an error without constructing a device, so nothing is closed
+    let err = SwitchtecDevice::open("/dev/pciswitch-does-not-exist").unwrap_err();
+    assert!(matches!(
+        SwitchtecError::from_io(&err),
+        Some(SwitchtecError::Open { path, .. }) if path == Path::new("/dev/pciswitch-does-not-exist")
+    ));
+
+    // Paths that can't be passed to the library fail before it's called
+    assert!(SwitchtecDevice::open("/dev/pciswitch\0").is_err());
+}
@@ -0,0 +1,334 @@
+use std::fmt;
+
+/// Negotiated link rate of a port, as reported in [`PortStatus`](crate::PortStatus)
+///
+/// ```
+/// use switchtec_user_sys::{LinkRate, LinkWidth};
+///
+/// let rate = LinkRate::from(4);
+/// assert_eq!(rate, LinkRate::Gen4);
+/// assert_eq!(rate.to_string(), "16 GT/s");
+/// assert_eq!(rate.generation(), Some(4));
+/// assert_eq!(LinkRate::from(0), LinkRate::Down);
+/// println!("{:.1} Gb/s", rate.gbps(LinkWidth::X16));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LinkRate {
+    /// The link is down or still training, so no rate has been negotiated
+    Down,
+    /// PCIe Gen1, 2.5 GT/s
+    Gen1,
+    /// PCIe Gen2, 5 GT/s
+    Gen2,
+    /// PCIe Gen3, 8 GT/s
+    Gen3,
+    /// PCIe Gen4, 16 GT/s
+    Gen4,
+    /// PCIe Gen5, 32 GT/s
+    Gen5,
+    /// PCIe Gen6, 64 GT/s
+    Gen6,
+    /// A raw rate this crate doesn't know about
+    Unknown(u8),
+}
+
+impl LinkRate {
+    /// Get the PCIe generation of the rate, or `None` if the link is down or the rate unknown
+    pub const fn generation(&self) -> Option<u8> {
+        match self {
+            Self::Gen1 => Some(1),
+            Self::Gen2 => Some(2),
+            Self::Gen3 => Some(3),
+            Self::Gen4 => Some(4),
+            Self::Gen5 => Some(5),
+            Self::Gen6 => Some(6),
+            Self::Down | Self::Unknown(_) => None,
+        }
+    }
+
+    /// Get the raw transfer rate of a single lane in GT/s, or `0.0` if the link is down or the
+    /// rate unknown
+    pub fn transfers(&self) -> f64 {
+        match self {
+            Self::Gen1 => 2.5,
+            Self::Gen2 => 5.0,
+            Self::Gen3 => 8.0,
+            Self::Gen4 => 16.0,
+            Self::Gen5 => 32.0,
+            Self::Gen6 => 64.0,
+            Self::Down | Self::Unknown(_) => 0.0,
+        }
+    }
+
+    /// Get the usable bandwidth of a link at this rate with `width` lanes, in Gb/s per direction
+    ///
+    /// This accounts for the line encoding (8b/10b for Gen1 and Gen2, 128b/130b from Gen3),
+    /// but not for packet overhead
+    pub fn gbps(&self, width: LinkWidth) -> f64 {
+        let encoding = match self {
+            Self::Gen1 | Self::Gen2 => 8.0 / 10.0,
+            _ => 128.0 / 130.0,
+        };
+        self.transfers() * encoding * f64::from(width.lanes())
+    }
+}
+
+impl From<u8> for LinkRate {
+    fn from(rate: u8) -> Self {
+        match rate {
+            0 => Self::Down,
+            1 => Self::Gen1,
+            2 => Self::Gen2,
+            3 => Self::Gen3,
+            4 => Self::Gen4,
+            5 => Self::Gen5,
+            6 => Self::Gen6,
+            rate => Self::Unknown(rate),
+        }
+    }
+}
+
+impl fmt::Display for LinkRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Down => write!(f, "down"),
+            Self::Unknown(rate) => write!(f, "unknown rate ({rate})"),
+            rate => write!(f, "{} GT/s", rate.transfers()),
+        }
+    }
+}
+
+/// Link width (number of lanes) of a port, as reported in [`PortStatus`](crate::PortStatus)
+///
+/// ```
+/// use switchtec_user_sys::LinkWidth;
+///
+/// let width = LinkWidth::from(16);
+/// assert_eq!(width, LinkWidth::X16);
+/// assert_eq!(width.to_string(), "x16");
+/// assert_eq!(LinkWidth::from(0), LinkWidth::Down);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LinkWidth {
+    /// The link is down or still training, so no width has been negotiated
+    Down,
+    /// 1 lane
+    X1,
+    /// 2 lanes
+    X2,
+    /// 4 lanes
+    X4,
+    /// 8 lanes
+    X8,
+    /// 16 lanes
+    X16,
+    /// Any other number of lanes
+    Other(u8),
+}
+
+impl LinkWidth {
+    /// Get the number of lanes, or `0` if the link is down
+    pub const fn lanes(&self) -> u8 {
+        match self {
+            Self::Down => 0,
+            Self::X1 => 1,
+            Self::X2 => 2,
+            Self::X4 => 4,
+            Self::X8 => 8,
+            Self::X16 => 16,
+            Self::Other(lanes) => *lanes,
+        }
+    }
+}
+
+impl From<u8> for LinkWidth {
+    fn from(lanes: u8) -> Self {
+        match lanes {
+            0 => Self::Down,
+            1 => Self::X1,
+            2 => Self::X2,
+            4 => Self::X4,
+            8 => Self::X8,
+            16 => Self::X16,
+            lanes => Self::Other(lanes),
+        }
+    }
+}
+
+impl fmt::Display for LinkWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Down => write!(f, "down"),
+            width => write!(f, "x{}", width.lanes()),
+        }
+    }
+}
+
+/// Major state of a port's LTSSM (Link Training and Status State Machine), see
+/// [`PortStatus::ltssm_state`](crate::PortStatus::ltssm_state)
+///
+/// A healthy link settles in [`L0`](LtssmState::L0) (or one of its power saving states), so a
+/// port that keeps showing up in [`Polling`](LtssmState::Polling) or
+/// [`Recovery`](LtssmState::Recovery) is having trouble training.
+///
+/// ```
+/// use switchtec_user_sys::LtssmState;
+///
+/// let state = LtssmState::from("Recovery (RCVRLOCK)");
+/// assert_eq!(state, LtssmState::Recovery);
+/// assert!(state.is_training());
+/// assert_eq!(state.to_string(), "Recovery");
+/// assert_eq!(LtssmState::from("L0 (L0)"), LtssmState::L0);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LtssmState {
+    /// Looking for a link partner
+    Detect,
+    /// Exchanging training sets to establish bit and symbol lock
+    Polling,
+    /// Negotiating the link width and lane numbers
+    Configuration,
+    /// The link is up and exchanging packets
+    L0,
+    /// Retraining the link, E.g. to change its rate or after errors
+    Recovery,
+    /// The link is disabled
+    Disabled,
+    /// The link is in loopback (E.g. for diagnostics)
+    Loopback,
+    /// The link is being reset in-band
+    HotReset,
+    /// Transmitter side of the L0s low power state
+    TxL0s,
+    /// L1 low power state
+    L1,
+    /// L2 low power state
+    L2,
+    /// A state this crate doesn't know about, see [`PortStatus::ltssm`](crate::PortStatus::ltssm)
+    /// or [`LtssmLogEntry::raw_state`](crate::diag::LtssmLogEntry::raw_state) for the raw state
+    Unknown,
+}
+
+impl LtssmState {
+    /// Get the name of the state (E.g. "Recovery")
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Detect => "Detect",
+            Self::Polling => "Polling",
+            Self::Configuration => "Configuration",
+            Self::L0 => "L0",
+            Self::Recovery => "Recovery",
+            Self::Disabled => "Disabled",
+            Self::Loopback => "Loopback",
+            Self::HotReset => "Hot Reset",
+            Self::TxL0s => "TxL0s",
+            Self::L1 => "L1",
+            Self::L2 => "L2",
+            Self::Unknown => "Unknown",
+        }
+    }
+
+    /// Check if the link is (re)training: in Detect, Polling, Configuration or Recovery
+    pub const fn is_training(&self) -> bool {
+        matches!(
+            self,
+            Self::Detect | Self::Polling | Self::Configuration | Self::Recovery
+        )
+    }
+
+    /// Get the state from the major LTSSM code reported by Gen4 and newer switches (E.g. in
+    /// the LTSSM log), which is in the low byte of `code`
+    pub(crate) fn from_code(code: i32) -> Self {
+        match code & 0xff {
+            0 => Self::Detect,
+            1 => Self::Polling,
+            2 => Self::Configuration,
+            3 => Self::L0,
+            4 => Self::Recovery,
+            5 => Self::Disabled,
+            6 => Self::Loopback,
+            7 => Self::HotReset,
+            8 => Self::TxL0s,
+            9 => Self::L1,
+            10 => Self::L2,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl From<&str> for LtssmState {
+    /// Parse the LTSSM string reported by the library (E.g. "Config (LINKWD_START)"), which
+    /// is the major state optionally followed by the minor state in parentheses
+    fn from(ltssm: &str) -> Self {
+        let major = ltssm.split('(').next().unwrap_or_default().trim();
+        match major.to_ascii_lowercase().as_str() {
+            "detect" => Self::Detect,
+            "polling" => Self::Polling,
+            "config" | "configuration" => Self::Configuration,
+            "l0" => Self::L0,
+            "recovery" => Self::Recovery,
+            "disable" | "disabled" => Self::Disabled,
+            "loopback" => Self::Loopback,
+            "hot reset" | "hotreset" => Self::HotReset,
+            "txl0s" | "l0s" => Self::TxL0s,
+            "l1" => Self::L1,
+            "l2" => Self::L2,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for LtssmState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[test]
+fn test_link_rate_and_width() {
+    for raw in 0..=u8::MAX {
+        let rate = LinkRate::from(raw);
+        assert_eq!(rate.generation().unwrap_or(raw), raw);
+        assert_eq!(LinkWidth::from(raw).lanes(), raw);
+    }
+    assert_eq!(LinkRate::from(9), LinkRate::Unknown(9));
+    assert_eq!(LinkRate::Gen1.to_string(), "2.5 GT/s");
+    assert_eq!(LinkRate::Down.to_string(), "down");
+    assert_eq!(LinkWidth::from(12), LinkWidth::Other(12));
+    assert_eq!(LinkWidth::Other(12).to_string(), "x12");
+
+    assert_eq!(LinkRate::Gen2.gbps(LinkWidth::X4), 16.0);
+    assert!((LinkRate::Gen4.gbps(LinkWidth::X16) - 252.06).abs() < 0.01);
+    assert_eq!(LinkRate::Gen4.gbps(LinkWidth::Down), 0.0);
+    assert_eq!(LinkRate::Down.gbps(LinkWidth::X16), 0.0);
+}
+
+#[test]
+fn test_ltssm_state() {
+    for (raw, state) in [
+        ("Detect (INACTIVE)", LtssmState::Detect),
+        ("Polling (ACTIVE)", LtssmState::Polling),
+        ("Config (LINKWD_START)", LtssmState::Configuration),
+        ("L0 (L0)", LtssmState::L0),
+        ("L0", LtssmState::L0),
+        ("Recovery (RCVRLOCK)", LtssmState::Recovery),
+        ("Disable (DISABLE_0)", LtssmState::Disabled),
+        ("Loopback (ENTRY)", LtssmState::Loopback),
+        ("Hot Reset (HOT_RESET)", LtssmState::HotReset),
+        ("TxL0s (IDLE)", LtssmState::TxL0s),
+        ("L1 (IDLE)", LtssmState::L1),
+        ("L2 (IDLE)", LtssmState::L2),
+        ("Unknown", LtssmState::Unknown),
+        ("", LtssmState::Unknown),
+    ] {
+        assert_eq!(LtssmState::from(raw), state, "{raw}");
+    }
+    assert!(!LtssmState::L0.is_training());
+    assert_eq!(LtssmState::from_code(0x0403), LtssmState::L0);
+    assert_eq!(LtssmState::from_code(4), LtssmState::Recovery);
+    assert_eq!(LtssmState::from_code(0x20), LtssmState::Unknown);
+    assert_eq!(LtssmState::HotReset.to_string(), "Hot Reset");
+}
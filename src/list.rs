@@ -0,0 +1,218 @@
+use std::io;
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::ffi::{switchtec_list, switchtec_list_free};
+use crate::{
+    char_buf_to_string, char_buf_to_string_lossy, get_switchtec_error, switchtec_device_info,
+    SwitchtecDevice,
+};
+
+/// Details for a Switchtec device discovered by [`SwitchtecDevice::list`]
+///
+/// This is an owned copy of the `switchtec_device_info` struct returned by `switchtec_list`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceInfo {
+    /// Device name (E.g. "pciswitch0")
+    pub name: String,
+    /// Device description, if available
+    pub description: String,
+    /// PCI BDF of the device (E.g. "0000:03:00.1")
+    pub pci_dev: String,
+    /// Product ID
+    pub product_id: String,
+    /// Product revision
+    pub product_rev: String,
+    /// Firmware version
+    pub fw_version: String,
+    /// Path to the device (E.g. "/dev/pciswitch0")
+    pub path: PathBuf,
+}
+
+impl TryFrom<&switchtec_device_info> for DeviceInfo {
+    type Error = io::Error;
+
+    fn try_from(info: &switchtec_device_info) -> io::Result<Self> {
+        Ok(Self {
+            name: char_buf_to_string(&info.name)?,
+            description: char_buf_to_string(&info.desc)?,
+            pci_dev: char_buf_to_string(&info.pci_dev)?,
+            product_id: char_buf_to_string(&info.product_id)?,
+            product_rev: char_buf_to_string(&info.product_rev)?,
+            fw_version: char_buf_to_string(&info.fw_version)?,
+            path: char_buf_to_string(&info.path)?.into(),
+        })
+    }
+}
+
+impl SwitchtecDevice {
+    /// List the Switchtec devices available on this system
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// for info in SwitchtecDevice::list()? {
+    ///     println!("{} ({}): {}", info.name, info.pci_dev, info.path.display());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Device.html>
+    pub fn list() -> io::Result<Vec<DeviceInfo>> {
+        with_device_list(|devices| devices.iter().map(DeviceInfo::try_from).collect())?
+    }
+
+    /// Open every Switchtec device listed by [`SwitchtecDevice::list`]
+    ///
+    /// Each device is opened separately, so one that can't be opened (E.g. because of its
+    /// permissions) doesn't stop the rest from being opened. A device that fails to open is
+    /// returned as a [`SwitchtecError::Open`](crate::SwitchtecError::Open) error carrying its
+    /// path. Only failing to list the devices returns an error for the whole call.
+    ///
+    /// Every returned device is an open handle owned by the caller, and is closed when it's
+    /// dropped. Keeping them all around holds every switch open at once, so drop the ones
+    /// that aren't needed.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// for device in SwitchtecDevice::open_all()? {
+    ///     match device {
+    ///         Ok(device) => println!("{}: {}", device.name()?, device.die_temp()?),
+    ///         Err(e) => eprintln!("{e}"),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_all() -> io::Result<Vec<io::Result<Self>>> {
+        Ok(Self::list()?
+            .iter()
+            .map(|info| Self::open(&info.path))
+            .collect())
+    }
+
+    /// Open the Switchtec device at the given PCI address (E.g. "0000:03:00.1")
+    ///
+    /// The address is matched against the devices from [`SwitchtecDevice::list`], ignoring
+    /// case. The PCI domain can be left out (E.g. "03:00.1"), in which case domain 0 is
+    /// assumed. Returns an [`io::ErrorKind::NotFound`] error if no device has that address.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open_by_pci("0000:03:00.1")?;
+    /// println!("Opened {}", device.name()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_by_pci(bdf: &str) -> io::Result<Self> {
+        let devices = Self::list()?;
+        let info = find_by_pci(&devices, bdf).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no switchtec device at PCI address {bdf}"),
+            )
+        })?;
+        Self::open(&info.path)
+    }
+
+    /// Get the SKU (product ID) of the device, as listed by [`SwitchtecDevice::list`]
+    ///
+    /// The product ID is read from the host's view of the device (E.g. sysfs on Linux), so
+    /// this is only available for devices opened locally over PCIe. Unlike
+    /// [`SwitchtecDevice::list`], invalid UTF-8 in the product ID is replaced rather than
+    /// returned as an error. Returns an [`io::ErrorKind::NotFound`] error if the device isn't
+    /// listed.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// println!("SKU: {}", device.sku()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sku(&self) -> io::Result<String> {
+        let name = self.name()?;
+        with_device_list(|devices| {
+            devices
+                .iter()
+                .find(|info| char_buf_to_string_lossy(&info.name) == name)
+                .map(|info| char_buf_to_string_lossy(&info.product_id).trim().to_owned())
+        })?
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{name} not found in the device list"),
+            )
+        })
+    }
+}
+
+/// Find the device with the PCI address `bdf`, assuming domain 0 if it's left out
+fn find_by_pci<'a>(devices: &'a [DeviceInfo], bdf: &str) -> Option<&'a DeviceInfo> {
+    let bdf = bdf.trim();
+    // A full address has a domain, bus and device separated by ':' (E.g. "0000:03:00.1")
+    let bdf = if bdf.matches(':').count() == 1 {
+        format!("0000:{bdf}")
+    } else {
+        bdf.to_owned()
+    };
+    devices
+        .iter()
+        .find(|info| info.pci_dev.eq_ignore_ascii_case(&bdf))
+}
+
+/// Call `f` with the devices listed by `switchtec_list`, freeing the list afterwards
+fn with_device_list<T>(f: impl FnOnce(&[switchtec_device_info]) -> T) -> io::Result<T> {
+    let mut devlist: *mut switchtec_device_info = ptr::null_mut();
+
+    // SAFETY: We're checking that the returned list is not null, and the `count` resp
+    // provides how many `switchtec_device_info` structs are present in the data
+    unsafe {
+        let count = traced!(switchtec_list(ptr::addr_of_mut!(devlist)));
+        if count.is_negative() {
+            return Err(get_switchtec_error());
+        }
+        if devlist.is_null() {
+            return Ok(f(&[]));
+        }
+        let result = f(std::slice::from_raw_parts(devlist, count as usize));
+
+        // Must be called after switchtec_list to free the allocated device list
+        switchtec_list_free(devlist);
+
+        Ok(result)
+    }
+}
+
+#[test]
+fn test_find_by_pci() {
+    let device = |name: &str, pci_dev: &str| DeviceInfo {
+        name: name.to_owned(),
+        description: String::new(),
+        pci_dev: pci_dev.to_owned(),
+        product_id: String::new(),
+        product_rev: String::new(),
+        fw_version: String::new(),
+        path: PathBuf::from(format!("/dev/{name}")),
+    };
+    let devices = [
+        device("pciswitch0", "0000:03:00.1"),
+        device("pciswitch1", "0001:0a:00.1"),
+    ];
+
+    let find = |bdf| find_by_pci(&devices, bdf).map(|info| info.name.as_str());
+    assert_eq!(find("0000:03:00.1"), Some("pciswitch0"));
+    assert_eq!(find("03:00.1"), Some("pciswitch0"));
+    assert_eq!(find("0001:0A:00.1"), Some("pciswitch1"));
+    assert_eq!(find("0a:00.1"), None);
+    assert_eq!(find("0000:03:00.0"), None);
+}
@@ -0,0 +1,322 @@
+//! Read-only access to the secure boot and manufacturing settings of a Switchtec device
+
+use std::io;
+use std::mem::MaybeUninit;
+
+use crate::ffi::{
+    switchtec_debug_mode, switchtec_debug_mode_SWITCHTEC_DEBUG_MODE_DISABLED,
+    switchtec_debug_mode_SWITCHTEC_DEBUG_MODE_DISABLED_BUT_ENABLE_ALLOWED,
+    switchtec_debug_mode_SWITCHTEC_DEBUG_MODE_DISABLED_EXT,
+    switchtec_debug_mode_SWITCHTEC_DEBUG_MODE_ENABLED, switchtec_secure_state,
+    switchtec_secure_state_SWITCHTEC_INITIALIZED_SECURED,
+    switchtec_secure_state_SWITCHTEC_INITIALIZED_UNSECURED,
+    switchtec_secure_state_SWITCHTEC_UNINITIALIZED_UNSECURED, switchtec_security_cfg_state,
+    switchtec_security_config_get, switchtec_sn_ver_get, switchtec_sn_ver_info,
+};
+use crate::{get_switchtec_error, SwitchtecDevice};
+
+/// Secure boot state of a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum SecureState {
+    /// Security settings haven't been provisioned, and secure boot is disabled
+    UninitializedUnsecured,
+    /// Security settings have been provisioned, but secure boot is disabled
+    InitializedUnsecured,
+    /// Security settings have been provisioned, and secure boot is enabled
+    InitializedSecured,
+    /// The firmware reported a state this crate doesn't know about
+    Unknown,
+}
+
+impl From<switchtec_secure_state> for SecureState {
+    fn from(state: switchtec_secure_state) -> Self {
+        match state {
+            switchtec_secure_state_SWITCHTEC_UNINITIALIZED_UNSECURED => {
+                Self::UninitializedUnsecured
+            }
+            switchtec_secure_state_SWITCHTEC_INITIALIZED_UNSECURED => Self::InitializedUnsecured,
+            switchtec_secure_state_SWITCHTEC_INITIALIZED_SECURED => Self::InitializedSecured,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Debug (E.g. JTAG and EJTAG) access mode of a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum DebugMode {
+    /// Debug access is enabled
+    Enabled,
+    /// Debug access is disabled, but can be enabled with a debug unlock
+    DisabledButEnableAllowed,
+    /// Debug access is disabled
+    Disabled,
+    /// Debug access is disabled, including extended debug features
+    DisabledExtended,
+}
+
+impl DebugMode {
+    fn from_raw(mode: switchtec_debug_mode) -> Option<Self> {
+        match mode {
+            switchtec_debug_mode_SWITCHTEC_DEBUG_MODE_ENABLED => Some(Self::Enabled),
+            switchtec_debug_mode_SWITCHTEC_DEBUG_MODE_DISABLED_BUT_ENABLE_ALLOWED => {
+                Some(Self::DisabledButEnableAllowed)
+            }
+            switchtec_debug_mode_SWITCHTEC_DEBUG_MODE_DISABLED => Some(Self::Disabled),
+            switchtec_debug_mode_SWITCHTEC_DEBUG_MODE_DISABLED_EXT => Some(Self::DisabledExtended),
+            _ => None,
+        }
+    }
+}
+
+/// JTAG lock settings of a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct JtagLock {
+    /// JTAG is locked after a reset
+    pub after_reset: bool,
+    /// JTAG is locked after the BL1 boot loader runs
+    pub after_bl1: bool,
+    /// JTAG can be unlocked while BL1 is running
+    pub bl1_unlock_allowed: bool,
+    /// JTAG can be unlocked after BL1 has run
+    pub post_bl1_unlock_allowed: bool,
+}
+
+/// A key manifest (KMSK) slot, returned by [`SwitchtecDevice::kmsk_slots`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct KmskSlot {
+    /// Index of the slot
+    pub index: usize,
+    /// Whether a key manifest has been programmed into the slot
+    pub programmed: bool,
+    /// Whether this is the slot the firmware currently validates images against
+    pub active: bool,
+    /// Hash of the key manifest programmed into the slot, or all zeroes if it's not programmed
+    pub hash: Vec<u8>,
+}
+
+/// The security configuration of a device, returned by [`SwitchtecDevice::security_config`]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SecurityConfig {
+    /// Secure boot state
+    pub secure_state: SecureState,
+    /// Debug access mode, if reported by the firmware
+    pub debug_mode: Option<DebugMode>,
+    /// JTAG lock settings, if reported by the firmware
+    pub jtag_lock: Option<JtagLock>,
+    /// SPI clock rate in MHz, if reported by the firmware
+    pub spi_clock_mhz: Option<f32>,
+}
+
+impl SecurityConfig {
+    /// Check if secure boot is enabled
+    pub fn secure_boot_enabled(&self) -> bool {
+        self.secure_state == SecureState::InitializedSecured
+    }
+}
+
+impl From<&switchtec_security_cfg_state> for SecurityConfig {
+    fn from(state: &switchtec_security_cfg_state) -> Self {
+        let basic_settings_valid = state.basic_setting_valid != 0;
+        Self {
+            secure_state: state.secure_state.into(),
+            debug_mode: if state.debug_mode_valid != 0 {
+                DebugMode::from_raw(state.debug_mode)
+            } else {
+                None
+            },
+            jtag_lock: basic_settings_valid.then_some(JtagLock {
+                after_reset: state.jtag_lock_after_reset != 0,
+                after_bl1: state.jtag_lock_after_bl1 != 0,
+                bl1_unlock_allowed: state.jtag_bl1_unlock_allowed != 0,
+                post_bl1_unlock_allowed: state.jtag_post_bl1_unlock_allowed != 0,
+            }),
+            spi_clock_mhz: basic_settings_valid.then_some(state.spi_clk_rate),
+        }
+    }
+}
+
+impl SwitchtecDevice {
+    /// Get the secure boot and debug configuration of the device
+    ///
+    /// This only reads the configuration, so it doesn't need any keys. Security configuration
+    /// was added in Gen4, so this returns an [`Unsupported`](io::ErrorKind::Unsupported) error
+    /// for Gen3 switches.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let config = device.security_config()?;
+    /// println!("Secure boot enabled: {}", config.secure_boot_enabled());
+    /// println!("Debug mode: {:?}", config.debug_mode);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn security_config(&self) -> io::Result<SecurityConfig> {
+        Ok((&self.security_cfg_state()?).into())
+    }
+
+    /// List the key manifest (KMSK) slots of the device, for auditing secure boot provisioning
+    ///
+    /// This only reads the slots. Like [`SwitchtecDevice::security_config`], it returns an
+    /// [`Unsupported`](io::ErrorKind::Unsupported) error for Gen3 switches.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for slot in device.kmsk_slots()?.iter().filter(|slot| slot.programmed) {
+    ///     let active = if slot.active { " (active)" } else { "" };
+    ///     let hash: String = slot.hash.iter().map(|b| format!("{b:02x}")).collect();
+    ///     println!("KMSK #{}{active}: {hash}", slot.index);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn kmsk_slots(&self) -> io::Result<Vec<KmskSlot>> {
+        Ok(kmsk_slots(&self.security_cfg_state()?))
+    }
+
+    /// Get the unique serial number of the switch chip, as a hex string (E.g. "0x12345678")
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// println!("Chip serial: {}", device.serial_number()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn serial_number(&self) -> io::Result<String> {
+        let mut info = MaybeUninit::<switchtec_sn_ver_info>::zeroed();
+
+        // SAFETY: `info` is only read after the library reports that it was populated
+        let info = unsafe {
+            if traced!(switchtec_sn_ver_get(self.inner, info.as_mut_ptr())) < 0 {
+                return Err(self.perror("serial number"));
+            }
+            info.assume_init()
+        };
+        Ok(serial_number(&info))
+    }
+
+    /// Read the raw `switchtec_security_cfg_state` for the device
+    fn security_cfg_state(&self) -> io::Result<switchtec_security_cfg_state> {
+        if self.is_gen3() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "security configuration is not supported on Gen3 switches",
+            ));
+        }
+
+        let mut state = MaybeUninit::<switchtec_security_cfg_state>::zeroed();
+
+        // SAFETY: `state` is only read after the library reports that it was populated
+        unsafe {
+            if traced!(switchtec_security_config_get(
+                self.inner,
+                state.as_mut_ptr()
+            )) < 0
+            {
+                return Err(get_switchtec_error());
+            }
+            Ok(state.assume_init())
+        }
+    }
+}
+
+/// Format the chip serial number from a `switchtec_sn_ver_info` as a hex string
+fn serial_number(info: &switchtec_sn_ver_info) -> String {
+    format!("{:#010x}", info.chip_serial)
+}
+
+/// Build the KMSK slot list from a `switchtec_security_cfg_state`
+///
+/// The firmware reports how many slots are programmed (filled in order) and the index of the
+/// slot it currently validates against (`public_key_ver`), each only when marked valid.
+fn kmsk_slots(state: &switchtec_security_cfg_state) -> Vec<KmskSlot> {
+    let programmed = if state.public_key_num_valid != 0 && state.public_key_valid != 0 {
+        state.public_key_num as usize
+    } else {
+        0
+    };
+    let active = (state.public_key_ver_valid != 0).then_some(state.public_key_ver as usize);
+    state
+        .public_key
+        .iter()
+        .enumerate()
+        .map(|(index, hash)| KmskSlot {
+            index,
+            programmed: index < programmed,
+            active: active == Some(index),
+            hash: hash.to_vec(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_security_config_from_state() {
+    // SAFETY: `switchtec_security_cfg_state` is plain data, so all zeroes is a valid value
+    let mut state: switchtec_security_cfg_state = unsafe { MaybeUninit::zeroed().assume_init() };
+    state.secure_state = switchtec_secure_state_SWITCHTEC_INITIALIZED_SECURED;
+    state.debug_mode = switchtec_debug_mode_SWITCHTEC_DEBUG_MODE_DISABLED;
+
+    // Nothing is reported until the firmware marks it valid
+    let config = SecurityConfig::from(&state);
+    assert!(config.secure_boot_enabled());
+    assert_eq!(config.debug_mode, None);
+    assert_eq!(config.jtag_lock, None);
+
+    state.debug_mode_valid = 1;
+    state.basic_setting_valid = 1;
+    state.jtag_lock_after_reset = 1;
+    state.spi_clk_rate = 25.0;
+    let config = SecurityConfig::from(&state);
+    assert_eq!(config.debug_mode, Some(DebugMode::Disabled));
+    assert!(config.jtag_lock.unwrap().after_reset);
+    assert_eq!(config.spi_clock_mhz, Some(25.0));
+
+    assert_eq!(SecureState::from(0x42), SecureState::Unknown);
+}
+
+#[test]
+fn test_kmsk_slots() {
+    // SAFETY: `switchtec_security_cfg_state` is plain data, so all zeroes is a valid value
+    let mut state: switchtec_security_cfg_state = unsafe { MaybeUninit::zeroed().assume_init() };
+    state.public_key_num = 2;
+    state.public_key_ver = 1;
+    state.public_key[1][0] = 0xab;
+
+    // Nothing is programmed or active until the firmware marks it valid
+    let slots = kmsk_slots(&state);
+    assert!(slots.iter().all(|slot| !slot.programmed && !slot.active));
+
+    state.public_key_valid = 1;
+    state.public_key_num_valid = 1;
+    state.public_key_ver_valid = 1;
+    let slots = kmsk_slots(&state);
+    assert_eq!(slots.len(), crate::ffi::SWITCHTEC_KMSK_NUM as usize);
+    assert!(slots[0].programmed && !slots[0].active);
+    assert!(slots[1].programmed && slots[1].active);
+    assert_eq!(slots[1].hash[0], 0xab);
+    assert!(!slots[2].programmed);
+}
+
+#[test]
+fn test_serial_number() {
+    // SAFETY: `switchtec_sn_ver_info` is plain data, so all zeroes is a valid value
+    let mut info: switchtec_sn_ver_info = unsafe { MaybeUninit::zeroed().assume_init() };
+    info.chip_serial = 0x1234_abcd;
+    assert_eq!(serial_number(&info), "0x1234abcd");
+    // Leading zeroes are kept, so every serial number is the same length
+    info.chip_serial = 0x42;
+    assert_eq!(serial_number(&info), "0x00000042");
+}
@@ -0,0 +1,278 @@
+use std::ffi::CString;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::mem;
+use std::os::raw::c_int;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::IntoRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ffi::{switchtec_open_eth, switchtec_open_i2c, switchtec_open_uart};
+use crate::{switchtec_dev, PartitionId, SwitchtecDevice, SwitchtecError};
+
+/// Builder for opening a [`SwitchtecDevice`] over any of the transports supported by
+/// `libswitchtec`, created with [`SwitchtecDevice::builder`]
+///
+/// Each terminal method (E.g. [`OpenBuilder::i2c`]) returns the same [`SwitchtecDevice`], so
+/// code using the device doesn't need to know how it was opened.
+///
+/// ```no_run
+/// use switchtec_user_sys::{PartitionId, SwitchtecDevice};
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let local = SwitchtecDevice::builder().path("/dev/pciswitch0")?;
+/// let over_i2c = SwitchtecDevice::builder().i2c("/dev/i2c-1", 0x20)?;
+/// let over_eth = SwitchtecDevice::builder().partition(PartitionId(1)).eth("10.0.0.10", 0)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OpenBuilder {
+    partition: Option<PartitionId>,
+}
+
+impl OpenBuilder {
+    /// Require the opened device to be bound to `partition`
+    ///
+    /// Opening fails with an [`InvalidInput`](io::ErrorKind::InvalidInput) error if the device
+    /// is in a different partition. See [`SwitchtecDevice::open_with_partition`].
+    pub fn partition(mut self, partition: PartitionId) -> Self {
+        self.partition = Some(partition);
+        self
+    }
+
+    /// Open the Switchtec PCIe Switch character device at the given `path`
+    ///
+    /// This is the same as [`SwitchtecDevice::open`]
+    pub fn path<T: AsRef<Path>>(self, path: T) -> io::Result<SwitchtecDevice> {
+        let device = SwitchtecDevice::open(path.as_ref())?;
+        self.check_partition(device, &path.as_ref().display())
+    }
+
+    /// Open a Switchtec device over I2C, using the I2C adapter at `path` (E.g. "/dev/i2c-1")
+    /// and the switch's I2C slave address `addr`
+    pub fn i2c<T: AsRef<Path>>(self, path: T, addr: u8) -> io::Result<SwitchtecDevice> {
+        let path = path.as_ref();
+        let path_c = path_to_cstring(path)?;
+        // SAFETY: `path_c` is a valid C string for the duration of the call
+        let dev = unsafe { traced!(switchtec_open_i2c(path_c.as_ptr(), c_int::from(addr))) };
+        let transport = Transport::I2c {
+            path: path.to_path_buf(),
+            addr,
+        };
+        let device = opened(dev, path.to_path_buf(), transport)?;
+        self.check_partition(
+            device,
+            &format_args!("{} (I2C {addr:#04x})", path.display()),
+        )
+    }
+
+    /// Open a Switchtec device over the UART serial port at `path` (E.g. "/dev/ttyUSB0")
+    pub fn uart<T: AsRef<Path>>(self, path: T) -> io::Result<SwitchtecDevice> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let fd = file.into_raw_fd();
+        // SAFETY: `fd` is an open file descriptor that the library takes ownership of. It's
+        // closed with the device, or by the library itself if opening fails, so it mustn't be
+        // closed here in either case
+        let dev = unsafe { traced!(switchtec_open_uart(fd)) };
+        if dev.is_null() {
+            return Err(SwitchtecError::open_failed(path.to_path_buf()).into());
+        }
+        let device = opened(dev, path.to_path_buf(), Transport::Uart(path.to_path_buf()))?;
+        self.check_partition(device, &path.display())
+    }
+
+    /// Open a Switchtec device over Ethernet, connecting to `host` (E.g. "10.0.0.10") and
+    /// using the device `instance` on that host
+    pub fn eth(self, host: &str, instance: i32) -> io::Result<SwitchtecDevice> {
+        let host_c = CString::new(host)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        // SAFETY: `host_c` is a valid C string for the duration of the call
+        let dev = unsafe { traced!(switchtec_open_eth(host_c.as_ptr(), instance)) };
+        let transport = Transport::Eth {
+            host: host.to_owned(),
+            instance,
+        };
+        let device = opened(dev, PathBuf::from(format!("{host}:{instance}")), transport)?;
+        self.check_partition(device, &format_args!("{host}:{instance}"))
+    }
+
+    fn check_partition(
+        &self,
+        device: SwitchtecDevice,
+        name: &dyn fmt::Display,
+    ) -> io::Result<SwitchtecDevice> {
+        match self.partition {
+            Some(partition) if device.partition() != i32::from(partition) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{name} is in partition {}, not partition {partition}",
+                    device.partition()
+                ),
+            )),
+            _ => Ok(device),
+        }
+    }
+}
+
+impl SwitchtecDevice {
+    /// Create an [`OpenBuilder`] to open a device over I2C, UART, Ethernet or its character
+    /// device
+    pub fn builder() -> OpenBuilder {
+        OpenBuilder::default()
+    }
+
+    /// Open another, independent handle to the same device
+    ///
+    /// The device is reopened the same way it was originally opened (E.g. over I2C with the
+    /// same adapter and address), and the new handle is closed separately when it's dropped.
+    /// This isn't implemented as [`Clone`] because reopening the device can fail.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let monitor = device.try_clone()?;
+    /// std::thread::spawn(move || println!("{:?}", monitor.die_temp()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_clone(&self) -> io::Result<SwitchtecDevice> {
+        self.transport.open()
+    }
+
+    /// Reopen the device after it has dropped off (E.g. after [`SwitchtecDevice::hard_reset`]
+    /// or a firmware activation), retrying with backoff until it reappears or `timeout` has
+    /// passed
+    ///
+    /// Calls on a device that has dropped off fail with a [`SwitchtecError::DeviceClosed`]
+    /// error (of kind [`NotConnected`](io::ErrorKind::NotConnected)), which is the cue to
+    /// reconnect.
+    ///
+    /// The device is reopened the same way it was originally opened. Once it has been
+    /// reopened, the old handle is closed and replaced in place. If the device doesn't come
+    /// back before the timeout, a [`TimedOut`](io::ErrorKind::TimedOut) error with the last
+    /// open error is returned and the old handle is left as it was.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let mut device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// // The reset may report an error since the management link drops while it's issued
+    /// let _ = device.try_clone()?.hard_reset();
+    /// device.reconnect_with_timeout(Duration::from_secs(30))?;
+    /// println!("Now running {}", device.firmware_version()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reconnect_with_timeout(&mut self, timeout: Duration) -> io::Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = INITIAL_RECONNECT_BACKOFF;
+        loop {
+            match self.transport.open() {
+                Ok(mut device) => {
+                    // Move the old handle into `device`, which closes it when it's dropped
+                    mem::swap(&mut self.inner, &mut device.inner);
+                    mem::swap(&mut self.cache, &mut device.cache);
+                    return Ok(());
+                }
+                Err(err) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!("device didn't reappear within {timeout:?}: {err}"),
+                        ));
+                    }
+                    thread::sleep(backoff.min(deadline - now));
+                    backoff = next_reconnect_backoff(backoff);
+                }
+            }
+        }
+    }
+}
+
+/// Delay before the first retry of [`SwitchtecDevice::reconnect_with_timeout`]
+const INITIAL_RECONNECT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Longest delay between retries of [`SwitchtecDevice::reconnect_with_timeout`]
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Double the delay between reconnect attempts, up to [`MAX_RECONNECT_BACKOFF`]
+fn next_reconnect_backoff(backoff: Duration) -> Duration {
+    (backoff * 2).min(MAX_RECONNECT_BACKOFF)
+}
+
+/// How a [`SwitchtecDevice`] was opened, kept so the same device can be opened again
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Transport {
+    /// The device's character device (E.g. "/dev/pciswitch0")
+    Path(PathBuf),
+    /// An I2C adapter and the switch's slave address on it
+    I2c { path: PathBuf, addr: u8 },
+    /// A UART serial port
+    Uart(PathBuf),
+    /// A host and device instance over Ethernet
+    Eth { host: String, instance: i32 },
+}
+
+impl Transport {
+    /// Get the path of the device or the adapter it's connected through, if any
+    pub(crate) fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Path(path) | Self::I2c { path, .. } | Self::Uart(path) => Some(path),
+            Self::Eth { .. } => None,
+        }
+    }
+
+    /// Open the device again with the same transport
+    fn open(&self) -> io::Result<SwitchtecDevice> {
+        let builder = OpenBuilder::default();
+        match self {
+            Self::Path(path) => builder.path(path),
+            Self::I2c { path, addr } => builder.i2c(path, *addr),
+            Self::Uart(path) => builder.uart(path),
+            Self::Eth { host, instance } => builder.eth(host, *instance),
+        }
+    }
+}
+
+/// Wrap a `switchtec_dev` returned by one of the library's open functions, or get the
+/// library's error for `name` if it's null
+pub(crate) fn opened(
+    dev: *mut switchtec_dev,
+    name: PathBuf,
+    transport: Transport,
+) -> io::Result<SwitchtecDevice> {
+    // SAFETY: `dev` was just returned by the library, so it's null or a new handle that
+    // nothing else owns
+    unsafe { SwitchtecDevice::from_handle(dev, transport) }
+        .ok_or_else(|| SwitchtecError::open_failed(name).into())
+}
+
+fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))
+}
+
+#[test]
+fn test_next_reconnect_backoff() {
+    let mut backoff = INITIAL_RECONNECT_BACKOFF;
+    let delays: Vec<_> = std::iter::from_fn(|| {
+        backoff = next_reconnect_backoff(backoff);
+        Some(backoff)
+    })
+    .take(6)
+    .collect();
+    assert_eq!(delays[0], Duration::from_millis(200));
+    assert_eq!(delays[3], Duration::from_millis(1600));
+    assert_eq!(delays[4], MAX_RECONNECT_BACKOFF);
+    assert_eq!(delays[5], MAX_RECONNECT_BACKOFF);
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::os::raw::{c_char, c_int};
+
+use crate::ffi::switchtec_get_devices;
+use crate::{
+    get_switchtec_error, switchtec_status, CStrExt, PartitionId, PortStatus, SwitchtecDevice,
+};
+
+/// A partition of a Switchtec device and the ports bound to it, returned by
+/// [`SwitchtecDevice::partitions`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PartitionInfo {
+    /// Partition number
+    pub id: PartitionId,
+    /// Whether this is the partition the device was opened on
+    pub local: bool,
+    /// Status of the ports bound to the partition, ordered by logical port number
+    pub ports: Vec<PortStatus>,
+}
+
+impl PartitionInfo {
+    /// Get the upstream port of the partition, if one is bound to it
+    pub fn upstream_port(&self) -> Option<&PortStatus> {
+        self.ports.iter().find(|port| port.upstream)
+    }
+
+    /// Iterate over the downstream ports of the partition
+    pub fn downstream_ports(&self) -> impl Iterator<Item = &PortStatus> {
+        self.ports.iter().filter(|port| !port.upstream)
+    }
+
+    /// Check if the partition is active, meaning its upstream port has its link up
+    pub fn is_active(&self) -> bool {
+        self.upstream_port().is_some_and(|port| port.link_up)
+    }
+}
+
+/// A port of a partition and the PCI function attached below it, returned by
+/// [`SwitchtecDevice::partition_devices`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PartitionDevice {
+    /// Status of the port, including its link state
+    pub port: PortStatus,
+    /// PCI address of the port itself (E.g. "0000:03:00.0"), if the host has enumerated it
+    pub pci_bdf: Option<String>,
+    /// PCI address of the function attached below the port, if there is one
+    pub pci_dev: Option<String>,
+    /// Vendor ID of the attached function
+    pub vendor_id: Option<u16>,
+    /// Device ID of the attached function
+    pub device_id: Option<u16>,
+    /// Class devices (E.g. "nvme0") the host created for the attached function
+    pub class_devices: Option<String>,
+}
+
+impl PartitionDevice {
+    /// Check if a PCI function is attached below the port
+    pub fn is_attached(&self) -> bool {
+        self.pci_dev.is_some()
+    }
+}
+
+impl TryFrom<&switchtec_status> for PartitionDevice {
+    type Error = io::Error;
+
+    fn try_from(status: &switchtec_status) -> io::Result<Self> {
+        let optional_string = |s: *mut c_char| (!s.is_null()).then(|| s.as_string_lossy());
+        let pci_dev = optional_string(status.pci_dev);
+        let id = |id: c_int| pci_dev.as_ref().and_then(|_| u16::try_from(id).ok());
+        Ok(Self {
+            port: status.try_into()?,
+            pci_bdf: optional_string(status.pci_bdf),
+            vendor_id: id(status.vendor_id),
+            device_id: id(status.device_id),
+            class_devices: optional_string(status.class_devices),
+            pci_dev,
+        })
+    }
+}
+
+impl SwitchtecDevice {
+    /// Get the ports of a partition, along with the PCI functions attached below them
+    ///
+    /// Unlike the fabric (GFMS) APIs, this works on any multi-partition switch. The attached
+    /// functions are looked up by the host (E.g. in sysfs on Linux), so they're only reported
+    /// for partitions the host has enumerated, which is usually just the local partition.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{PartitionId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for dev in device.partition_devices(PartitionId(0))? {
+    ///     match (dev.pci_dev, dev.vendor_id, dev.device_id) {
+    ///         (Some(bdf), Some(vendor), Some(device)) => println!(
+    ///             "Port {}: {bdf} [{vendor:04x}:{device:04x}]",
+    ///             dev.port.log_port
+    ///         ),
+    ///         _ => println!("Port {}: link up: {}", dev.port.log_port, dev.port.link_up),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn partition_devices(&self, partition: PartitionId) -> io::Result<Vec<PartitionDevice>> {
+        self.with_raw_status(|statuses| {
+            // SAFETY: We know that device holds a valid/open switchtec device, and `statuses`
+            // holds every port status. The strings the library fills in are freed along with
+            // the statuses
+            let ret = unsafe {
+                traced!(switchtec_get_devices(
+                    self.inner,
+                    statuses.as_mut_ptr(),
+                    statuses.len() as c_int
+                ))
+            };
+            if ret < 0 {
+                return Err(get_switchtec_error());
+            }
+            let mut devices = statuses
+                .iter()
+                .filter(|status| status.port.partition == partition.0)
+                .map(PartitionDevice::try_from)
+                .collect::<io::Result<Vec<_>>>()?;
+            devices.sort_by_key(|dev| dev.port.log_port);
+            Ok(devices)
+        })
+    }
+
+    /// Get every partition of the device, along with the ports bound to each of them
+    ///
+    /// Partitions are built from the port status of the device (see
+    /// [`SwitchtecDevice::status`]), so only partitions with ports bound to them are listed,
+    /// along with the partition the device was opened on. A single-partition switch always
+    /// returns exactly one partition.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for partition in device.partitions()? {
+    ///     let local = if partition.local { " (local)" } else { "" };
+    ///     println!(
+    ///         "Partition {}{local}: {} ports, active: {}",
+    ///         partition.id,
+    ///         partition.ports.len(),
+    ///         partition.is_active()
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn partitions(&self) -> io::Result<Vec<PartitionInfo>> {
+        Ok(group_partitions(self.status()?, self.try_partition()?))
+    }
+
+    /// Get the partition of the device, or an error if it's not a valid partition number
+    ///
+    /// [`SwitchtecDevice::partition`] returns the raw value from the library as-is. This
+    /// returns an [`InvalidData`](io::ErrorKind::InvalidData) error instead if it's negative
+    /// (E.g. the library couldn't determine the partition) or out of range.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// println!("Opened partition {}", device.try_partition()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_partition(&self) -> io::Result<PartitionId> {
+        let partition = self.partition();
+        u8::try_from(partition).map(PartitionId).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid partition number: {partition}"),
+            )
+        })
+    }
+
+    /// Get the number of partitions of the device
+    ///
+    /// This counts the partitions listed by [`SwitchtecDevice::partitions`]
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// println!("{} partitions", device.partition_count()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn partition_count(&self) -> io::Result<usize> {
+        Ok(self.partitions()?.len())
+    }
+}
+
+/// Group port statuses by their partition, making sure the `local` partition is listed
+fn group_partitions(statuses: Vec<PortStatus>, local: PartitionId) -> Vec<PartitionInfo> {
+    let mut partitions: BTreeMap<PartitionId, Vec<PortStatus>> = BTreeMap::new();
+    partitions.entry(local).or_default();
+    for status in statuses {
+        partitions.entry(status.partition).or_default().push(status);
+    }
+    partitions
+        .into_iter()
+        .map(|(id, mut ports)| {
+            ports.sort_by_key(|port| port.log_port);
+            PartitionInfo {
+                id,
+                local: id == local,
+                ports,
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_group_partitions() {
+    use crate::{LinkRate, LinkWidth, PortId};
+
+    let port = |partition: u8, log_port: u8, upstream: bool, link_up: bool| PortStatus {
+        partition: PartitionId(partition),
+        stack: 0,
+        stack_port: log_port,
+        phys_port: PortId(partition * 8 + log_port),
+        log_port,
+        upstream,
+        cfg_link_width: LinkWidth::X4,
+        link_width: LinkWidth::X4,
+        link_rate: LinkRate::Gen4,
+        link_up,
+        ltssm: "L0".to_owned(),
+    };
+
+    // The local partition is listed even if no ports are bound to it
+    let partitions = group_partitions(vec![], PartitionId(0));
+    assert_eq!(partitions.len(), 1);
+    assert!(partitions[0].local && !partitions[0].is_active());
+
+    let partitions = group_partitions(
+        vec![
+            port(1, 1, false, true),
+            port(0, 0, true, true),
+            port(1, 0, true, false),
+            port(0, 1, false, false),
+        ],
+        PartitionId(1),
+    );
+    assert_eq!(partitions.len(), 2);
+    assert!(!partitions[0].local && partitions[0].is_active());
+    assert!(partitions[1].local && !partitions[1].is_active());
+    assert_eq!(partitions[1].upstream_port().unwrap().log_port, 0);
+    assert_eq!(partitions[1].downstream_ports().count(), 1);
+}
@@ -0,0 +1,951 @@
+//! Performance monitoring counters for a Switchtec device
+
+use std::io;
+use std::ops::{BitAnd, BitOr, BitOrAssign};
+use std::os::raw::{c_int, c_uint};
+use std::time::{Duration, Instant};
+
+use crate::ffi::{
+    switchtec_bw_type, switchtec_bw_type_SWITCHTEC_BW_TYPE_PAYLOAD,
+    switchtec_bw_type_SWITCHTEC_BW_TYPE_RAW, switchtec_bwcntr_many, switchtec_bwcntr_set_all,
+    switchtec_evcntr_get, switchtec_evcntr_setup, switchtec_evcntr_type_mask,
+    switchtec_evcntr_type_mask_ALL, switchtec_evcntr_type_mask_ALL_ERRORS,
+    switchtec_evcntr_type_mask_ALL_TLPS, switchtec_evcntr_type_mask_BAD_DLLP,
+    switchtec_evcntr_type_mask_BAD_TLP, switchtec_evcntr_type_mask_CMPLTR_ABORT_ERR,
+    switchtec_evcntr_type_mask_COMP_TLP, switchtec_evcntr_type_mask_DATA_LINK_PROTO_ERR,
+    switchtec_evcntr_type_mask_ECRC_ERR, switchtec_evcntr_type_mask_HDR_LOG_OFLOW_ERR,
+    switchtec_evcntr_type_mask_MALFORM_TLP_ERR, switchtec_evcntr_type_mask_NAK_RCVD,
+    switchtec_evcntr_type_mask_NON_POSTED_TLP, switchtec_evcntr_type_mask_POISONED_TLP_ERR,
+    switchtec_evcntr_type_mask_POSTED_TLP, switchtec_evcntr_type_mask_RCVR_ERR,
+    switchtec_evcntr_type_mask_RCVR_OFLOW_ERR, switchtec_evcntr_type_mask_RCV_CORR_MSG,
+    switchtec_evcntr_type_mask_RCV_FATAL_MSG, switchtec_evcntr_type_mask_RCV_NON_FATAL_MSG,
+    switchtec_evcntr_type_mask_REPLAY_NUM_ROLLOVER, switchtec_evcntr_type_mask_REPLAY_TMR_TIMEOUT,
+    switchtec_evcntr_type_mask_RULE_TABLE_HIT, switchtec_evcntr_type_mask_SURPRISE_DOWN_ERR,
+    switchtec_evcntr_type_mask_UNCOR_INT_ERR, switchtec_evcntr_type_mask_UNSUP_REQ_ERR,
+    switchtec_lat_get_many, switchtec_lat_setup_many, SWITCHTEC_LAT_ALL_INGRESS,
+    SWITCHTEC_MAX_EVENT_COUNTERS, SWITCHTEC_MAX_STACKS,
+};
+use crate::{
+    get_switchtec_error, switchtec_bwcntr_res, switchtec_bwcntr_res_switchtec_bwcntr_dir, PortId,
+    SwitchtecDevice,
+};
+
+/// Maximum number of stacks on a device
+pub const MAX_STACKS: usize = SWITCHTEC_MAX_STACKS as usize;
+
+/// Number of event counters in each stack
+pub const EVENT_COUNTERS_PER_STACK: usize = SWITCHTEC_MAX_EVENT_COUNTERS as usize;
+
+/// What the bandwidth counters count, set with [`SwitchtecDevice::set_bandwidth_type`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum BandwidthType {
+    /// Count every byte of each TLP, including headers
+    Raw,
+    /// Count only TLP payload bytes
+    Payload,
+}
+
+impl BandwidthType {
+    fn raw(self) -> switchtec_bw_type {
+        match self {
+            Self::Raw => switchtec_bw_type_SWITCHTEC_BW_TYPE_RAW,
+            Self::Payload => switchtec_bw_type_SWITCHTEC_BW_TYPE_PAYLOAD,
+        }
+    }
+}
+
+/// Byte counts for one direction of traffic on a port
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BandwidthDirection {
+    /// Bytes of posted TLPs
+    pub posted: u64,
+    /// Bytes of non-posted TLPs
+    pub non_posted: u64,
+    /// Bytes of completion TLPs
+    pub completion: u64,
+}
+
+impl BandwidthDirection {
+    /// Total bytes of all TLP types
+    pub fn total(&self) -> u64 {
+        self.posted + self.non_posted + self.completion
+    }
+
+    /// Bytes counted since an `earlier` sample of the same counters
+    fn since(&self, earlier: &Self) -> Self {
+        Self {
+            posted: counter_delta(earlier.posted, self.posted),
+            non_posted: counter_delta(earlier.non_posted, self.non_posted),
+            completion: counter_delta(earlier.completion, self.completion),
+        }
+    }
+}
+
+impl From<&switchtec_bwcntr_res_switchtec_bwcntr_dir> for BandwidthDirection {
+    fn from(dir: &switchtec_bwcntr_res_switchtec_bwcntr_dir) -> Self {
+        Self {
+            posted: dir.posted,
+            non_posted: dir.nonposted,
+            completion: dir.comp,
+        }
+    }
+}
+
+/// A sample of the bandwidth counters for a single port, returned by
+/// [`SwitchtecDevice::bandwidth_counters`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BandwidthCounter {
+    /// Physical port the counters are for
+    pub port: PortId,
+    /// Device timestamp of the sample, in microseconds
+    pub time_us: u64,
+    /// Traffic leaving the switch through this port
+    pub egress: BandwidthDirection,
+    /// Traffic entering the switch through this port
+    pub ingress: BandwidthDirection,
+}
+
+/// Throughput of a port in bytes per second, calculated with [`BandwidthCounter::throughput`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Throughput {
+    /// Bytes per second leaving the switch through the port
+    pub egress: f64,
+    /// Bytes per second entering the switch through the port
+    pub ingress: f64,
+}
+
+impl Throughput {
+    /// Gigabytes (10^9 bytes) per second leaving the switch through the port
+    pub fn egress_gbytes(&self) -> f64 {
+        self.egress / 1e9
+    }
+
+    /// Gigabytes (10^9 bytes) per second entering the switch through the port
+    pub fn ingress_gbytes(&self) -> f64 {
+        self.ingress / 1e9
+    }
+}
+
+/// Throughput of each monitored port over one interval, returned by
+/// [`BandwidthMonitor::sample`]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BandwidthSample {
+    /// Wall-clock time the throughput was measured over
+    pub elapsed: Duration,
+    /// Throughput of each port, in the order the ports were given to
+    /// [`SwitchtecDevice::bandwidth_monitor`]
+    pub ports: Vec<(PortId, Throughput)>,
+}
+
+/// Samples the throughput of a set of ports at a fixed interval, created with
+/// [`SwitchtecDevice::bandwidth_monitor`]
+///
+/// Each [`sample`](BandwidthMonitor::sample) waits until `interval` has passed since the
+/// previous one, then reads the bandwidth counters and divides the bytes counted in between by
+/// the wall-clock time that actually elapsed. The monitor is also an endless iterator of
+/// samples.
+pub struct BandwidthMonitor<'a> {
+    device: &'a SwitchtecDevice,
+    ports: Vec<PortId>,
+    interval: Duration,
+    previous: Vec<BandwidthCounter>,
+    previous_at: Instant,
+}
+
+impl BandwidthMonitor<'_> {
+    /// Wait for the rest of the interval, then get the throughput of each port since the
+    /// previous sample (or since the monitor was created)
+    pub fn sample(&mut self) -> io::Result<BandwidthSample> {
+        let next = self.previous_at + self.interval;
+        let now = Instant::now();
+        if next > now {
+            std::thread::sleep(next - now);
+        }
+
+        let current = self.device.bandwidth_counters(&self.ports)?;
+        let sampled_at = Instant::now();
+        let elapsed = sampled_at - self.previous_at;
+        let ports = current
+            .iter()
+            .zip(&self.previous)
+            .map(|(current, previous)| (current.port, current.throughput(previous, elapsed)))
+            .collect();
+        self.previous = current;
+        self.previous_at = sampled_at;
+        Ok(BandwidthSample { elapsed, ports })
+    }
+}
+
+impl Iterator for BandwidthMonitor<'_> {
+    type Item = io::Result<BandwidthSample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.sample())
+    }
+}
+
+impl BandwidthCounter {
+    fn new(port: PortId, res: &switchtec_bwcntr_res) -> Self {
+        Self {
+            port,
+            time_us: res.time_us,
+            egress: (&res.egress).into(),
+            ingress: (&res.ingress).into(),
+        }
+    }
+
+    /// Calculate the throughput between an `earlier` sample of the same port and this one,
+    /// taken `elapsed` apart
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use switchtec_user_sys::pmon::{BandwidthCounter, BandwidthDirection};
+    /// use switchtec_user_sys::PortId;
+    ///
+    /// let earlier = BandwidthCounter {
+    ///     port: PortId(0),
+    ///     time_us: 0,
+    ///     egress: BandwidthDirection::default(),
+    ///     ingress: BandwidthDirection::default(),
+    /// };
+    /// let mut later = earlier;
+    /// later.egress.posted = 2048;
+    ///
+    /// let throughput = later.throughput(&earlier, Duration::from_secs(2));
+    /// assert_eq!(throughput.egress, 1024.0);
+    /// ```
+    ///
+    /// Counters that were cleared or wrapped around between the samples are handled, see
+    /// [`SwitchtecDevice::bandwidth_monitor`]
+    pub fn throughput(&self, earlier: &BandwidthCounter, elapsed: Duration) -> Throughput {
+        let secs = elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return Throughput::default();
+        }
+        let egress = self.egress.since(&earlier.egress).total();
+        let ingress = self.ingress.since(&earlier.ingress).total();
+        Throughput {
+            egress: egress as f64 / secs,
+            ingress: ingress as f64 / secs,
+        }
+    }
+}
+
+/// The latency of a single egress port, returned by [`SwitchtecDevice::latency`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LatencyCounter {
+    /// Physical egress port the latency is measured on
+    pub port: PortId,
+    /// Latency of the most recent TLP, in nanoseconds
+    pub current_ns: u32,
+    /// Maximum latency seen since the counter was set up, in nanoseconds
+    pub max_ns: u32,
+}
+
+/// A set of event types for an event counter to count, set up with
+/// [`SwitchtecDevice::setup_event_counter`]
+///
+/// Sets are combined with `|`:
+///
+/// ```
+/// use switchtec_user_sys::pmon::EventTypes;
+///
+/// let types = EventTypes::BAD_TLP | EventTypes::BAD_DLLP;
+/// assert!(types.contains(EventTypes::BAD_TLP));
+/// assert!(!types.contains(EventTypes::NAK_RECEIVED));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EventTypes(u32);
+
+impl EventTypes {
+    /// Unsupported request errors
+    pub const UNSUPPORTED_REQUEST: Self = Self(switchtec_evcntr_type_mask_UNSUP_REQ_ERR);
+    /// ECRC errors
+    pub const ECRC_ERROR: Self = Self(switchtec_evcntr_type_mask_ECRC_ERR);
+    /// Malformed TLP errors
+    pub const MALFORMED_TLP: Self = Self(switchtec_evcntr_type_mask_MALFORM_TLP_ERR);
+    /// Receiver overflow errors
+    pub const RECEIVER_OVERFLOW: Self = Self(switchtec_evcntr_type_mask_RCVR_OFLOW_ERR);
+    /// Completer abort errors
+    pub const COMPLETER_ABORT: Self = Self(switchtec_evcntr_type_mask_CMPLTR_ABORT_ERR);
+    /// Poisoned TLP errors
+    pub const POISONED_TLP: Self = Self(switchtec_evcntr_type_mask_POISONED_TLP_ERR);
+    /// Surprise down errors
+    pub const SURPRISE_DOWN: Self = Self(switchtec_evcntr_type_mask_SURPRISE_DOWN_ERR);
+    /// Data link protocol errors
+    pub const DATA_LINK_PROTOCOL: Self = Self(switchtec_evcntr_type_mask_DATA_LINK_PROTO_ERR);
+    /// Header log overflow errors
+    pub const HEADER_LOG_OVERFLOW: Self = Self(switchtec_evcntr_type_mask_HDR_LOG_OFLOW_ERR);
+    /// Uncorrectable internal errors
+    pub const UNCORRECTABLE_INTERNAL: Self = Self(switchtec_evcntr_type_mask_UNCOR_INT_ERR);
+    /// Replay timer timeouts
+    pub const REPLAY_TIMER_TIMEOUT: Self = Self(switchtec_evcntr_type_mask_REPLAY_TMR_TIMEOUT);
+    /// Replay number rollovers
+    pub const REPLAY_NUM_ROLLOVER: Self = Self(switchtec_evcntr_type_mask_REPLAY_NUM_ROLLOVER);
+    /// Bad DLLPs
+    pub const BAD_DLLP: Self = Self(switchtec_evcntr_type_mask_BAD_DLLP);
+    /// Bad TLPs
+    pub const BAD_TLP: Self = Self(switchtec_evcntr_type_mask_BAD_TLP);
+    /// Receiver errors
+    pub const RECEIVER_ERROR: Self = Self(switchtec_evcntr_type_mask_RCVR_ERR);
+    /// Received fatal error messages
+    pub const FATAL_MESSAGE: Self = Self(switchtec_evcntr_type_mask_RCV_FATAL_MSG);
+    /// Received non-fatal error messages
+    pub const NON_FATAL_MESSAGE: Self = Self(switchtec_evcntr_type_mask_RCV_NON_FATAL_MSG);
+    /// Received correctable error messages
+    pub const CORRECTABLE_MESSAGE: Self = Self(switchtec_evcntr_type_mask_RCV_CORR_MSG);
+    /// Received NAKs
+    pub const NAK_RECEIVED: Self = Self(switchtec_evcntr_type_mask_NAK_RCVD);
+    /// TLPs matching the rule table
+    pub const RULE_TABLE_HIT: Self = Self(switchtec_evcntr_type_mask_RULE_TABLE_HIT);
+    /// Posted TLPs
+    pub const POSTED_TLP: Self = Self(switchtec_evcntr_type_mask_POSTED_TLP);
+    /// Completion TLPs
+    pub const COMPLETION_TLP: Self = Self(switchtec_evcntr_type_mask_COMP_TLP);
+    /// Non-posted TLPs
+    pub const NON_POSTED_TLP: Self = Self(switchtec_evcntr_type_mask_NON_POSTED_TLP);
+    /// Every kind of error
+    pub const ALL_ERRORS: Self = Self(switchtec_evcntr_type_mask_ALL_ERRORS);
+    /// Every kind of TLP
+    pub const ALL_TLPS: Self = Self(switchtec_evcntr_type_mask_ALL_TLPS);
+    /// Every event type
+    pub const ALL: Self = Self(switchtec_evcntr_type_mask_ALL);
+
+    /// An empty set, which counts nothing
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Create a set from its raw `switchtec_evcntr_type_mask` bits, dropping any unknown bits
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+
+    /// Get the raw `switchtec_evcntr_type_mask` bits of the set
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Check if the set contains no event types
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Check if every event type in `other` is in this set
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for EventTypes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for EventTypes {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for EventTypes {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+/// What an event counter counts, set up with [`SwitchtecDevice::setup_event_counter`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EventCounterConfig {
+    /// Bitmap of the ports within the stack to count events on, where bit 0 is the first port
+    /// of the stack
+    pub port_mask: u32,
+    /// Event types to count
+    pub types: EventTypes,
+    /// Count events in the egress direction instead of ingress
+    pub egress: bool,
+    /// Counter value at which the firmware raises a threshold event, or 0 for no threshold
+    pub threshold: u32,
+}
+
+impl From<EventCounterConfig> for switchtec_evcntr_setup {
+    fn from(config: EventCounterConfig) -> Self {
+        Self {
+            port_mask: config.port_mask,
+            type_mask: config.types.bits() as switchtec_evcntr_type_mask,
+            egress: config.egress as c_int,
+            threshold: config.threshold,
+        }
+    }
+}
+
+/// Latency counters set up with [`SwitchtecDevice::latency_guard`], which are set back to
+/// measuring TLPs from any ingress port when the guard is dropped
+///
+/// This keeps a filtered setup from outliving the operation that needed it, even if that
+/// operation returns early or panics. Errors tearing down the setup are ignored on drop, use
+/// [`LatencyGuard::release`] to handle them.
+#[must_use = "the latency counters are torn down as soon as the guard is dropped"]
+pub struct LatencyGuard<'a> {
+    device: &'a SwitchtecDevice,
+    ports: Vec<PortId>,
+    armed: bool,
+}
+
+impl LatencyGuard<'_> {
+    /// Read the current and maximum latency of the guarded ports, see
+    /// [`SwitchtecDevice::latency`]
+    pub fn latency(&self) -> io::Result<Vec<LatencyCounter>> {
+        self.device.latency(&self.ports)
+    }
+
+    /// Tear down the setup now, returning any error
+    pub fn release(mut self) -> io::Result<()> {
+        self.armed = false;
+        self.device.setup_latency(&self.ports, None)
+    }
+
+    /// Leave the latency counters set up after the guard is dropped
+    pub fn keep(mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for LatencyGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            let _ = self.device.setup_latency(&self.ports, None);
+        }
+    }
+}
+
+/// An event counter set up with [`SwitchtecDevice::event_counter_guard`], which is set back
+/// to counting nothing when the guard is dropped
+///
+/// This keeps an armed counter (and any threshold events it raises) from outliving the
+/// operation that needed it, even if that operation returns early or panics. Errors tearing
+/// down the counter are ignored on drop, use [`EventCounterGuard::release`] to handle them.
+#[must_use = "the event counter is torn down as soon as the guard is dropped"]
+pub struct EventCounterGuard<'a> {
+    device: &'a SwitchtecDevice,
+    stack: u8,
+    counter: u8,
+    armed: bool,
+}
+
+impl EventCounterGuard<'_> {
+    /// Read the number of events counted since the counter was set up
+    pub fn read(&self) -> io::Result<u32> {
+        let counts = self.device.read_event_counters(self.stack)?;
+        Ok(counts[usize::from(self.counter)])
+    }
+
+    /// Tear down the counter now, returning any error
+    pub fn release(mut self) -> io::Result<()> {
+        self.armed = false;
+        self.teardown()
+    }
+
+    /// Leave the counter set up after the guard is dropped
+    pub fn keep(mut self) {
+        self.armed = false;
+    }
+
+    fn teardown(&self) -> io::Result<()> {
+        self.device
+            .setup_event_counter(self.stack, self.counter, EventCounterConfig::default())
+    }
+}
+
+impl Drop for EventCounterGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            let _ = self.teardown();
+        }
+    }
+}
+
+impl SwitchtecDevice {
+    /// Set what the bandwidth counters count for every port on the device
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::pmon::BandwidthType;
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// device.set_bandwidth_type(BandwidthType::Payload)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_bandwidth_type(&self, bw_type: BandwidthType) -> io::Result<()> {
+        // SAFETY: `switchtec_bwcntr_set_all` only takes the device and a plain enum value
+        let ret = unsafe { traced!(switchtec_bwcntr_set_all(self.inner, bw_type.raw())) };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(())
+    }
+
+    /// Read the bandwidth counters for each of the given physical ports
+    ///
+    /// The counters are cumulative, so take two samples and use
+    /// [`BandwidthCounter::throughput`] to get the throughput of a port:
+    ///
+    /// ```no_run
+    /// use std::time::{Duration, Instant};
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let ports = [PortId(0), PortId(1)];
+    /// let start = Instant::now();
+    /// let before = device.bandwidth_counters(&ports)?;
+    /// std::thread::sleep(Duration::from_secs(1));
+    /// let after = device.bandwidth_counters(&ports)?;
+    /// for (before, after) in before.iter().zip(&after) {
+    ///     let throughput = after.throughput(before, start.elapsed());
+    ///     println!(
+    ///         "Port {}: {:.0} B/s out, {:.0} B/s in",
+    ///         after.port, throughput.egress, throughput.ingress
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bandwidth_counters(&self, ports: &[PortId]) -> io::Result<Vec<BandwidthCounter>> {
+        if ports.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut port_ids: Vec<c_int> = ports.iter().copied().map(c_int::from).collect();
+        let mut results: Vec<switchtec_bwcntr_res> = Vec::with_capacity(ports.len());
+
+        // SAFETY: `port_ids` holds and `results` has capacity for `ports.len()` elements, which
+        // is the number of ports we tell `switchtec_bwcntr_many` to read. `results` is only
+        // marked as populated after the library reports success
+        unsafe {
+            let ret = traced!(switchtec_bwcntr_many(
+                self.inner,
+                port_ids.len() as c_int,
+                port_ids.as_mut_ptr(),
+                0,
+                results.as_mut_ptr(),
+            ));
+            if ret < 0 {
+                return Err(get_switchtec_error());
+            }
+            results.set_len(ports.len());
+        }
+
+        Ok(ports
+            .iter()
+            .zip(&results)
+            .map(|(port, res)| BandwidthCounter::new(*port, res))
+            .collect())
+    }
+
+    /// Monitor the throughput of each of the given physical ports, sampled every `interval`
+    ///
+    /// This is the equivalent of `switchtec bw --interval`. The counters are read once when
+    /// the monitor is created, and each [`BandwidthMonitor::sample`] reports the throughput
+    /// since the previous read.
+    ///
+    /// The counters are 64-bit and only grow, so a counter that reads lower than before was
+    /// either cleared (E.g. by another tool) or wrapped around. A counter that was past half
+    /// its range is assumed to have wrapped, and the bytes across the wrap are counted.
+    /// Otherwise it's assumed to have been cleared, and only the bytes since the clear are
+    /// counted.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let monitor = device.bandwidth_monitor(&[PortId(0), PortId(8)], Duration::from_secs(1))?;
+    /// for sample in monitor.take(10) {
+    ///     for (port, throughput) in sample?.ports {
+    ///         println!(
+    ///             "Port {port}: {:.3} GB/s out, {:.3} GB/s in",
+    ///             throughput.egress_gbytes(),
+    ///             throughput.ingress_gbytes()
+    ///         );
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bandwidth_monitor(
+        &self,
+        ports: &[PortId],
+        interval: Duration,
+    ) -> io::Result<BandwidthMonitor<'_>> {
+        let previous = self.bandwidth_counters(ports)?;
+        Ok(BandwidthMonitor {
+            device: self,
+            ports: ports.to_vec(),
+            interval,
+            previous,
+            previous_at: Instant::now(),
+        })
+    }
+
+    /// Set up the latency counters of the given physical `egress` ports to measure TLPs
+    /// arriving on the `ingress` port, or on any port when `ingress` is `None`
+    ///
+    /// The latency counters must be set up before [`SwitchtecDevice::latency`] returns
+    /// meaningful data. Setting up a counter also resets its maximum latency. The setup stays
+    /// in place until it's changed again, see [`SwitchtecDevice::latency_guard`] to have it
+    /// torn down automatically.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// // Measure the latency of TLPs from the upstream port (0) to two downstream ports
+    /// device.setup_latency(&[PortId(8), PortId(16)], Some(PortId(0)))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn setup_latency(&self, egress: &[PortId], ingress: Option<PortId>) -> io::Result<()> {
+        if egress.is_empty() {
+            return Ok(());
+        }
+        let ingress = ingress.map_or(SWITCHTEC_LAT_ALL_INGRESS as c_int, c_int::from);
+        let mut egress_ids: Vec<c_int> = egress.iter().copied().map(c_int::from).collect();
+        let mut ingress_ids = vec![ingress; egress.len()];
+
+        // SAFETY: `egress_ids` and `ingress_ids` both hold `egress.len()` elements, which is the
+        // number of ports we tell `switchtec_lat_setup_many` to set up
+        let ret = unsafe {
+            traced!(switchtec_lat_setup_many(
+                self.inner,
+                egress_ids.len() as c_int,
+                egress_ids.as_mut_ptr(),
+                ingress_ids.as_mut_ptr(),
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(())
+    }
+
+    /// Set up the latency counters like [`SwitchtecDevice::setup_latency`], returning a guard
+    /// that sets them back to measuring TLPs from any ingress port when it's dropped
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let guard = device.latency_guard(&[PortId(8)], Some(PortId(0)))?;
+    /// // An early return here still tears down the setup
+    /// for counter in guard.latency()? {
+    ///     println!("Port {}: max {}ns", counter.port, counter.max_ns);
+    /// }
+    /// guard.release()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn latency_guard(
+        &self,
+        egress: &[PortId],
+        ingress: Option<PortId>,
+    ) -> io::Result<LatencyGuard<'_>> {
+        self.setup_latency(egress, ingress)?;
+        Ok(LatencyGuard {
+            device: self,
+            ports: egress.to_vec(),
+            armed: true,
+        })
+    }
+
+    /// Read the current and maximum latency of each of the given physical egress ports
+    ///
+    /// The counters must first be set up with [`SwitchtecDevice::setup_latency`].
+    /// `libswitchtec` can read the current and maximum latency separately, but both come back
+    /// from the same MRPC command so they're always read together here.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let ports = [PortId(8), PortId(16)];
+    /// device.setup_latency(&ports, None)?;
+    /// for counter in device.latency(&ports)? {
+    ///     println!(
+    ///         "Port {}: {}ns (max {}ns)",
+    ///         counter.port, counter.current_ns, counter.max_ns
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn latency(&self, ports: &[PortId]) -> io::Result<Vec<LatencyCounter>> {
+        if ports.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut port_ids: Vec<c_int> = ports.iter().copied().map(c_int::from).collect();
+        let mut current_ns: Vec<c_int> = vec![0; ports.len()];
+        let mut max_ns: Vec<c_int> = vec![0; ports.len()];
+
+        // SAFETY: `port_ids`, `current_ns` and `max_ns` all hold `ports.len()` elements, which is
+        // the number of ports we tell `switchtec_lat_get_many` to read
+        let ret = unsafe {
+            traced!(switchtec_lat_get_many(
+                self.inner,
+                port_ids.len() as c_int,
+                0,
+                port_ids.as_mut_ptr(),
+                current_ns.as_mut_ptr(),
+                max_ns.as_mut_ptr(),
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+
+        Ok(ports
+            .iter()
+            .zip(current_ns.iter().zip(&max_ns))
+            .map(|(port, (current_ns, max_ns))| LatencyCounter {
+                port: *port,
+                current_ns: *current_ns as u32,
+                max_ns: *max_ns as u32,
+            })
+            .collect())
+    }
+
+    /// Set up event counter `counter` of `stack` to count the events described by `config`
+    ///
+    /// Each stack has its own [`EVENT_COUNTERS_PER_STACK`] counters, so `stack` must be less
+    /// than [`MAX_STACKS`] and `counter` less than [`EVENT_COUNTERS_PER_STACK`], otherwise an
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput) error is returned. Setting up a counter
+    /// also resets it. The counter stays armed until it's set up again, see
+    /// [`SwitchtecDevice::event_counter_guard`] to have it torn down automatically.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::pmon::{EventCounterConfig, EventTypes};
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// // Count bad TLPs and DLLPs received on the first two ports of stack 0
+    /// let config = EventCounterConfig {
+    ///     port_mask: 0b11,
+    ///     types: EventTypes::BAD_TLP | EventTypes::BAD_DLLP,
+    ///     ..Default::default()
+    /// };
+    /// device.setup_event_counter(0, 0, config)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn setup_event_counter(
+        &self,
+        stack: u8,
+        counter: u8,
+        config: EventCounterConfig,
+    ) -> io::Result<()> {
+        check_event_counter(stack, counter)?;
+        let mut setup = switchtec_evcntr_setup::from(config);
+
+        // SAFETY: `setup` is a valid `switchtec_evcntr_setup` that is only read by the library
+        let ret = unsafe {
+            traced!(switchtec_evcntr_setup(
+                self.inner,
+                c_uint::from(stack),
+                c_uint::from(counter),
+                &mut setup,
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(())
+    }
+
+    /// Set up an event counter like [`SwitchtecDevice::setup_event_counter`], returning a guard
+    /// that sets it back to counting nothing when it's dropped
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::pmon::{EventCounterConfig, EventTypes};
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let config = EventCounterConfig {
+    ///     port_mask: 0b1,
+    ///     types: EventTypes::ALL_ERRORS,
+    ///     ..Default::default()
+    /// };
+    /// let counter = device.event_counter_guard(0, 0, config)?;
+    /// // An early return here still tears down the counter
+    /// std::thread::sleep(std::time::Duration::from_secs(1));
+    /// println!("{} errors", counter.read()?);
+    /// counter.release()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn event_counter_guard(
+        &self,
+        stack: u8,
+        counter: u8,
+        config: EventCounterConfig,
+    ) -> io::Result<EventCounterGuard<'_>> {
+        self.setup_event_counter(stack, counter, config)?;
+        Ok(EventCounterGuard {
+            device: self,
+            stack,
+            counter,
+            armed: true,
+        })
+    }
+
+    /// Read every event counter of `stack`, indexed by counter
+    ///
+    /// The counters are not cleared by reading them. Returns an
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput) error if `stack` isn't less than
+    /// [`MAX_STACKS`].
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let counters = device.read_event_counters(0)?;
+    /// println!("Counter 0: {} events", counters[0]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_event_counters(&self, stack: u8) -> io::Result<Vec<u32>> {
+        check_event_counter(stack, 0)?;
+        let mut counts: Vec<c_uint> = vec![0; EVENT_COUNTERS_PER_STACK];
+
+        // SAFETY: `counts` holds `EVENT_COUNTERS_PER_STACK` elements, which is the number of
+        // counters we tell `switchtec_evcntr_get` to read
+        let ret = unsafe {
+            traced!(switchtec_evcntr_get(
+                self.inner,
+                c_uint::from(stack),
+                0,
+                counts.len() as c_uint,
+                counts.as_mut_ptr(),
+                0,
+            ))
+        };
+        if ret < 0 {
+            return Err(get_switchtec_error());
+        }
+        Ok(counts)
+    }
+}
+
+/// Check that `stack` and `counter` are within the per-device and per-stack limits
+fn check_event_counter(stack: u8, counter: u8) -> io::Result<()> {
+    if usize::from(stack) >= MAX_STACKS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("stack {stack} is out of range, devices have at most {MAX_STACKS} stacks"),
+        ));
+    }
+    if usize::from(counter) >= EVENT_COUNTERS_PER_STACK {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "event counter {counter} is out of range, each stack has {EVENT_COUNTERS_PER_STACK}"
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Get how much a counter grew from `earlier` to `later`, allowing for it to be cleared or to
+/// wrap around in between
+fn counter_delta(earlier: u64, later: u64) -> u64 {
+    if later >= earlier {
+        later - earlier
+    } else if earlier > u64::MAX / 2 {
+        // Wrapped around, so count up to the wrap and then from zero
+        later.wrapping_sub(earlier)
+    } else {
+        // Cleared, so everything counted since the clear is new
+        later
+    }
+}
+
+#[test]
+fn test_counter_delta() {
+    assert_eq!(counter_delta(100, 250), 150);
+    assert_eq!(counter_delta(u64::MAX - 9, 20), 30);
+    assert_eq!(counter_delta(1000, 20), 20);
+}
+
+#[test]
+fn test_bandwidth_throughput() {
+    let dir = |posted, non_posted, completion| BandwidthDirection {
+        posted,
+        non_posted,
+        completion,
+    };
+    let earlier = BandwidthCounter {
+        port: PortId(3),
+        time_us: 0,
+        egress: dir(100, 100, 100),
+        ingress: dir(1000, 0, 0),
+    };
+    let later = BandwidthCounter {
+        port: PortId(3),
+        time_us: 500_000,
+        egress: dir(400, 400, 400),
+        ingress: dir(0, 0, 0),
+    };
+
+    let throughput = later.throughput(&earlier, Duration::from_millis(500));
+    assert_eq!(throughput.egress, 1800.0);
+    // Cleared counters don't produce a negative (or wrapped) throughput
+    assert_eq!(throughput.ingress, 0.0);
+    assert_eq!(
+        later.throughput(&earlier, Duration::ZERO),
+        Throughput::default()
+    );
+}
+
+#[test]
+fn test_event_counter_config() {
+    let types = EventTypes::BAD_TLP | EventTypes::NAK_RECEIVED;
+    assert!(types.contains(EventTypes::BAD_TLP));
+    assert!(!types.contains(EventTypes::BAD_DLLP));
+    assert!(EventTypes::ALL.contains(types));
+    assert!(EventTypes::empty().is_empty());
+
+    let setup = switchtec_evcntr_setup::from(EventCounterConfig {
+        port_mask: 0b101,
+        types,
+        egress: true,
+        threshold: 10,
+    });
+    assert_eq!(setup.port_mask, 0b101);
+    assert_eq!(setup.type_mask, types.bits());
+    assert_eq!(setup.egress, 1);
+    assert_eq!(setup.threshold, 10);
+
+    assert!(check_event_counter(0, 0).is_ok());
+    assert!(check_event_counter(MAX_STACKS as u8, 0).is_err());
+    assert!(check_event_counter(0, EVENT_COUNTERS_PER_STACK as u8).is_err());
+}
@@ -0,0 +1,160 @@
+//! Background polling of a device's temperature and port status, for async services
+//!
+//! A [`Poller`] owns a [`SwitchtecDevice`] and reads it on a blocking thread at a fixed
+//! interval, publishing each [`DeviceSnapshot`] on a [`tokio::sync::watch`] channel. Any
+//! number of subscribers can read the latest snapshot without issuing MRPC commands of their
+//! own, so the device only ever sees one command at a time.
+
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+use tokio::task::{self, JoinHandle};
+
+use crate::{PortStatus, SwitchtecDevice, Temperature};
+
+/// The state of a device read by a [`Poller`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceSnapshot {
+    /// Die temperature of the switch
+    pub temperature: Temperature,
+    /// Status of each port, see [`SwitchtecDevice::status`]
+    pub ports: Vec<PortStatus>,
+    /// When the snapshot was read
+    pub updated: Instant,
+}
+
+impl DeviceSnapshot {
+    fn read(device: &SwitchtecDevice) -> io::Result<Self> {
+        Ok(Self {
+            temperature: device.die_temp()?,
+            ports: device.status()?,
+            updated: Instant::now(),
+        })
+    }
+}
+
+/// Polls a device in the background, publishing a [`DeviceSnapshot`] after every successful
+/// read
+///
+/// The channel holds `None` until the first read succeeds. A read that fails (E.g. because
+/// another process holds the MRPC lock) is skipped, so subscribers keep the last good
+/// snapshot, and [`DeviceSnapshot::updated`] tells them how old it is.
+///
+/// The device is closed once the poller is shut down with [`Poller::shutdown`], or dropped.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use switchtec_user_sys::poller::Poller;
+/// use switchtec_user_sys::SwitchtecDevice;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// # let runtime = tokio::runtime::Runtime::new()?;
+/// # runtime.block_on(async {
+/// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+/// let poller = Poller::spawn(device, Duration::from_secs(1));
+///
+/// let mut snapshots = poller.subscribe();
+/// while snapshots.changed().await.is_ok() {
+///     if let Some(snapshot) = &*snapshots.borrow_and_update() {
+///         println!("Temperature: {}", snapshot.temperature);
+///     }
+/// }
+///
+/// poller.shutdown().await?;
+/// # Ok(())
+/// # })
+/// # }
+/// ```
+pub struct Poller {
+    snapshots: watch::Receiver<Option<DeviceSnapshot>>,
+    stop: mpsc::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Poller {
+    /// Start polling `device` every `interval` on a blocking thread of the tokio runtime
+    ///
+    /// The first read is made right away. This must be called from within a tokio runtime.
+    pub fn spawn(device: SwitchtecDevice, interval: Duration) -> Self {
+        let (sender, snapshots) = watch::channel(None);
+        let (stop, stopped) = mpsc::channel();
+        let task = task::spawn_blocking(move || {
+            poll(
+                || DeviceSnapshot::read(&device),
+                &sender,
+                &stopped,
+                interval,
+            );
+            // `device` is dropped (and closed) here, on the polling thread
+        });
+        Self {
+            snapshots,
+            stop,
+            task,
+        }
+    }
+
+    /// Get a receiver for the snapshots, holding the latest one
+    pub fn subscribe(&self) -> watch::Receiver<Option<DeviceSnapshot>> {
+        self.snapshots.clone()
+    }
+
+    /// Stop polling and wait for the device to be closed
+    ///
+    /// A read that's in progress is finished first. Receivers keep the last snapshot, but
+    /// [`watch::Receiver::changed`] returns an error once the poller has stopped.
+    pub async fn shutdown(self) -> io::Result<()> {
+        drop(self.stop);
+        self.task
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+/// Publish the result of `read` to `sender` every `interval`, keeping the last good value when
+/// it fails, until `stopped` is signalled or disconnected
+fn poll<T>(
+    mut read: impl FnMut() -> io::Result<T>,
+    sender: &watch::Sender<Option<T>>,
+    stopped: &mpsc::Receiver<()>,
+    interval: Duration,
+) {
+    loop {
+        if let Ok(value) = read() {
+            sender.send_replace(Some(value));
+        }
+        match stopped.recv_timeout(interval) {
+            Err(RecvTimeoutError::Timeout) => continue,
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+#[test]
+fn test_poll_keeps_last_good_value() {
+    let (sender, receiver) = watch::channel(None);
+    let (stop, stopped) = mpsc::channel();
+    let mut reads = 0;
+    poll(
+        || {
+            reads += 1;
+            // Stop after the fourth read
+            if reads == 4 {
+                stop.send(()).unwrap();
+            }
+            match reads {
+                1 => Err(io::ErrorKind::Other.into()),
+                2 => Ok(reads),
+                _ => Err(io::ErrorKind::TimedOut.into()),
+            }
+        },
+        &sender,
+        &stopped,
+        Duration::from_millis(1),
+    );
+    assert_eq!(reads, 4);
+    // The failed reads after the second one didn't replace its value
+    assert_eq!(*receiver.borrow(), Some(2));
+}
@@ -0,0 +1,130 @@
+//! Bridges Rust progress closures to the `void (*progress_callback)(int cur, int tot)`
+//! callbacks used by the library's firmware transfer functions
+//!
+//! The C callback has no user-data argument, so the closure for the transfer running on the
+//! current thread is stored in a thread-local for [`progress_trampoline`] to find.
+
+use std::any::Any;
+use std::cell::Cell;
+use std::os::raw::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// C progress callback type accepted by the library's firmware transfer functions
+pub(crate) type ProgressCallback = Option<unsafe extern "C" fn(cur: c_int, tot: c_int)>;
+
+struct ProgressState<'a> {
+    /// Receives the `cur` and `tot` values reported by the library
+    callback: &'a mut dyn FnMut(c_int, c_int),
+    /// Panic payload from the closure, to be resumed once back in Rust
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+thread_local! {
+    static PROGRESS: Cell<*mut c_void> = const { Cell::new(ptr::null_mut()) };
+}
+
+/// Call `f` with a C callback that reports progress (as a percentage) to `progress`
+///
+/// A panic in `progress` is caught before it can unwind into C; later progress updates are
+/// skipped, and the panic is resumed once `f` returns.
+pub(crate) fn with_progress<T>(
+    progress: &mut dyn FnMut(u32),
+    f: impl FnOnce(ProgressCallback) -> T,
+) -> T {
+    with_byte_progress(&mut |cur, tot| progress(percent(cur, tot)), f)
+}
+
+/// Call `f` with a C callback that reports progress to `progress` as the number of bytes
+/// transferred so far and the total number of bytes, like [`with_progress`]
+pub(crate) fn with_byte_progress<T>(
+    progress: &mut dyn FnMut(usize, usize),
+    f: impl FnOnce(ProgressCallback) -> T,
+) -> T {
+    let mut callback = |cur: c_int, tot: c_int| progress(cur.max(0) as usize, tot.max(0) as usize);
+    let mut state = ProgressState {
+        callback: &mut callback,
+        panic: None,
+    };
+    let prev = PROGRESS.with(|p| p.replace(ptr::addr_of_mut!(state) as *mut c_void));
+    let ret = f(Some(progress_trampoline));
+    PROGRESS.with(|p| p.set(prev));
+
+    if let Some(panic) = state.panic {
+        panic::resume_unwind(panic);
+    }
+    ret
+}
+
+unsafe extern "C" fn progress_trampoline(cur: c_int, tot: c_int) {
+    let state = PROGRESS.with(|p| p.get()) as *mut ProgressState;
+    if state.is_null() {
+        return;
+    }
+    // SAFETY: `state` is only set by `with_byte_progress` while its `ProgressState` is alive, and
+    // the callback is invoked synchronously on the same thread
+    let state = unsafe { &mut *state };
+    if state.panic.is_some() {
+        return;
+    }
+    if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| (state.callback)(cur, tot))) {
+        state.panic = Some(panic);
+    }
+}
+
+/// Convert a `cur` of `tot` progress report to a percentage
+pub(crate) fn percent(cur: usize, tot: usize) -> u32 {
+    if tot == 0 {
+        return 0;
+    }
+    let percent = (cur as u64 * 100) / tot as u64;
+    percent.min(100) as u32
+}
+
+#[test]
+fn test_progress_trampoline() {
+    let mut reports = vec![];
+    let ret = with_progress(&mut |p| reports.push(p), |callback| {
+        let callback = callback.unwrap();
+        // SAFETY: Called synchronously within `with_progress`, like the library does
+        unsafe {
+            callback(0, 200);
+            callback(50, 200);
+            callback(200, 200);
+        }
+        7
+    });
+    assert_eq!(ret, 7);
+    assert_eq!(reports, vec![0, 25, 100]);
+
+    // Panics are held until the C call returns, and later updates are skipped
+    let mut calls = 0;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        with_progress(
+            &mut |_| {
+                calls += 1;
+                panic!("progress panic");
+            },
+            |callback| unsafe {
+                callback.unwrap()(1, 2);
+                callback.unwrap()(2, 2);
+            },
+        )
+    }));
+    assert!(result.is_err());
+    assert_eq!(calls, 1);
+    assert!(PROGRESS.with(|p| p.get()).is_null());
+}
+
+#[test]
+fn test_byte_progress() {
+    let mut reports = vec![];
+    with_byte_progress(&mut |cur, tot| reports.push((cur, tot)), |callback| {
+        // SAFETY: Called synchronously within `with_byte_progress`, like the library does
+        unsafe {
+            callback.unwrap()(-1, 4096);
+            callback.unwrap()(1024, 4096);
+        }
+    });
+    assert_eq!(reports, vec![(0, 4096), (1024, 4096)]);
+}
@@ -0,0 +1,341 @@
+use std::fmt;
+use std::io;
+use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{
+    get_switchtec_error, switchtec_status, switchtec_status_free, CStrExt, LinkRate, LinkWidth,
+    LtssmState, PartitionId, PortId, PortLocation, SwitchtecDevice,
+};
+
+/// Link status for a single port, returned by [`SwitchtecDevice::status`]
+///
+/// This is an owned copy of the `switchtec_status` struct populated by `switchtec_status`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PortStatus {
+    /// Partition the port is in
+    pub partition: PartitionId,
+    /// Stack the port is in
+    pub stack: u8,
+    /// Port number within the stack
+    pub stack_port: u8,
+    /// Physical port number
+    pub phys_port: PortId,
+    /// Logical port number
+    pub log_port: u8,
+    /// Whether this is an upstream port
+    pub upstream: bool,
+    /// Configured link width (number of lanes)
+    pub cfg_link_width: LinkWidth,
+    /// Negotiated link width (number of lanes)
+    pub link_width: LinkWidth,
+    /// Negotiated link rate (PCIe generation)
+    pub link_rate: LinkRate,
+    /// Whether the link is up
+    pub link_up: bool,
+    /// Link training state as reported by the library (E.g. "L0 (L0)"), see
+    /// [`PortStatus::ltssm_state`] for the parsed state
+    pub ltssm: String,
+}
+
+impl PortStatus {
+    /// Get the major link training state of the port
+    ///
+    /// This is parsed from [`PortStatus::ltssm`], which is kept as-is for states
+    /// [`LtssmState`] doesn't know about
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{LtssmState, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for port in device.status()? {
+    ///     if port.ltssm_state() == LtssmState::Recovery {
+    ///         println!("Port {} is stuck in {}", port.phys_port, port.ltssm);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ltssm_state(&self) -> LtssmState {
+        LtssmState::from(self.ltssm.as_str())
+    }
+
+    /// Get the location (partition, stack and port within the stack) of the port
+    ///
+    /// Ports are reported in hardware order, so sorting by location gives a stable layout
+    /// (E.g. for diffing two status dumps)
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{PortStatus, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let mut ports = device.status()?;
+    /// ports.sort_by_key(PortStatus::location);
+    /// for port in ports {
+    ///     println!("{}: {}", port.location(), port.ltssm);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn location(&self) -> PortLocation {
+        PortLocation::new(self.partition, self.stack, self.stack_port)
+    }
+}
+
+impl TryFrom<&switchtec_status> for PortStatus {
+    type Error = io::Error;
+
+    fn try_from(status: &switchtec_status) -> io::Result<Self> {
+        Ok(Self {
+            partition: PartitionId(status.port.partition),
+            stack: status.port.stack,
+            stack_port: status.port.stk_id,
+            phys_port: PortId(status.port.phys_id),
+            log_port: status.port.log_id,
+            upstream: status.port.upstream != 0,
+            cfg_link_width: status.cfg_lnk_width.into(),
+            link_width: status.neg_lnk_width.into(),
+            link_rate: status.link_rate.into(),
+            link_up: status.link_up != 0,
+            ltssm: status.ltssm_str.as_string()?,
+        })
+    }
+}
+
+impl fmt::Display for PortStatus {
+    /// Display the port status in the same layout as the `switchtec status` CLI command
+    ///
+    /// ```text
+    /// Partition 0, Logical Port ID 0 (USP):
+    ///     Phys Port ID:   0 (Stack 0, Port 0)
+    ///     Status:         UP
+    ///     LTSSM:          L0
+    ///     Max-Width:      x16
+    ///     Neg Width:      x16
+    ///     Rate:           Gen4
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let port_type = if self.upstream { "USP" } else { "DSP" };
+        writeln!(
+            f,
+            "Partition {}, Logical Port ID {} ({port_type}):",
+            self.partition, self.log_port
+        )?;
+        writeln!(
+            f,
+            "    Phys Port ID:   {} (Stack {}, Port {})",
+            self.phys_port, self.stack, self.stack_port
+        )?;
+        let status = if self.link_up { "UP" } else { "DOWN" };
+        writeln!(f, "    Status:         {status}")?;
+        writeln!(f, "    LTSSM:          {}", self.ltssm)?;
+        writeln!(f, "    Max-Width:      {}", self.cfg_link_width)?;
+        if self.link_up {
+            writeln!(f, "    Neg Width:      {}", self.link_width)?;
+            match self.link_rate.generation() {
+                Some(gen) => write!(f, "    Rate:           Gen{gen}")?,
+                None => write!(f, "    Rate:           {}", self.link_rate)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SwitchtecDevice {
+    /// Get the link status of every port on the device
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// for port in device.status()? {
+    ///     println!("{port}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// <https://microsemi.github.io/switchtec-user/group__Device.html#ga780a757b81a704c19217aca00f42b50e>
+    pub fn status(&self) -> io::Result<Vec<PortStatus>> {
+        self.with_raw_status(|statuses| statuses.iter().map(PortStatus::try_from).collect())
+    }
+
+    /// Call `f` with the raw `switchtec_status` structs of every port, freeing them afterwards
+    pub(crate) fn with_raw_status<T>(
+        &self,
+        f: impl FnOnce(&mut [switchtec_status]) -> io::Result<T>,
+    ) -> io::Result<T> {
+        // Response struct out-value, to be populated by `switchtec_status`
+        let mut status: *mut switchtec_status = ptr::null_mut();
+
+        // SAFETY: We're checking that the returned status is not null, and the `port_count`
+        // resp provides how many `switchtec_status` structs are present in the data
+        unsafe {
+            let port_count = traced!(switchtec_status(self.inner, ptr::addr_of_mut!(status)));
+            if status.is_null() || port_count.is_negative() {
+                // Negative value represents an error
+                return Err(get_switchtec_error());
+            }
+            let result = f(std::slice::from_raw_parts_mut(status, port_count as usize));
+
+            // Must be called after switchtec_status to free allocated status structs
+            // https://microsemi.github.io/switchtec-user/group__Device.html#ga742519774cbc236ba2d80a08a7dc6b5f
+            switchtec_status_free(status, port_count);
+
+            result
+        }
+    }
+
+    /// Iterate over the link status of every port on the device
+    ///
+    /// The iterator owns the port statuses, so it doesn't borrow the device
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let downstream = device.ports()?.filter(|port| !port.upstream).count();
+    /// println!("{downstream} downstream ports");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ports(&self) -> io::Result<impl Iterator<Item = PortStatus>> {
+        Ok(self.status()?.into_iter())
+    }
+
+    /// Iterate over the link status of the ports that have their link up
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// println!("{} active links", device.linked_ports()?.count());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn linked_ports(&self) -> io::Result<impl Iterator<Item = PortStatus>> {
+        Ok(self.ports()?.filter(|port| port.link_up))
+    }
+
+    /// Poll the status of the physical `port` until its link has finished training, or until
+    /// `timeout` has passed
+    ///
+    /// The link is considered trained once it's up and its LTSSM has left the training states
+    /// (see [`LtssmState::is_training`]), so it's in L0 or one of its power saving states. This
+    /// is useful after a hotplug or reset to confirm that the device below the port came up.
+    /// The status is polled every 100ms.
+    ///
+    /// - Returns the port's status once it's trained
+    /// - Returns a [`TimedOut`](io::ErrorKind::TimedOut) error with the last LTSSM state seen
+    ///   if the timeout passed first, or an [`InvalidInput`](io::ErrorKind::InvalidInput) error
+    ///   if the device has no such port
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let port = device.wait_for_link_training(PortId(8), Duration::from_secs(5))?;
+    /// println!("Link up: {} at {}", port.link_width, port.link_rate);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wait_for_link_training(
+        &self,
+        port: PortId,
+        timeout: Duration,
+    ) -> io::Result<PortStatus> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let status = self
+                .ports()?
+                .find(|status| status.phys_port == port)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("device has no port {port}"),
+                    )
+                })?;
+            if link_trained(&status) {
+                return Ok(status);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "link of port {port} didn't train within {timeout:?}, last LTSSM state: {}",
+                        status.ltssm
+                    ),
+                ));
+            }
+            thread::sleep(LINK_TRAINING_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+}
+
+/// Delay between polls of [`SwitchtecDevice::wait_for_link_training`]
+const LINK_TRAINING_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Check if the link of a port is up and has finished training
+fn link_trained(status: &PortStatus) -> bool {
+    status.link_up && !status.ltssm_state().is_training()
+}
+
+#[test]
+fn test_port_status_display() {
+    let port = PortStatus {
+        partition: PartitionId(0),
+        stack: 1,
+        stack_port: 2,
+        phys_port: PortId(10),
+        log_port: 3,
+        upstream: false,
+        cfg_link_width: LinkWidth::X4,
+        link_width: LinkWidth::X4,
+        link_rate: LinkRate::Gen4,
+        link_up: true,
+        ltssm: "L0".to_owned(),
+    };
+    assert_eq!(port.ltssm_state(), LtssmState::L0);
+    assert_eq!(
+        port.to_string(),
+        "Partition 0, Logical Port ID 3 (DSP):
+    Phys Port ID:   10 (Stack 1, Port 2)
+    Status:         UP
+    LTSSM:          L0
+    Max-Width:      x4
+    Neg Width:      x4
+    Rate:           Gen4"
+    );
+}
+
+#[test]
+fn test_link_trained() {
+    let port = |link_up: bool, ltssm: &str| PortStatus {
+        partition: PartitionId(0),
+        stack: 0,
+        stack_port: 0,
+        phys_port: PortId(0),
+        log_port: 0,
+        upstream: true,
+        cfg_link_width: LinkWidth::X16,
+        link_width: LinkWidth::X16,
+        link_rate: LinkRate::Gen4,
+        link_up,
+        ltssm: ltssm.to_owned(),
+    };
+    assert!(link_trained(&port(true, "L0 (L0)")));
+    assert!(link_trained(&port(true, "L1 (IDLE)")));
+    assert!(!link_trained(&port(true, "Recovery (RCVRLOCK)")));
+    assert!(!link_trained(&port(false, "Polling (ACTIVE)")));
+    assert!(!link_trained(&port(false, "L0 (L0)")));
+}
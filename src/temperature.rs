@@ -0,0 +1,60 @@
+use std::fmt;
+
+/// A temperature reading from a Switchtec device
+///
+/// The firmware reports temperatures in hundredths of a degree Celsius, which is the
+/// precision stored here (and how it's serialized with the `serde` feature)
+///
+/// ```
+/// use switchtec_user_sys::Temperature;
+///
+/// let temp = Temperature::from_centidegrees(5430);
+/// assert_eq!(temp.celsius(), 54.3);
+/// assert_eq!(temp.to_string(), "54.3°C");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Temperature(i32);
+
+impl Temperature {
+    /// Create a `Temperature` from hundredths of a degree Celsius
+    pub const fn from_centidegrees(centidegrees: i32) -> Self {
+        Self(centidegrees)
+    }
+
+    /// Create a `Temperature` from degrees Celsius, rounded to hundredths of a degree
+    pub fn from_celsius(celsius: f32) -> Self {
+        Self((celsius * 100.0).round() as i32)
+    }
+
+    /// Get the temperature in hundredths of a degree Celsius
+    pub const fn centidegrees(&self) -> i32 {
+        self.0
+    }
+
+    /// Get the temperature in degrees Celsius
+    pub fn celsius(&self) -> f32 {
+        self.0 as f32 / 100.0
+    }
+
+    /// Get the temperature in degrees Fahrenheit
+    pub fn fahrenheit(&self) -> f32 {
+        self.celsius() * 9.0 / 5.0 + 32.0
+    }
+}
+
+impl fmt::Display for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}°C", self.celsius())
+    }
+}
+
+#[test]
+fn test_temperature_conversions() {
+    let temp = Temperature::from_celsius(54.3);
+    assert_eq!(temp.centidegrees(), 5430);
+    assert_eq!(temp, Temperature::from_centidegrees(5430));
+    assert_eq!(temp.to_string(), "54.3°C");
+    assert!((temp.fahrenheit() - 129.74).abs() < 0.001);
+    assert_eq!(Temperature::from_centidegrees(-1250).to_string(), "-12.5°C");
+}
@@ -0,0 +1,76 @@
+//! Tracing of the `libswitchtec` calls made by the safe wrappers
+//!
+//! Every call that talks to the device goes through [`traced!`], which logs the function's
+//! name, what it returned and how long it took as a `log::debug!` record when the `log`
+//! feature is enabled. Without the feature, it's just the call.
+
+use std::fmt;
+
+/// Call a `libswitchtec` function through [`traced`], named after the function
+///
+/// The call is made inside a closure, so within an `unsafe` block the arguments are
+/// evaluated in the same unsafe context as a direct call.
+macro_rules! traced {
+    ($func:ident($($arg:expr),* $(,)?)) => {
+        $crate::trace::traced(stringify!($func), || $func($($arg),*))
+    };
+}
+
+/// Call `f`, which calls the `libswitchtec` function `name`, logging what it returned and how
+/// long it took with the `log` feature enabled
+///
+/// `errno` is kept as `f` left it, so the library's error can still be read afterwards.
+#[cfg(feature = "log")]
+pub(crate) fn traced<T: fmt::Debug>(name: &str, f: impl FnOnce() -> T) -> T {
+    let start = std::time::Instant::now();
+    let ret = f();
+    let elapsed = start.elapsed();
+    // Logging could overwrite `errno`
+    let errno = std::io::Error::last_os_error().raw_os_error().unwrap_or(0);
+    log::debug!("{name} returned {ret:?} in {elapsed:?}");
+    set_errno(errno);
+    ret
+}
+
+/// Call `f`, which calls the `libswitchtec` function `name`
+#[cfg(not(feature = "log"))]
+#[inline(always)]
+pub(crate) fn traced<T: fmt::Debug>(_name: &str, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+#[cfg(feature = "log")]
+fn set_errno(errno: i32) {
+    // SAFETY: `errno` is a thread-local the C library always has, so its location is valid for
+    // the current thread
+    unsafe {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let location = libc::__errno_location();
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let location = libc::__error();
+        *location = errno;
+    }
+}
+
+#[test]
+fn test_traced() {
+    fn add(a: i32, b: i32) -> i32 {
+        a + b
+    }
+    assert_eq!(traced!(add(1, 2)), 3);
+    assert_eq!(traced("add", || add(2, 3)), 5);
+}
+
+#[test]
+#[cfg(feature = "log")]
+fn test_traced_keeps_errno() {
+    let ret = traced("test_fn", || {
+        set_errno(libc::EBUSY);
+        -1
+    });
+    assert_eq!(ret, -1);
+    assert_eq!(
+        std::io::Error::last_os_error().raw_os_error(),
+        Some(libc::EBUSY)
+    );
+}
@@ -0,0 +1,188 @@
+//! Translation between port numbers, PFF instances and lane numbers
+
+use std::io;
+use std::os::raw::c_int;
+use std::ptr;
+
+use crate::ffi::{
+    switchtec_calc_lane_id, switchtec_calc_port_lane, switchtec_pff_to_port, switchtec_port_to_pff,
+};
+use crate::{PartitionId, PortId, SwitchtecDevice};
+
+impl SwitchtecDevice {
+    /// Get the PFF (Port Function Framework) instance of a logical port in a partition
+    ///
+    /// PFF instances index the per-port register blocks in the GAS (See
+    /// [`SwitchtecDevice::gas_map`]). Logical port 0 is the partition's upstream port.
+    ///
+    /// Returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error if the partition or port
+    /// is out of range
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{PartitionId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let pff = device.port_to_pff(PartitionId(0), 0)?;
+    /// println!("Upstream port of partition 0 is PFF {pff}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn port_to_pff(&self, partition: PartitionId, log_port: u8) -> io::Result<u32> {
+        let mut pff: c_int = 0;
+        // SAFETY: We know that device holds a valid/open switchtec device, and `pff` is only
+        // read after the library reports success
+        let ret = unsafe {
+            traced!(switchtec_port_to_pff(
+                self.inner,
+                partition.into(),
+                log_port.into(),
+                ptr::addr_of_mut!(pff),
+            ))
+        };
+        if ret < 0 {
+            return Err(self.perror(&format!(
+                "partition {partition} logical port {log_port} to PFF"
+            )));
+        }
+        in_range("PFF instance", pff)
+    }
+
+    /// Get the partition and logical port of a PFF (Port Function Framework) instance
+    ///
+    /// Returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error if the PFF instance isn't
+    /// assigned to a port
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let (partition, log_port) = device.pff_to_port(1)?;
+    /// println!("PFF 1 is partition {partition} logical port {log_port}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pff_to_port(&self, pff: u32) -> io::Result<(PartitionId, u8)> {
+        let pff_id = in_range("PFF instance", pff)?;
+        let mut partition: c_int = 0;
+        let mut log_port: c_int = 0;
+        // SAFETY: We know that device holds a valid/open switchtec device, and the out-values
+        // are only read after the library reports success
+        let ret = unsafe {
+            traced!(switchtec_pff_to_port(
+                self.inner,
+                pff_id,
+                ptr::addr_of_mut!(partition),
+                ptr::addr_of_mut!(log_port),
+            ))
+        };
+        if ret < 0 {
+            return Err(self.perror(&format!("PFF {pff} to port")));
+        }
+        Ok((
+            PartitionId(in_range("partition", partition)?),
+            in_range("logical port", log_port)?,
+        ))
+    }
+
+    /// Get the switch-wide lane number of a lane within a physical port
+    ///
+    /// Returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error if the port doesn't exist
+    /// or `port_lane` is beyond its width
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::{PortId, SwitchtecDevice};
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let lane = device.lane_id(PortId(8), 0)?;
+    /// println!("Lane 0 of port 8 is switch lane {lane}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lane_id(&self, port: PortId, port_lane: u8) -> io::Result<u32> {
+        // SAFETY: We know that device holds a valid/open switchtec device, and the library
+        // looks up the port status itself when it's not given one
+        let lane = unsafe {
+            traced!(switchtec_calc_lane_id(
+                self.inner,
+                port.into(),
+                port_lane.into(),
+                ptr::null_mut()
+            ))
+        };
+        if lane < 0 {
+            return Err(self.perror(&format!("lane {port_lane} of port {port} to lane number")));
+        }
+        in_range("lane number", lane)
+    }
+
+    /// Get the physical port and lane within that port of a switch-wide lane number
+    ///
+    /// This is the reverse of [`SwitchtecDevice::lane_id`]. Returns an
+    /// [`InvalidInput`](io::ErrorKind::InvalidInput) error if the lane isn't assigned to a port.
+    ///
+    /// ```no_run
+    /// use switchtec_user_sys::SwitchtecDevice;
+    ///
+    /// # fn main() -> anyhow::Result<()> {
+    /// let device = SwitchtecDevice::open("/dev/pciswitch0")?;
+    /// let (port, port_lane) = device.port_lane(16)?;
+    /// println!("Switch lane 16 is lane {port_lane} of port {port}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn port_lane(&self, lane: u32) -> io::Result<(PortId, u8)> {
+        let lane_id = in_range("lane number", lane)?;
+        let mut port: c_int = 0;
+        let mut port_lane: c_int = 0;
+        // SAFETY: We know that device holds a valid/open switchtec device, and the out-values
+        // are only read after the library reports success. The library looks up the port
+        // status itself when it's not given one
+        let ret = unsafe {
+            traced!(switchtec_calc_port_lane(
+                self.inner,
+                lane_id,
+                ptr::addr_of_mut!(port),
+                ptr::addr_of_mut!(port_lane),
+                ptr::null_mut(),
+            ))
+        };
+        if ret < 0 {
+            return Err(self.perror(&format!("lane number {lane} to port")));
+        }
+        Ok((
+            PortId(in_range("physical port", port)?),
+            in_range("port lane", port_lane)?,
+        ))
+    }
+}
+
+/// Convert a number to or from the C library's `int`, with an
+/// [`InvalidInput`](io::ErrorKind::InvalidInput) error if it's out of range
+fn in_range<T, U>(name: &str, value: T) -> io::Result<U>
+where
+    T: Copy + std::fmt::Display,
+    U: TryFrom<T>,
+{
+    U::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{name} {value} is out of range"),
+        )
+    })
+}
+
+#[test]
+fn test_in_range() {
+    assert_eq!(in_range::<c_int, u8>("port", 12).unwrap(), 12);
+    assert_eq!(in_range::<u32, c_int>("lane", 100).unwrap(), 100);
+    for err in [
+        in_range::<c_int, u8>("port", 256).unwrap_err(),
+        in_range::<c_int, u32>("lane", -1).unwrap_err(),
+        in_range::<u32, c_int>("lane", u32::MAX).unwrap_err(),
+    ] {
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
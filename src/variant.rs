@@ -0,0 +1,203 @@
+use std::fmt;
+
+use crate::ffi::{
+    switchtec_rev, switchtec_rev_SWITCHTEC_REVA, switchtec_rev_SWITCHTEC_REVB,
+    switchtec_rev_SWITCHTEC_REVC, switchtec_rev_SWITCHTEC_REV_UNKNOWN, switchtec_variant,
+    switchtec_variant_SWITCHTEC_PAX, switchtec_variant_SWITCHTEC_PAXA,
+    switchtec_variant_SWITCHTEC_PFX, switchtec_variant_SWITCHTEC_PFXA,
+    switchtec_variant_SWITCHTEC_PFXI, switchtec_variant_SWITCHTEC_PFXL,
+    switchtec_variant_SWITCHTEC_PSX, switchtec_variant_SWITCHTEC_PSXA,
+    switchtec_variant_SWITCHTEC_VAR_UNKNOWN,
+};
+
+/// Product variant of a Switchtec device
+///
+/// ```
+/// use switchtec_user_sys::Variant;
+/// use switchtec_user_sys::ffi::{switchtec_variant, switchtec_variant_SWITCHTEC_PAX};
+///
+/// let variant = Variant::from(switchtec_variant_SWITCHTEC_PAX);
+/// assert_eq!(variant, Variant::Pax);
+/// assert_eq!(variant.to_string(), "PAX");
+/// assert!(variant.is_fabric());
+/// assert_eq!(switchtec_variant::from(variant), switchtec_variant_SWITCHTEC_PAX);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Variant {
+    /// PFX fanout switch
+    Pfx,
+    /// PFX-L fanout switch
+    PfxL,
+    /// PFX-I fanout switch
+    PfxI,
+    /// PSX programmable switch
+    Psx,
+    /// PAX fabric switch
+    Pax,
+    /// PAX-A advanced fabric switch
+    PaxA,
+    /// PFX-A advanced fanout switch
+    PfxA,
+    /// PSX-A advanced programmable switch
+    PsxA,
+    /// The device reported a variant this crate doesn't know about
+    Unknown,
+}
+
+impl Variant {
+    /// Get the name of the variant (E.g. "PAX-A")
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Pfx => "PFX",
+            Self::PfxL => "PFX-L",
+            Self::PfxI => "PFX-I",
+            Self::Psx => "PSX",
+            Self::Pax => "PAX",
+            Self::PaxA => "PAX-A",
+            Self::PfxA => "PFX-A",
+            Self::PsxA => "PSX-A",
+            Self::Unknown => "Unknown",
+        }
+    }
+
+    /// Check if the variant is a fabric (PAX) switch, see [`fabric`](crate::fabric)
+    pub const fn is_fabric(&self) -> bool {
+        matches!(self, Self::Pax | Self::PaxA)
+    }
+}
+
+impl From<switchtec_variant> for Variant {
+    fn from(variant: switchtec_variant) -> Self {
+        match variant {
+            switchtec_variant_SWITCHTEC_PFX => Self::Pfx,
+            switchtec_variant_SWITCHTEC_PFXL => Self::PfxL,
+            switchtec_variant_SWITCHTEC_PFXI => Self::PfxI,
+            switchtec_variant_SWITCHTEC_PSX => Self::Psx,
+            switchtec_variant_SWITCHTEC_PAX => Self::Pax,
+            switchtec_variant_SWITCHTEC_PAXA => Self::PaxA,
+            switchtec_variant_SWITCHTEC_PFXA => Self::PfxA,
+            switchtec_variant_SWITCHTEC_PSXA => Self::PsxA,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl From<Variant> for switchtec_variant {
+    fn from(variant: Variant) -> Self {
+        match variant {
+            Variant::Pfx => switchtec_variant_SWITCHTEC_PFX,
+            Variant::PfxL => switchtec_variant_SWITCHTEC_PFXL,
+            Variant::PfxI => switchtec_variant_SWITCHTEC_PFXI,
+            Variant::Psx => switchtec_variant_SWITCHTEC_PSX,
+            Variant::Pax => switchtec_variant_SWITCHTEC_PAX,
+            Variant::PaxA => switchtec_variant_SWITCHTEC_PAXA,
+            Variant::PfxA => switchtec_variant_SWITCHTEC_PFXA,
+            Variant::PsxA => switchtec_variant_SWITCHTEC_PSXA,
+            Variant::Unknown => switchtec_variant_SWITCHTEC_VAR_UNKNOWN,
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Silicon revision of a Switchtec device
+///
+/// ```
+/// use switchtec_user_sys::Revision;
+/// use switchtec_user_sys::ffi::switchtec_rev_SWITCHTEC_REVB;
+///
+/// let revision = Revision::from(switchtec_rev_SWITCHTEC_REVB);
+/// assert_eq!(revision, Revision::RevB);
+/// assert_eq!(revision.to_string(), "Rev B");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Revision {
+    /// Revision A silicon
+    RevA,
+    /// Revision B silicon
+    RevB,
+    /// Revision C silicon
+    RevC,
+    /// The device reported a revision this crate doesn't know about
+    Unknown,
+}
+
+impl Revision {
+    /// Get the name of the revision (E.g. "Rev B")
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::RevA => "Rev A",
+            Self::RevB => "Rev B",
+            Self::RevC => "Rev C",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
+impl From<switchtec_rev> for Revision {
+    fn from(revision: switchtec_rev) -> Self {
+        match revision {
+            switchtec_rev_SWITCHTEC_REVA => Self::RevA,
+            switchtec_rev_SWITCHTEC_REVB => Self::RevB,
+            switchtec_rev_SWITCHTEC_REVC => Self::RevC,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl From<Revision> for switchtec_rev {
+    fn from(revision: Revision) -> Self {
+        match revision {
+            Revision::RevA => switchtec_rev_SWITCHTEC_REVA,
+            Revision::RevB => switchtec_rev_SWITCHTEC_REVB,
+            Revision::RevC => switchtec_rev_SWITCHTEC_REVC,
+            Revision::Unknown => switchtec_rev_SWITCHTEC_REV_UNKNOWN,
+        }
+    }
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[test]
+fn test_variant_round_trip() {
+    for raw in [
+        switchtec_variant_SWITCHTEC_PFX,
+        switchtec_variant_SWITCHTEC_PFXL,
+        switchtec_variant_SWITCHTEC_PFXI,
+        switchtec_variant_SWITCHTEC_PSX,
+        switchtec_variant_SWITCHTEC_PAX,
+        switchtec_variant_SWITCHTEC_PAXA,
+        switchtec_variant_SWITCHTEC_PFXA,
+        switchtec_variant_SWITCHTEC_PSXA,
+        switchtec_variant_SWITCHTEC_VAR_UNKNOWN,
+    ] {
+        assert_eq!(switchtec_variant::from(Variant::from(raw)), raw);
+    }
+    assert_eq!(Variant::from(0x42), Variant::Unknown);
+    assert_eq!(Variant::PaxA.to_string(), "PAX-A");
+    assert!(!Variant::Pfx.is_fabric());
+}
+
+#[test]
+fn test_revision_round_trip() {
+    for raw in [
+        switchtec_rev_SWITCHTEC_REVA,
+        switchtec_rev_SWITCHTEC_REVB,
+        switchtec_rev_SWITCHTEC_REVC,
+        switchtec_rev_SWITCHTEC_REV_UNKNOWN,
+    ] {
+        assert_eq!(switchtec_rev::from(Revision::from(raw)), raw);
+    }
+    assert_eq!(Revision::from(0x42), Revision::Unknown);
+    assert_eq!(Revision::RevA.to_string(), "Rev A");
+}
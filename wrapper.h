@@ -1 +1,4 @@
-#include <switchtec/switchtec.h>
\ No newline at end of file
+#include <switchtec/switchtec.h>
+#include <switchtec/gas.h>
+#include <switchtec/fabric.h>
+#include <switchtec/mfg.h>